@@ -0,0 +1,212 @@
+//! Runs the generators from `build.rs`:
+//!
+//! ```no_run
+//! // build.rs
+//! protolang_build::Config::new()
+//!   .target("kotlin")
+//!   .input("schema/")
+//!   .compile()
+//!   .unwrap();
+//! ```
+//!
+//! Every `.proto` file and `module.yaml` that was read is reported with `cargo:rerun-if-changed`,
+//! so the build script reruns only when the schema changes.
+
+use std::env;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use protolang_codegen::{generate, FileError, Options, Registry};
+use protolang_parser::module::ModuleError;
+use protolang_parser::workspace::{Workspace, WorkspaceError, WorkspaceRoot, MODULE_DESCRIPTOR};
+
+#[derive(Debug)]
+pub enum Error {
+  /// Schema could not be read or has syntax errors
+  Workspace(WorkspaceError),
+  /// Module dependency or visibility violations, see [Workspace::validate]
+  Modules(Vec<ModuleError>),
+  /// Schema files a target could not generate, the other files are still written
+  Generate(Vec<FileError>),
+  Io {
+    path: PathBuf,
+    error: io::Error,
+  },
+  /// Neither [Config::out_dir] nor `OUT_DIR` is set
+  MissingOutDir,
+  /// Target name is not in [Registry::builtin]
+  UnknownTarget(String),
+}
+
+impl Display for Error {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Workspace(error) => write!(f, "{}", error),
+      Error::Modules(errors) => write!(f, "{}", errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")),
+      Error::Generate(errors) => write!(f, "{}", errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")),
+      Error::Io { path, error } => write!(f, "{:?}: {}", path, error),
+      Error::MissingOutDir => write!(f, "output directory is not set and OUT_DIR is not defined"),
+      Error::UnknownTarget(name) => write!(f, "unknown target '{}'", name),
+    }
+  }
+}
+
+impl error::Error for Error {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      Error::Workspace(error) => Some(error),
+      Error::Io { error, .. } => Some(error),
+      Error::Modules(_) | Error::Generate(_) | Error::MissingOutDir | Error::UnknownTarget(_) => None,
+    }
+  }
+}
+
+impl From<WorkspaceError> for Error {
+  fn from(error: WorkspaceError) -> Self {
+    Error::Workspace(error)
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+  targets: Vec<String>,
+  inputs: Vec<PathBuf>,
+  read_only_inputs: Vec<PathBuf>,
+  out_dir: Option<PathBuf>,
+  options: Options,
+}
+
+impl Config {
+  pub fn new() -> Config {
+    Config::default()
+  }
+
+  /// Adds a target to generate by its name, may be called several times. All targets write into
+  /// [Config::out_dir].
+  pub fn target(&mut self, name: impl Into<String>) -> &mut Config {
+    self.targets.push(name.into());
+    self
+  }
+
+  /// Adds a schema root directory or a single `.proto` file. All inputs are loaded into one
+  /// namespace.
+  pub fn input(&mut self, path: impl AsRef<Path>) -> &mut Config {
+    self.inputs.push(path.as_ref().to_path_buf());
+    self
+  }
+
+  /// Adds a schema root whose definitions can be referenced, but are not generated
+  pub fn read_only_input(&mut self, path: impl AsRef<Path>) -> &mut Config {
+    self.read_only_inputs.push(path.as_ref().to_path_buf());
+    self
+  }
+
+  /// Directory for the generated sources, `OUT_DIR` by default
+  pub fn out_dir(&mut self, path: impl AsRef<Path>) -> &mut Config {
+    self.out_dir = Some(path.as_ref().to_path_buf());
+    self
+  }
+
+  /// Package prepended to the packages derived from source paths
+  pub fn root_package(&mut self, package: impl Into<String>) -> &mut Config {
+    self.options.root_package = Some(package.into());
+    self
+  }
+
+  /// Restricts generation to a module, may be called several times. All modules are generated
+  /// by default.
+  pub fn module(&mut self, module: impl Into<String>) -> &mut Config {
+    self.options.modules.push(module.into());
+    self
+  }
+
+  /// Loads every input and writes the sources of every target. Files whose contents did not change
+  /// are not touched, so that their modification time stays the same.
+  pub fn compile(&self) -> Result<(), Error> {
+    let registry = Registry::builtin();
+    let targets = self.targets.iter()
+      .map(|name| registry.get(name).ok_or_else(|| Error::UnknownTarget(name.to_owned())))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let out_dir = match &self.out_dir {
+      Some(out_dir) => out_dir.clone(),
+      None => env::var_os("OUT_DIR").map(PathBuf::from).ok_or(Error::MissingOutDir)?,
+    };
+
+    let roots = self.inputs.iter().map(WorkspaceRoot::new)
+      .chain(self.read_only_inputs.iter().map(|it| WorkspaceRoot::new(it).read_only(true)))
+      .collect::<Vec<_>>();
+    for root in &roots {
+      // Also covers files added to the directory later
+      println!("cargo:rerun-if-changed={}", root.path.display());
+    }
+
+    let workspace = Workspace::load_roots(roots)?;
+    for file in &workspace.files {
+      println!("cargo:rerun-if-changed={}", workspace.path(file).display());
+    }
+    for module in workspace.modules.values() {
+      println!("cargo:rerun-if-changed={}", workspace.roots[module.root].path.join(&module.dir).join(MODULE_DESCRIPTOR).display());
+    }
+
+    let errors = workspace.validate();
+    if !errors.is_empty() {
+      return Err(Error::Modules(errors));
+    }
+
+    let mut errors = Vec::new();
+    for target in &targets {
+      let generation = generate(*target, &workspace, &self.options);
+      for file in generation.files {
+        write_if_changed(&out_dir.join(&file.path), &file.contents)?;
+      }
+      errors.extend(generation.errors);
+    }
+    if !errors.is_empty() {
+      return Err(Error::Generate(errors));
+    }
+    Ok(())
+  }
+}
+
+fn write_if_changed(path: &Path, contents: &str) -> Result<(), Error> {
+  let io_error = |error| Error::Io { path: path.to_path_buf(), error };
+  if fs::read_to_string(path).is_ok_and(|it| it == contents) {
+    return Ok(());
+  }
+
+  fs::create_dir_all(path.parent().unwrap()).map_err(io_error)?;
+  fs::write(path, contents).map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use crate::{Config, Error};
+
+  #[test]
+  fn compile() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let schema = root.join("schema");
+    fs::create_dir_all(schema.join("battle")).unwrap();
+    fs::write(schema.join("module.yaml"), "").unwrap();
+    fs::write(schema.join("battle/BuildTestTeam.proto"), "enum BuildTestTeam : i32 {\n  RED = 0;\n}\n").unwrap();
+
+    let out_dir = root.join("out");
+    Config::new().target("kotlin").input(&schema).out_dir(&out_dir).compile().unwrap();
+    let generated = fs::read_to_string(out_dir.join("battle/BuildTestTeam.generated.kt")).unwrap();
+    assert!(generated.contains("enum class BuildTestTeam"));
+
+    let result = Config::new().target("swift").input(&schema).out_dir(&out_dir).compile();
+    assert!(matches!(result, Err(Error::UnknownTarget(name)) if name == "swift"));
+
+    fs::write(schema.join("battle/Broken.proto"), "type").unwrap();
+    let result = Config::new().target("kotlin").input(&schema).out_dir(&out_dir).compile();
+    assert!(matches!(result, Err(Error::Workspace(_))));
+  }
+}
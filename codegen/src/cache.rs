@@ -0,0 +1,226 @@
+//! Content-hash cache for [crate::generate_incremental], stored as [CACHE_FILE] in the output
+//! root. It records, per target and schema file, a hash of everything the outputs of the file are
+//! rendered from and the paths and content hashes of those outputs. A file whose hash did not
+//! change and whose outputs are all still on disk unmodified is not rendered again.
+//!
+//! The hash covers the file, every definition it refers to directly or not, the module packages,
+//! the generation options and [Target::fingerprint]. The whole cache is dropped when the
+//! generator version changes. Schema files are keyed by their path relative to their input root,
+//! like the manifest sources, so the cache does not depend on the working directory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use protolang_parser::module::referenced_names;
+use protolang_parser::workspace::{SourceFile, Workspace};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::{GeneratedFile, Options, Target};
+
+pub const CACHE_FILE: &str = ".protolang-cache.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+  hash: String,
+  /// Output path relative to the output root -> hash of its contents
+  outputs: BTreeMap<PathBuf, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheData {
+  version: String,
+  /// Target name -> schema file path relative to its root, see [SourceFile::path] -> entry
+  targets: BTreeMap<String, BTreeMap<PathBuf, Entry>>,
+}
+
+#[derive(Debug)]
+pub struct Cache {
+  output_root: PathBuf,
+  data: CacheData,
+}
+
+impl Cache {
+  /// Cache of the given output root, empty if there is none or it cannot be read
+  pub fn load(output_root: &Path) -> Cache {
+    let path = output_root.join(CACHE_FILE);
+    let data = match fs::read_to_string(&path) {
+      Ok(content) => match serde_json::from_str::<CacheData>(&content) {
+        Ok(data) if data.version == env!("CARGO_PKG_VERSION") => data,
+        Ok(data) => {
+          debug!("Ignoring cache {:?} of version {}", path, data.version);
+          CacheData::default()
+        }
+        Err(error) => {
+          warn!("Ignoring invalid cache {:?}: {}", path, error);
+          CacheData::default()
+        }
+      },
+      Err(_) => CacheData::default(),
+    };
+    Cache { output_root: output_root.to_path_buf(), data }
+  }
+
+  pub fn save(&mut self) -> io::Result<()> {
+    self.data.version = env!("CARGO_PKG_VERSION").to_owned();
+    fs::create_dir_all(&self.output_root)?;
+    fs::write(self.output_root.join(CACHE_FILE), serde_json::to_string_pretty(&self.data)?)
+  }
+
+  /// Outputs of a schema file if its hash is unchanged and the files on disk still have the
+  /// recorded contents
+  pub(crate) fn outputs(&self, target: &str, file: &SourceFile, hash: &str) -> Option<Vec<PathBuf>> {
+    let entry = self.data.targets.get(target)?.get(&file.path)?;
+    if entry.hash != hash {
+      return None;
+    }
+    for (path, contents_hash) in &entry.outputs {
+      let contents = fs::read(self.output_root.join(path)).ok()?;
+      if hash_contents(&contents) != *contents_hash {
+        debug!("{:?} was modified since it was generated", path);
+        return None;
+      }
+    }
+    Some(entry.outputs.keys().cloned().collect())
+  }
+
+  pub(crate) fn insert(&mut self, target: &str, file: &SourceFile, hash: String, outputs: &[GeneratedFile]) {
+    let outputs = outputs.iter().map(|it| (it.path.clone(), hash_contents(it.contents.as_bytes()))).collect();
+    self.data.targets.entry(target.to_owned()).or_default().insert(file.path.clone(), Entry { hash, outputs });
+  }
+
+  pub(crate) fn remove(&mut self, target: &str, file: &SourceFile) {
+    if let Some(entries) = self.data.targets.get_mut(target) {
+      entries.remove(&file.path);
+    }
+  }
+
+  /// Drops the entries of schema files that are no longer in the workspace
+  pub(crate) fn retain(&mut self, target: &str, workspace: &Workspace) {
+    let paths = workspace.files.iter().map(|it| &it.path).collect::<BTreeSet<_>>();
+    if let Some(entries) = self.data.targets.get_mut(target) {
+      entries.retain(|path, _| paths.contains(path));
+    }
+  }
+}
+
+/// Hash of everything the outputs of `file` are rendered from, see the module documentation
+pub(crate) fn hash_file(target: &dyn Target, workspace: &Workspace, file: &SourceFile, options: &Options) -> String {
+  let mut hasher = Sha256::new();
+  let mut field = |value: &str| {
+    hasher.update(value.len().to_le_bytes());
+    hasher.update(value.as_bytes());
+  };
+
+  field(target.name());
+  field(&target.fingerprint());
+  field(options.root_package.as_deref().unwrap_or_default());
+  if let Some(builtins) = &options.builtins {
+    for (name, fqn) in builtins.iter().collect::<BTreeMap<_, _>>() {
+      field(name);
+      field(fqn);
+    }
+  }
+  for import in options.imports.iter().flatten() {
+    field(import);
+  }
+
+  for file in dependencies(workspace, file) {
+    field(&file.path.to_string_lossy());
+    field(workspace.module(file).and_then(|it| it.package(target.name())).unwrap_or_default());
+    field(&file.content);
+  }
+  format!("{:x}", hasher.finalize())
+}
+
+fn hash_contents(contents: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(contents))
+}
+
+/// The file itself and the files declaring the definitions it refers to, directly or not, in
+/// workspace order
+fn dependencies<'a>(workspace: &'a Workspace, file: &'a SourceFile) -> Vec<&'a SourceFile> {
+  let mut visited = BTreeSet::new();
+  let mut pending = workspace.files.iter().position(|it| std::ptr::eq(it, file)).into_iter().collect::<Vec<_>>();
+  while let Some(index) = pending.pop() {
+    if !visited.insert(index) {
+      continue;
+    }
+    for definition in &workspace.files[index].definitions {
+      pending.extend(referenced_names(definition).into_iter().filter_map(|name| workspace.symbols.get(name)).map(|it| it.file));
+    }
+  }
+  visited.into_iter().map(|index| &workspace.files[index]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::target::kotlin::Kotlin;
+  use crate::{generate_incremental, write_tree};
+
+  #[test]
+  fn incremental_generation() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let schema = root.join("schema");
+    let output = root.join("output");
+    let write_schema = |team: &str, notifications: &str| {
+      write_tree(&schema, &[
+        ("module.yaml", ""),
+        ("IncrementalTeam.proto", team),
+        ("IncrementalNotifications.proto", notifications),
+        ("IncrementalModel.proto", "model IncrementalModel = 1 {\n  client ping(team: IncrementalTeam) = 2;\n  include IncrementalNotifications;\n}\n"),
+        ("IncrementalOther.proto", "enum IncrementalOther : i32 {\n  ONE = 1;\n}\n"),
+      ]);
+    };
+    let run_in = |schema: &Path| {
+      let workspace = Workspace::load(schema).unwrap();
+      let mut cache = Cache::load(&output);
+      let generation = generate_incremental(&Kotlin, &workspace, &Options::default(), &mut cache);
+      for file in &generation.files {
+        fs::create_dir_all(output.join(&file.path).parent().unwrap()).unwrap();
+        fs::write(output.join(&file.path), &file.contents).unwrap();
+      }
+      cache.save().unwrap();
+      generation
+    };
+    let run = || run_in(&schema);
+
+    let notifications = "interface IncrementalNotifications {\n  client notify() = 100;\n}\n";
+    write_schema("enum IncrementalTeam : i32 {\n  RED = 0;\n}\n", notifications);
+    assert_eq!(run().generated.len(), 4);
+    let generation = run();
+    assert!(generation.generated.is_empty());
+    assert_eq!(generation.cached.len(), 4);
+
+    // The model refers to the changed enum, the other enum is unaffected
+    let team = "enum IncrementalTeam : i32 {\n  RED = 0;\n  BLUE = 1;\n}\n";
+    write_schema(team, notifications);
+    let generation = run();
+    assert_eq!(generation.generated, vec![schema.join("IncrementalModel.proto"), schema.join("IncrementalTeam.proto")]);
+    assert_eq!(generation.cached, vec![schema.join("IncrementalNotifications.proto"), schema.join("IncrementalOther.proto")]);
+
+    // The model includes the changed interface
+    write_schema(team, "interface IncrementalNotifications {\n  client notify() = 555;\n}\n");
+    let generation = run();
+    assert_eq!(generation.generated, vec![schema.join("IncrementalModel.proto"), schema.join("IncrementalNotifications.proto")]);
+    assert!(generation.files.iter().any(|it| it.contents.contains("@ModelMethod(555)")));
+
+    // Missing and modified outputs are rendered again
+    fs::remove_file(output.join("IncrementalOther.generated.kt")).unwrap();
+    assert_eq!(run().generated, vec![schema.join("IncrementalOther.proto")]);
+    fs::write(output.join("IncrementalTeam.generated.kt"), "").unwrap();
+    assert_eq!(run().generated, vec![schema.join("IncrementalTeam.proto")]);
+
+    // Entries are relative to the input root, e.g. when running from another directory
+    let moved = root.join("moved");
+    fs::rename(&schema, &moved).unwrap();
+    let generation = run_in(&moved);
+    assert!(generation.generated.is_empty());
+    assert_eq!(generation.cached.len(), 4);
+  }
+}
@@ -0,0 +1,222 @@
+//! Project configuration, read from a `protolang.toml` next to or above the schema root:
+//! ```toml
+//! # Paths are relative to the directory of this file
+//! inputs = ["schema"]
+//! # Definitions that can be referenced, but are generated elsewhere
+//! read_only_inputs = ["../platform/schema"]
+//! # Gitignore-style globs relative to each input, see protolang_parser::discovery
+//! include = ["battle/", "lobby/"]
+//! exclude = ["legacy/", "*Test.proto"]
+//! root_package = "com.example"
+//! # Warnings reported by `protolang-generator check`, see protolang_parser::check::LINTS
+//! lints = ["missing-docs", "naming"]
+//!
+//! [targets.kotlin]
+//! output = "server/src/main/kotlin"
+//! imports = ["com.example.runtime.*"]
+//!
+//! [targets.kotlin.builtins]
+//! Resource = "com.example.runtime.Resource"
+//!
+//! [targets.actionscript]
+//! output = "client/src"
+//! root_package = "com.example.client"
+//! # Only the mappings below, without the target defaults
+//! default_builtins = false
+//! builtins = { Long = "alternativa.types.Long" }
+//! ```
+//!
+//! Command line flags take precedence over the file.
+
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tracing::info;
+
+use crate::target::Target;
+use crate::Options;
+
+pub const CONFIG_FILE: &str = "protolang.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+  /// Directory of the config file
+  #[serde(skip)]
+  pub root: PathBuf,
+  /// Schema roots
+  #[serde(default)]
+  pub inputs: Vec<PathBuf>,
+  /// Schema roots that are resolved, but not generated
+  #[serde(default)]
+  pub read_only_inputs: Vec<PathBuf>,
+  /// Only files matching these globs are loaded, all files if empty
+  #[serde(default)]
+  pub include: Vec<String>,
+  /// Files and directories skipped when loading the inputs, after the default exclusions
+  #[serde(default)]
+  pub exclude: Vec<String>,
+  /// Package prepended to the packages derived from source paths, for every target
+  pub root_package: Option<String>,
+  /// Optional schema lints run by the `check` command
+  #[serde(default)]
+  pub lints: Vec<String>,
+  /// Target name -> settings
+  #[serde(default)]
+  pub targets: BTreeMap<String, TargetConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetConfig {
+  pub output: Option<PathBuf>,
+  /// Overrides [ProjectConfig::root_package]
+  pub root_package: Option<String>,
+  /// Simple name -> fully qualified name, merged over [Target::builtin_fqn]
+  #[serde(default)]
+  pub builtins: HashMap<String, String>,
+  /// Whether [TargetConfig::builtins] extends the target defaults or replaces them
+  #[serde(default = "default_true")]
+  pub default_builtins: bool,
+  /// Replaces the default imports of the target
+  pub imports: Option<Vec<String>>,
+}
+
+fn default_true() -> bool {
+  true
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+  Io {
+    path: PathBuf,
+    error: io::Error,
+  },
+  Invalid {
+    path: PathBuf,
+    error: toml::de::Error,
+  },
+}
+
+impl Display for ConfigError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      ConfigError::Io { path, error } => write!(f, "{:?}: {}", path, error),
+      ConfigError::Invalid { path, error } => write!(f, "{:?}: {}", path, error),
+    }
+  }
+}
+
+impl error::Error for ConfigError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      ConfigError::Io { error, .. } => Some(error),
+      ConfigError::Invalid { error, .. } => Some(error),
+    }
+  }
+}
+
+impl ProjectConfig {
+  pub fn load(path: &Path) -> Result<ProjectConfig, ConfigError> {
+    let content = fs::read_to_string(path).map_err(|error| ConfigError::Io { path: path.to_path_buf(), error })?;
+    let mut config: ProjectConfig = toml::from_str(&content).map_err(|error| ConfigError::Invalid { path: path.to_path_buf(), error })?;
+    // Absolute, so that it can be compared with the canonical input paths
+    let path = path.canonicalize().map_err(|error| ConfigError::Io { path: path.to_path_buf(), error })?;
+    config.root = path.parent().unwrap().to_path_buf();
+    Ok(config)
+  }
+
+  /// Looks for [CONFIG_FILE] in the directory of `input` and its parents
+  pub fn discover(input: &Path) -> Result<Option<ProjectConfig>, ConfigError> {
+    let input = input.canonicalize().map_err(|error| ConfigError::Io { path: input.to_path_buf(), error })?;
+    let start = if input.is_dir() { input.as_path() } else { input.parent().unwrap() };
+    for dir in start.ancestors() {
+      let path = dir.join(CONFIG_FILE);
+      if path.is_file() {
+        info!("Using configuration {:?}", path);
+        return ProjectConfig::load(&path).map(Some);
+      }
+    }
+    Ok(None)
+  }
+
+  pub fn inputs(&self) -> Vec<PathBuf> {
+    self.inputs.iter().map(|it| self.root.join(it)).collect()
+  }
+
+  pub fn read_only_inputs(&self) -> Vec<PathBuf> {
+    self.read_only_inputs.iter().map(|it| self.root.join(it)).collect()
+  }
+
+  pub fn output(&self, target: &str) -> Option<PathBuf> {
+    self.targets.get(target).and_then(|it| it.output.as_ref()).map(|it| self.root.join(it))
+  }
+
+  /// Generation options of a target, without any module selection
+  pub fn options(&self, target: &dyn Target) -> Options {
+    let target_config = self.targets.get(target.name());
+    let builtins = target_config.filter(|it| !it.builtins.is_empty() || !it.default_builtins).map(|it| {
+      let mut builtins = if it.default_builtins { target.builtin_fqn() } else { HashMap::new() };
+      builtins.extend(it.builtins.clone());
+      builtins
+    });
+
+    Options {
+      root_package: target_config.and_then(|it| it.root_package.clone()).or_else(|| self.root_package.clone()),
+      builtins,
+      imports: target_config.and_then(|it| it.imports.clone()),
+      ..Options::default()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+  use crate::target::kotlin::Kotlin;
+
+  #[test]
+  fn project_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("schema/battle")).unwrap();
+    fs::write(root.join("protolang.toml"), r#"
+inputs = ["schema"]
+exclude = ["legacy/"]
+root_package = "com.example"
+
+[targets.kotlin]
+output = "out"
+root_package = "com.example.server"
+imports = []
+builtins = { Resource = "com.example.Resource" }
+
+[targets.actionscript]
+default_builtins = false
+"#).unwrap();
+
+    let config = ProjectConfig::discover(&root.join("schema/battle")).unwrap().unwrap();
+    let root = root.canonicalize().unwrap();
+    assert_eq!(config.inputs(), vec![root.join("schema")]);
+    assert_eq!(config.exclude, vec!["legacy/".to_owned()]);
+    assert_eq!(config.output("kotlin"), Some(root.join("out")));
+
+    let options = config.options(&Kotlin);
+    assert_eq!(options.root_package.as_deref(), Some("com.example.server"));
+    assert_eq!(options.imports, Some(Vec::new()));
+    let builtins = options.builtins.unwrap();
+    assert_eq!(builtins["Resource"], "com.example.Resource");
+    assert_eq!(builtins.len(), Kotlin.builtin_fqn().len());
+
+    let options = config.options(&crate::target::actionscript::Actionscript);
+    assert_eq!(options.root_package.as_deref(), Some("com.example"));
+    assert_eq!(options.builtins, Some(HashMap::new()));
+  }
+}
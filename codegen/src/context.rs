@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use protolang_parser::hl::{self, Definition};
+use protolang_parser::workspace::{SymbolKind, Workspace};
+use itertools::Itertools;
+use regex::{Captures, Regex};
+use tracing::{debug, info};
+
+use crate::target::Target;
+use crate::Options;
+
+/// Lookup tables shared by the code generators of a single [crate::generate] run
+#[derive(Debug, Default)]
+pub struct Context {
+  /// Simple name -> fully qualified name of types provided by the target runtime, see [Options::builtins]
+  pub builtin_fqn: HashMap<String, String>,
+  /// Model constructor reference (`TankModel.Constructor`) -> constructor class name
+  pub definition_fqn: HashMap<String, String>,
+  /// Definition name -> path relative to the root package
+  pub definition_fqn_2: HashMap<String, String>,
+  /// Definition name -> package replacing the root package, for modules that set one
+  pub definition_packages: HashMap<String, String>,
+  /// Model name -> lowered model, used by templates
+  pub model_definitions: HashMap<String, hl::Model>,
+  pub enum_types: HashSet<String>,
+  /// Table name -> regex matching any of its names, see [Context::replace_names]. Shared by the
+  /// files rendered in parallel.
+  pub(crate) regex_cache: Mutex<HashMap<String, Regex>>,
+}
+
+impl Context {
+  pub fn new(target: &dyn Target, workspace: &Workspace, options: &Options) -> Context {
+    let mut context = Context {
+      builtin_fqn: options.builtins.clone().unwrap_or_else(|| target.builtin_fqn()),
+      enum_types: workspace.symbols.iter().filter(|it| it.kind == SymbolKind::Enum).map(|it| it.name.clone()).collect(),
+      ..Context::default()
+    };
+
+    context.index_definitions(workspace);
+    context.index_definition_packages(target, workspace);
+    context.index_model_definitions(workspace);
+    target.prepare(&mut context, workspace);
+    context
+  }
+
+  fn index_definitions(&mut self, workspace: &Workspace) {
+    info!("generating definition index...");
+
+    for (file, definition) in workspace.definitions() {
+      let relative_path = file.dotted_path();
+      let (simple_name, relative_path) = match definition {
+        Definition::Model(model) => (model.name.to_owned(), format!("{}Base", relative_path)),
+        Definition::Type(type_def) => (type_def.name.to_owned(), relative_path),
+        Definition::Enum(enum_def) => (enum_def.name.to_owned(), relative_path),
+        _ => continue
+      };
+
+      debug!("registered definition {} -> {}", simple_name, relative_path);
+      self.definition_fqn_2.insert(simple_name, relative_path);
+    }
+
+    info!("definition index generated");
+  }
+
+  fn index_definition_packages(&mut self, target: &dyn Target, workspace: &Workspace) {
+    for symbol in workspace.symbols.iter() {
+      let file = &workspace.files[symbol.file];
+      if let Some(package) = workspace.module(file).and_then(|it| it.package(target.name())) {
+        self.definition_packages.insert(symbol.name.clone(), package.to_owned());
+      }
+    }
+  }
+
+  /// Replaces every whole word occurrence of a name of `names` with `replace(name, value)`. All
+  /// names are matched in a single pass, so that replacements are never rewritten again and the
+  /// result does not depend on the map order. Longer names win, e.g. `TankModel.Constructor` over
+  /// `TankModel`. `table` identifies `names` in [Context::regex_cache].
+  pub(crate) fn replace_names(&self, table: &str, names: &HashMap<String, String>, value: &str, replace: impl Fn(&str, &str) -> String) -> String {
+    if names.is_empty() {
+      return value.to_owned();
+    }
+
+    let regex = self.regex_cache.lock().unwrap()
+      .entry(table.to_owned())
+      .or_insert_with(|| {
+        let alternatives = names.keys()
+          .sorted_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)))
+          .map(|it| regex::escape(it))
+          .join("|");
+        Regex::new(&format!(r"\b(?:{})\b", alternatives)).unwrap()
+      })
+      .clone();
+    regex.replace_all(value, |captures: &Captures| replace(&captures[0], &names[&captures[0]])).into_owned()
+  }
+
+  /// Root package of the definitions with the given simple name
+  pub fn root_package<'a>(&'a self, name: &str, root_package: Option<&'a str>) -> Option<&'a str> {
+    self.definition_packages.get(name).map(String::as_str).or(root_package)
+  }
+
+  fn index_model_definitions(&mut self, workspace: &Workspace) {
+    info!("generating model definition index...");
+
+    for definition in workspace.models() {
+      debug!("registered model definition {}", definition.name);
+      self.model_definitions.insert(definition.name.clone(), definition.clone());
+    }
+
+    info!("model definition index generated");
+  }
+}
@@ -0,0 +1,822 @@
+//! Reverse generation of `.proto` definitions from decompiled ActionScript client sources.
+//!
+//! Models are found by their `[ModelInfo]` classes, types and enums are recovered from the codecs
+//! the models reference.
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR_STR};
+
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use protolang_parser::diagnostic::Diagnostic;
+use protolang_parser::discovery::{Discovery, DiscoveryError, Globs};
+use protolang_parser::hl::{self, Meta};
+use rayon::prelude::*;
+use regex::{escape, Regex};
+use tracing::{debug, error, info, warn};
+
+use crate::target::kotlin::Kotlin;
+use crate::target::protolang::{generate_protolang_code, generate_protolang_code_enum, generate_protolang_code_type};
+use crate::{convert_to_id, get_types_from_generic, GeneratedFile, Target};
+
+/// Default globs of the files scanned for `[ModelInfo]` classes
+pub const MODEL_FILES: [&str; 1] = ["*Model*.as"];
+
+#[derive(Debug)]
+pub enum ImportErrorKind {
+  Io {
+    path: PathBuf,
+    error: io::Error,
+  },
+  /// Source that does not look like decompiled client code, e.g. a model without a model id
+  Unrecognized(String),
+  /// Type or enum used by the model without a `Codec<name>.as` among the sources
+  MissingCodec(String),
+}
+
+impl Display for ImportErrorKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      ImportErrorKind::Io { path, error } => write!(f, "failed to read {:?}: {}", path, error),
+      ImportErrorKind::Unrecognized(message) => write!(f, "{}", message),
+      ImportErrorKind::MissingCodec(name) => write!(f, "cannot find a codec for type {}", name),
+    }
+  }
+}
+
+/// Model source that could not be imported, the other models are still imported
+#[derive(Debug)]
+pub struct ImportError {
+  pub path: PathBuf,
+  pub kind: ImportErrorKind,
+}
+
+impl Display for ImportError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}: {}", self.path, self.kind)
+  }
+}
+
+impl error::Error for ImportError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match &self.kind {
+      ImportErrorKind::Io { error, .. } => Some(error),
+      ImportErrorKind::Unrecognized(_) | ImportErrorKind::MissingCodec(_) => None,
+    }
+  }
+}
+
+impl From<&ImportError> for Diagnostic {
+  fn from(error: &ImportError) -> Self {
+    let code = match error.kind {
+      ImportErrorKind::Io { .. } => "io",
+      ImportErrorKind::Unrecognized(_) => "unrecognized",
+      ImportErrorKind::MissingCodec(_) => "missing-codec",
+    };
+    Diagnostic::error(code, error.kind.to_string()).at(error.path.clone(), None)
+  }
+}
+
+/// Result of [import_actionscript]
+#[derive(Debug, Default)]
+pub struct Import {
+  pub files: Vec<GeneratedFile>,
+  /// Model sources that were imported
+  pub models: Vec<PathBuf>,
+  pub errors: Vec<ImportError>,
+}
+
+/// Generates definitions for all models under `input_root` and the types they use. Returned
+/// paths are relative to the client sources root.
+///
+/// Only the `.as` files found by `discovery` are read, and model classes are only looked for in
+/// the ones that also match `models`, see [MODEL_FILES].
+pub fn import_actionscript(input_root: &Path, discovery: &Discovery, models: &[String]) -> Result<Import, DiscoveryError> {
+  let sources = discovery.walk(input_root)?;
+  let model_globs = Globs::new(input_root, models)?;
+  let model_sources = sources.iter().filter(|it| model_globs.is_match(&input_root.join(it), false)).cloned().collect();
+
+  // Builtin types never get a definition file
+  let mut existing_types = Kotlin.builtin_fqn().into_keys().collect::<HashSet<_>>();
+  existing_types.insert("Object".to_owned()); // synthetic
+
+  let mut importer = Importer {
+    input_root,
+    sources,
+    model_sources,
+    existing_types,
+    model_types: HashMap::new(),
+    codecs: HashMap::new(),
+    import: Import::default(),
+  };
+
+  importer.generate_model_index();
+  for (constructor_name, model_name) in &importer.model_types {
+    debug!("{} -> {}", constructor_name, model_name);
+  }
+
+  importer.generate_codec_index();
+  importer.generate_protolang_model();
+  Ok(importer.import)
+}
+
+#[derive(Debug, Clone)]
+struct ParsedField {
+  pub name: String,
+  pub codec: String,
+  pub kind: String,
+}
+
+#[derive(Debug, Clone)]
+struct ParsedVariant {
+  pub name: String,
+  pub value: i64,
+}
+
+#[derive(Debug, Clone)]
+enum ParsedCodec {
+  Type(Vec<ParsedField>),
+  Enum(Vec<ParsedVariant>),
+}
+
+#[derive(Debug)]
+struct ParsedModel {
+  pub name: String,
+  pub id: i64,
+  /// Relative to the input root
+  pub model_base_path: PathBuf,
+  /// Type of the constructor codec
+  pub constructor: Option<String>,
+  pub client_methods: Vec<ParsedMethod>,
+  pub server_methods: Vec<ParsedMethod>,
+}
+
+#[derive(Debug)]
+struct ParsedMethod {
+  pub name: String,
+  pub id: i64,
+  pub params: Vec<ParsedMethodParam>,
+}
+
+#[derive(Debug)]
+struct ParsedMethodParam {
+  pub name: String,
+  pub codec: String,
+  pub kind: String,
+}
+
+struct Importer<'a> {
+  input_root: &'a Path,
+  /// Relative to `input_root`, sorted
+  sources: Vec<PathBuf>,
+  /// Subset of `sources` that may declare models
+  model_sources: Vec<PathBuf>,
+  /// Types that already have a definition, either builtin or generated
+  existing_types: HashSet<String>,
+  /// Constructor codec -> model name
+  model_types: HashMap<String, String>,
+  /// Type or enum name -> relative path and contents of its codec
+  codecs: HashMap<String, (PathBuf, Result<ParsedCodec, ImportErrorKind>)>,
+  import: Import,
+}
+
+lazy_static! {
+  static ref MODEL_CLASS: Regex = Regex::new(r"class (\w+) extends (\w+) implements (\w+)").unwrap();
+  static ref CONSTRUCTOR_REGEX: Regex = Regex::new(r"registerModelConstructorCodec.+\((.+?),\s*false\)\)\);").unwrap();
+
+  static ref MODEL_ID_REGEX: Regex = Regex::new(r"this.modelId = Long.getLong\((?<high>-?(0x)?[0-9a-f]+),(?<low>-?(0x)?[0-9a-f]+)\)").unwrap();
+  static ref MODEL_CONSTRUCTOR_REGEX: Regex = Regex::new(r"registerModelConstructorCodec\(this.modelId,this._protocol.getCodec\((?<codec>.+)\)\)").unwrap();
+  static ref MODEL_METHOD_REGEX: Regex = Regex::new(r"this._(?<method>[A-Za-z0-9_]+)Id = Long.getLong\((?<high>-?(0x)?[0-9a-f]+),(?<low>-?(0x)?[0-9a-f]+)\)").unwrap();
+  static ref MODEL_CLIENT_METHOD_PARAM_REGEX: Regex = Regex::new(r"this._(?<method>[A-Za-z0-9_]+)_(?<param>[A-Za-z0-9_]+)Codec = this._protocol.getCodec\((?<codec>.+)\)").unwrap();
+  static ref MODEL_SERVER_METHOD_PARAM_REGEX: Regex = Regex::new(r"this._(?<method>[A-Za-z0-9_]+)_(?<param>[A-Za-z0-9_]+)Codec = this.protocol.getCodec\((?<codec>.+)\)").unwrap();
+
+  static ref FIELD_REGEX: Regex = Regex::new(r"this.codec_(?<field>[A-Za-z0-9_]+) = param1.getCodec\((?<codec>.+)\)").unwrap();
+  static ref VARIANT_REGEX: Regex = Regex::new(r"case (?<value>\d+):\s*.+\.(?<variant>\w+);").unwrap();
+}
+
+lazy_static! {
+  static ref TYPES_IN_GENERIC_REGEX: Regex = Regex::new(r"\.?<([\w\s,.?]+)>").unwrap();
+
+  static ref TYPE_REGEX: Regex = Regex::new(r"new (?:Type|Enum)CodecInfo\((.+?),\s*(false|true)\)").unwrap();
+  static ref COLLECTION_REGEX: Regex = Regex::new(r"new CollectionCodecInfo\((.+?),\s*(false|true)(?:,\s*\d+)?\)").unwrap();
+  static ref MAP_REGEX: Regex = Regex::new(r"new MapCodecInfo\((.+?),\s*(.+?),\s*(false|true)\)").unwrap();
+
+  static ref COLLECTION_REVERSE_REGEX: Regex = Regex::new(r"List<(.+?)(\?)?>").unwrap();
+
+  static ref REGEX_1: Regex = Regex::new(r"\bBoolean\b").unwrap();
+  static ref REGEX_2: Regex = Regex::new(r"\bByte\b").unwrap();
+  static ref REGEX_3: Regex = Regex::new(r"\bShort\b").unwrap();
+  static ref REGEX_4: Regex = Regex::new(r"\b[Ii]nt\b").unwrap();
+  static ref REGEX_5: Regex = Regex::new(r"\bLong\b").unwrap();
+  static ref REGEX_6: Regex = Regex::new(r"\bFloat\b").unwrap();
+  static ref REGEX_7: Regex = Regex::new(r"\b(Number|Double)\b").unwrap();
+  static ref REGEX_8: Regex = Regex::new(r"\bTanks3DSResource\b").unwrap();
+  static ref REGEX_9: Regex = Regex::new(r"\bDate\b").unwrap();
+}
+
+impl Importer<'_> {
+  /// Model sources that fail here are reported once and skipped by [Importer::generate_protolang_model].
+  /// Sources are scanned in parallel, but registered in source order.
+  fn generate_model_index(&mut self) {
+    let input_root = self.input_root;
+    info!("generating model index...");
+
+    let indexed = self.model_sources.par_iter()
+      .map(|relative_path| {
+        let path = input_root.join(relative_path);
+        let result = index_model(&path);
+        (path, result)
+      })
+      .collect::<Vec<_>>();
+    for (path, result) in indexed {
+      match result {
+        Ok(Some((constructor_name, model_name))) => {
+          // Do not generate definition files
+          // self.existing_types.insert(constructor_name.to_owned());
+          self.existing_types.insert(format!("{}.Constructor", model_name));
+          info!("registered {}", format!("{}.Constructor", model_name));
+          self.model_types.insert(constructor_name, model_name);
+        }
+        Ok(None) => {}
+        Err(kind) => {
+          error!("{:?}: {}", path, kind);
+          self.import.errors.push(ImportError { path, kind });
+        }
+      }
+    }
+
+    info!("model index generated");
+  }
+
+  /// Model sources are read and parsed in parallel, the models and the types they use are then
+  /// generated in source order.
+  fn generate_protolang_model(&mut self) {
+    let input_root = self.input_root;
+    let parsed = self.model_sources.par_iter()
+      .map(|relative_path| input_root.join(relative_path))
+      .filter(|path| !self.import.errors.iter().any(|it| &it.path == path))
+      .map(|path| {
+        let result = self.parse_model(&path);
+        (path, result)
+      })
+      .collect::<Vec<_>>();
+
+    for (path, result) in parsed {
+      let result = match result {
+        Ok(Some(model)) => self.import_model(model).map(|_| true),
+        Ok(None) => Ok(false),
+        Err(kind) => Err(kind),
+      };
+      match result {
+        Ok(true) => self.import.models.push(path),
+        Ok(false) => {}
+        Err(kind) => {
+          error!("{:?}: {}", path, kind);
+          self.import.errors.push(ImportError { path, kind });
+        }
+      }
+    }
+  }
+
+  /// `None` if the source is not a model class
+  fn parse_model(&self, path: &Path) -> Result<Option<ParsedModel>, ImportErrorKind> {
+    let input_root = self.input_root;
+
+    // debug!("Parsing {:?}...", path);
+    let content = read_source(path)?;
+    if !content.contains("[ModelInfo]") {
+      return Ok(None);
+    }
+
+    // debug!("{}", content);
+
+    let captures = MODEL_CLASS.captures(&content).ok_or_else(|| unrecognized("no model class declaration"))?;
+    // let model_name = &captures[1];
+    let model_base_name = &captures[2];
+    let model_interface_name = &captures[3];
+    let model_server_name = model_base_name.replace("ModelBase", "ModelServer");
+    debug!("{:?} {:?} {:?} {:?}", &captures[1], model_base_name, model_interface_name, model_server_name);
+
+    let (model_base_path, model_base_contents) = read_model_base(path, &content, model_base_name)?;
+    let relative_model_base_path = model_base_path.strip_prefix(input_root).map_err(|_| unrecognized(format!("model base {:?} is outside of the input", model_base_path)))?;
+    // debug!("{}", model_base_contents);
+
+    let captures = MODEL_ID_REGEX.captures(&model_base_contents).ok_or_else(|| unrecognized("no model id in the model base"))?;
+    let model_id = parse_id(&captures)?;
+    debug!("model id: {}", model_id);
+
+    let model_constructor = if let Some(captures) = MODEL_CONSTRUCTOR_REGEX.captures(&model_base_contents) {
+      let model_constructor = &captures["codec"];
+      debug!("model constructor: {}", model_constructor);
+      Some(model_constructor.to_owned())
+    } else {
+      None
+    };
+
+    let mut client_methods = Vec::new();
+    let captures = MODEL_METHOD_REGEX.captures_iter(&model_base_contents);
+    for capture in captures {
+      let method_name = &capture["method"];
+      let model_id = parse_id(&capture)?;
+      // debug!("client method: {} = {}", method_name, model_id);
+
+      client_methods.push(ParsedMethod {
+        name: method_name.to_owned(),
+        id: model_id,
+        params: Vec::new(),
+      });
+    }
+
+    let captures = MODEL_CLIENT_METHOD_PARAM_REGEX.captures_iter(&model_base_contents);
+    for capture in captures {
+      let method_name = &capture["method"];
+      let param = &capture["param"];
+      let codec = &capture["codec"];
+      // debug!("client method: {} = {} by {}", method_name, param, codec);
+      //
+      // debug!("searching for {method_name}");
+      // debug!("{:?}", client_methods);
+      let method = client_methods.iter_mut().find(|it| it.name == method_name).ok_or_else(|| unrecognized(format!("codec for parameter {} of unknown client method {}", param, method_name)))?;
+      method.params.push(ParsedMethodParam {
+        name: param.to_owned(),
+        codec: codec.to_owned(),
+        kind: self.codec_to_type(codec, false),
+      });
+    }
+
+    let model_server_contents = read_source(&model_base_path.with_file_name(model_server_name + ".as"))?;
+    // debug!("{}", model_server_contents);
+
+    let mut server_methods = Vec::new();
+    let captures = MODEL_METHOD_REGEX.captures_iter(&model_server_contents);
+    for capture in captures {
+      let method_name = &capture["method"];
+      let model_id = parse_id(&capture)?;
+      // debug!("server method: {} = {}", method_name, model_id);
+
+      server_methods.push(ParsedMethod {
+        name: method_name.to_owned(),
+        id: model_id,
+        params: Vec::new(),
+      });
+    }
+
+    let captures = MODEL_SERVER_METHOD_PARAM_REGEX.captures_iter(&model_server_contents);
+    for capture in captures {
+      let method_name = &capture["method"];
+      let param = &capture["param"];
+      let codec = &capture["codec"];
+      // debug!("server method: {} = {} by {}", method_name, param, codec);
+
+      let method = server_methods.iter_mut().find(|it| it.name == method_name).ok_or_else(|| unrecognized(format!("codec for parameter {} of unknown server method {}", param, method_name)))?;
+      method.params.push(ParsedMethodParam {
+        name: param.to_owned(),
+        codec: codec.to_owned(),
+        kind: self.codec_to_type(codec, false),
+      });
+    }
+
+    debug!("CI {:?}", client_methods);
+    debug!("SI {:?}", server_methods);
+
+    Ok(Some(ParsedModel {
+      name: model_base_name.replace("ModelBase", "Model"),
+      id: model_id,
+      model_base_path: relative_model_base_path.to_path_buf(),
+      constructor: model_constructor.map(|codec| self.codec_to_type(&codec, true)),
+      client_methods,
+      server_methods,
+    }))
+  }
+
+  fn import_model(&mut self, parsed: ParsedModel) -> Result<(), ImportErrorKind> {
+    let ParsedModel { name: model_name, id: model_id, model_base_path, constructor, client_methods, server_methods } = parsed;
+    let relative_model_base_path = model_base_path.as_path();
+    let constructor = match constructor {
+      Some(kind) => {
+        let (_, type_def) = self.generate_protolang_type(&kind)?.ok_or_else(|| unrecognized(format!("model constructor {} is an enum", kind)))?;
+        Some(hl::ModelConstructor {
+          fields: type_def.fields,
+          meta: type_def.meta,
+          comments: type_def.comments,
+        })
+      }
+      None => None,
+    };
+    let model = hl::Model {
+      name: model_name.clone(),
+      id: model_id,
+      constructor,
+      client_methods: client_methods.iter().map(|it| hl::ClientMethod {
+        name: it.name.to_owned(),
+        id: it.id,
+        params: it.params.iter().map(|it| hl::Param {
+          name: it.name.to_owned(),
+          kind: it.kind.to_owned(),
+          codec: it.codec.to_owned(),
+        }).collect_vec(),
+        comments: vec![],
+      }).collect_vec(),
+      server_methods: server_methods.iter().map(|it| hl::ServerMethod {
+        name: it.name.to_owned(),
+        id: it.id,
+        params: it.params.iter().map(|it| hl::Param {
+          name: it.name.to_owned(),
+          kind: it.kind.to_owned(),
+          codec: it.codec.to_owned(),
+        }).collect_vec(),
+        comments: vec![],
+      }).collect_vec(),
+      includes: vec![],
+      meta: vec![
+        Meta {
+          key: "client_package".to_owned(),
+          // value: format!("{}:{}", project, convert_path_to_definition(&relative_model_base_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, "."))
+          value: convert_path_to_definition(relative_model_base_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".")
+        },
+        Meta { key: "client_name".to_owned(), value: model_name.to_owned() },
+      ],
+      comments: vec![
+        format!("TODO: This is an automatically generated model definition for \"{}\"", model_name)
+      ],
+    };
+    let definition = generate_protolang_code(&model);
+    debug!("{}", definition);
+
+    if let Some(constructor) = &model.constructor {
+      for field in &constructor.fields {
+        let types = get_types_from_generic(&field.kind);
+        for name in &types {
+          if !self.existing_types.contains(name) {
+            debug!("generating constructor type for {}", name);
+
+            let (relative_model_base_path, definition) = self.generate_type_code_for(name)?;
+            debug!("{}", definition);
+
+            let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
+            let output_path = convert_path_to_definition(&relative_model_base_path);
+            info!("generate type into {:?}", output_path);
+            self.import.files.push(GeneratedFile { path: output_path, contents: definition });
+          }
+        }
+      }
+    }
+
+    for method in &model.client_methods {
+      for param in &method.params {
+        let types = get_types_from_generic(&param.kind);
+        // debug!("{:?}", types);
+        for name in &types {
+          if !self.existing_types.contains(name) {
+            debug!("generating type for {}", name);
+
+            // TODO
+            let (relative_model_base_path, definition) = self.generate_type_code_for(name)?;
+            debug!("{}", definition);
+
+            let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
+            let output_path = convert_path_to_definition(&relative_model_base_path);
+            info!("generate type into {:?}", output_path);
+            self.import.files.push(GeneratedFile { path: output_path, contents: definition });
+          }
+        }
+      }
+    }
+
+    for method in &model.server_methods {
+      for param in &method.params {
+        let types = get_types_from_generic(&param.kind);
+        // debug!("{:?}", types);
+        for name in &types {
+          if !self.existing_types.contains(name) {
+            debug!("generating type for {}", name);
+
+            // TODO
+            let (relative_model_base_path, definition) = self.generate_type_code_for(name)?;
+            debug!("{}", definition);
+
+            let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
+            let output_path = convert_path_to_definition(&relative_model_base_path);
+            info!("generate type into {:?}", output_path);
+            self.import.files.push(GeneratedFile { path: output_path, contents: definition });
+          }
+        }
+      }
+    }
+
+    let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replace("ModelBase.as", "Model.proto"));
+    let output_path = convert_path_to_definition(&relative_model_base_path);
+    info!("generate model into {:?}", output_path);
+    self.import.files.push(GeneratedFile { path: output_path, contents: definition });
+    Ok(())
+  }
+
+  fn generate_type_code_for(&mut self, name: &str) -> Result<(PathBuf, String), ImportErrorKind> {
+    match self.generate_protolang_type(name)? {
+      Some((relative_path, type_def)) => {
+        let definition = generate_protolang_code_type(&type_def);
+        Ok((relative_path, definition))
+      }
+
+      None => match self.generate_protolang_enum(name)? {
+        Some((relative_path, enum_def)) => {
+          let definition = generate_protolang_code_enum(&enum_def);
+          debug!("{}", definition);
+
+          Ok((relative_path, definition))
+        }
+
+        None => Err(unrecognized(format!("cannot generate type/enum definition for {}", name)))
+      }
+    }
+  }
+
+  /// `None` if the codec is an enum codec
+  fn generate_protolang_type(&mut self, name: &str) -> Result<Option<(PathBuf, hl::Type)>, ImportErrorKind> {
+    let (relative_path, fields) = match self.codec(name)? {
+      (relative_path, ParsedCodec::Type(fields)) => (relative_path, fields),
+      (_, ParsedCodec::Enum(_)) => return Ok(None),
+    };
+
+    debug!("{:?}", fields);
+
+    let type_def = hl::Type {
+      name: name.to_owned(),
+      fields: fields.iter().enumerate().map(|(index, it)| hl::Field {
+        name: it.name.to_owned(),
+        kind: it.kind.to_owned(),
+        codec: it.codec.to_owned(),
+        position: index + 1,
+        comments: vec![],
+      }).collect_vec(),
+      meta: vec![
+        Meta { key: "client_package".to_owned(), value: convert_path_to_definition(&relative_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".") },
+        Meta { key: "client_name".to_owned(), value: name.to_owned() },
+      ],
+      comments: vec![
+        format!("TODO: This is an automatically generated type definition for \"{}\"", name)
+      ],
+    };
+
+    self.existing_types.insert(name.to_owned());
+
+    for field in &type_def.fields {
+      let types = get_types_from_generic(&field.kind);
+      for name in &types {
+        if !self.existing_types.contains(name) {
+          debug!("generating recursive type for {}", name);
+
+          let (relative_path, definition) = self.generate_type_code_for(name)?;
+          debug!("{}", definition);
+
+          let relative_path = relative_path.with_file_name(relative_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
+          let output_path = convert_path_to_definition(&relative_path);
+          info!("generate type into {:?}", output_path);
+          self.import.files.push(GeneratedFile { path: output_path, contents: definition });
+        }
+      }
+    }
+
+    Ok(Some((relative_path, type_def)))
+  }
+
+  /// `None` if the codec is not an enum codec
+  fn generate_protolang_enum(&mut self, name: &str) -> Result<Option<(PathBuf, hl::Enum)>, ImportErrorKind> {
+    let (relative_path, variants) = match self.codec(name)? {
+      (relative_path, ParsedCodec::Enum(variants)) => (relative_path, variants),
+      (_, ParsedCodec::Type(_)) => return Ok(None),
+    };
+
+    debug!("{:?}", variants);
+
+    let enum_def = hl::Enum {
+      name: name.to_owned(),
+      repr: "i32".to_owned(),
+      variants: variants.iter().map(|it| hl::Variant {
+        name: it.name.to_owned(),
+        value: it.value,
+        comments: vec![],
+      }).collect_vec(),
+      meta: vec![
+        Meta { key: "client_package".to_owned(), value: convert_path_to_definition(&relative_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".") },
+        Meta { key: "client_name".to_owned(), value: name.to_owned() }
+      ],
+      comments: vec![
+        format!("TODO: This is an automatically generated enum definition for \"{}\"", name)
+      ],
+    };
+
+    self.existing_types.insert(name.to_owned());
+
+    Ok(Some((relative_path, enum_def)))
+  }
+
+  /// Parses all `Codec<name>.as` sources in parallel, only the first source of each name is used.
+  /// Needs the model index to resolve model constructor codecs.
+  fn generate_codec_index(&mut self) {
+    let parsed = self.sources.par_iter()
+      .filter_map(|relative_path| {
+        let name = relative_path.file_name()?.to_str()?.strip_prefix("Codec")?.strip_suffix(".as")?;
+        let result = self.parse_codec(relative_path);
+        Some((name.to_owned(), relative_path.clone(), result))
+      })
+      .collect::<Vec<_>>();
+    for (name, relative_path, result) in parsed {
+      self.codecs.entry(name).or_insert((relative_path, result));
+    }
+  }
+
+  /// Relative path and contents of the codec for the type or enum `name`
+  fn codec(&self, name: &str) -> Result<(PathBuf, ParsedCodec), ImportErrorKind> {
+    match self.codecs.get(name) {
+      Some((relative_path, Ok(codec))) => Ok((relative_path.clone(), codec.clone())),
+      // Errors are only reported for codecs that are used, parse again to take ownership of it
+      Some((relative_path, Err(_))) => Ok((relative_path.clone(), self.parse_codec(relative_path)?)),
+      None => Err(ImportErrorKind::MissingCodec(name.to_owned())),
+    }
+  }
+
+  fn parse_codec(&self, relative_path: &Path) -> Result<ParsedCodec, ImportErrorKind> {
+    debug!("Parsing {:?}...", relative_path);
+    let content = read_source(&self.input_root.join(relative_path))?;
+    // debug!("{}", content);
+
+    if content.contains(" switch(") {
+      let mut variants = Vec::new();
+      let captures = VARIANT_REGEX.captures_iter(&content);
+      for capture in captures {
+        let variant = &capture["variant"];
+        let value = capture["value"].parse::<i64>().map_err(|_| unrecognized(format!("enum value {} is out of range", &capture["value"])))?;
+        // debug!("variant: {} = {}", variant, value);
+
+        variants.push(ParsedVariant {
+          name: variant.to_owned(),
+          value,
+        });
+      }
+      return Ok(ParsedCodec::Enum(variants));
+    }
+
+    let mut fields = Vec::new();
+    let captures = FIELD_REGEX.captures_iter(&content);
+    for capture in captures {
+      let field_name = &capture["field"];
+      let codec = &capture["codec"];
+      // debug!("field: {} by {}", field_name, codec =);
+
+      fields.push(ParsedField {
+        name: field_name.to_owned(),
+        codec: codec.to_owned(),
+        kind: self.codec_to_type(codec, false),
+      });
+    }
+    Ok(ParsedCodec::Type(fields))
+  }
+
+  fn codec_to_type(&self, codec: &str, is_constructor: bool) -> String {
+    let codec = TYPE_REGEX.replace_all(codec, |captures: &regex::Captures| {
+      let inner = captures.get(1).unwrap().as_str();
+      let optional = captures.get(2).unwrap().as_str();
+      format!("{}{}", inner, if optional == "true" { "?" } else { "" })
+    });
+    let codec = COLLECTION_REGEX.replace_all(&codec, |captures: &regex::Captures| {
+      let inner = captures.get(1).unwrap().as_str();
+      let optional = captures.get(2).unwrap().as_str();
+      format!("List<{}>{}", inner, if optional == "true" { "?" } else { "" })
+    });
+    let codec = MAP_REGEX.replace_all(&codec, |captures: &regex::Captures| {
+      let key = captures.get(1).unwrap().as_str();
+      let value = captures.get(2).unwrap().as_str();
+      let optional = captures.get(3).unwrap().as_str();
+      format!("Map<{}, {}>{}", key, value, if optional == "true" { "?" } else { "" })
+    });
+    let codec = REGEX_1.replace_all(&codec, "bool");
+    let codec = REGEX_2.replace_all(&codec, "i8");
+    let codec = REGEX_3.replace_all(&codec, "i16");
+    let codec = REGEX_4.replace_all(&codec, "i32");
+    let codec = REGEX_5.replace_all(&codec, "i64");
+    let codec = REGEX_6.replace_all(&codec, "f32");
+    let codec = REGEX_7.replace_all(&codec, "f64");
+    let codec = REGEX_8.replace_all(&codec, "Object3DResource");
+    let codec = REGEX_9.replace_all(&codec, "Instant");
+
+    if !is_constructor {
+      // Convert CC to Model.Constructor references
+      // TODO: This does not support wrapped types, only TypeCodecInfo
+      let model_name = self.model_types.get(&codec.to_string()).cloned();
+      if let Some(model_name) = &model_name {
+        return format!("{}.Constructor", model_name);
+      }
+    }
+
+    codec.to_string()
+  }
+}
+
+/// Constructor codec and model name of a model source, `None` if it is not a model class or the
+/// model has no constructor
+fn index_model(path: &Path) -> Result<Option<(String, String)>, ImportErrorKind> {
+  // debug!("Parsing {:?}...", path);
+  let content = read_source(path)?;
+  if !content.contains("[ModelInfo]") {
+    return Ok(None);
+  }
+
+  let captures = MODEL_CLASS.captures(&content).ok_or_else(|| unrecognized("no model class declaration"))?;
+  let model_name = &captures[1];
+  let model_base_name = &captures[2];
+
+  if model_name != model_base_name.replace("ModelBase", "Model") {
+    // debug!("{:?} {:?}", model_name, model_base_name.replace("ModelBase", "Model"));
+  }
+
+  let (_, model_base_contents) = read_model_base(path, &content, model_base_name)?;
+  // debug!("{}", model_base_contents);
+
+  if !model_base_contents.contains("registerModelConstructorCodec") {
+    return Ok(None);
+  }
+
+  // debug!("{}", content);
+
+  let captures = CONSTRUCTOR_REGEX.captures(&model_base_contents).ok_or_else(|| unrecognized("no model constructor codec in the model base"))?;
+  let constructor_name = &captures[1];
+
+  let model_name = model_base_name.replace("ModelBase", "Model");
+
+  Ok(Some((constructor_name.to_owned(), model_name)))
+}
+
+fn unrecognized(message: impl Into<String>) -> ImportErrorKind {
+  ImportErrorKind::Unrecognized(message.into())
+}
+
+fn read_source(path: &Path) -> Result<String, ImportErrorKind> {
+  fs::read_to_string(path).map_err(|error| ImportErrorKind::Io { path: path.to_path_buf(), error })
+}
+
+/// Path and contents of the `ModelBase` class imported by a model class
+fn read_model_base(path: &Path, content: &str, model_base_name: &str) -> Result<(PathBuf, String), ImportErrorKind> {
+  let class_import = Regex::new(&format!(r"import ([\w.]+\.{})", escape(model_base_name))).map_err(|error| unrecognized(error.to_string()))?;
+  let captures = class_import.captures(content).ok_or_else(|| unrecognized(format!("no import of {}", model_base_name)))?;
+  let model_base_import_fqdn = &captures[1];
+  let model_base_import_path = model_base_import_fqdn.replace('.', "/") + ".as";
+  let model_base_import_path = Path::new(&model_base_import_path);
+  // debug!("{:?} {:?}", model_base_import_fqdn, model_base_import_path);
+
+  let mut sources_root = path;
+  while let Some(parent) = sources_root.parent() {
+    sources_root = parent;
+    if parent.file_name().is_some_and(|it| it == "src") {
+      break;
+    }
+  }
+  // debug!("{:?}", sources_root);
+
+  let model_base_path = sources_root.join(model_base_import_path);
+  // debug!("{:?}", model_base_path);
+  match fs::read_to_string(&model_base_path) {
+    Ok(contents) => Ok((model_base_path, contents)),
+    Err(_) => {
+      // debug!("failed to read model base file (trying entrance) {:?}: {:?}", model_base_path, error);
+
+      // Try "entrance"
+      let model_base_path = model_base_path.components().map(|it| if it.as_os_str().to_string_lossy() == "game" { Component::Normal(OsStr::new("entrance")) } else { it }).collect::<PathBuf>();
+      // debug!("{:?}", model_base_path);
+      let contents = read_source(&model_base_path)?;
+      Ok((model_base_path, contents))
+    }
+  }
+}
+
+/// Model or method id from the `high` and `low` captures
+fn parse_id(captures: &regex::Captures) -> Result<i64, ImportErrorKind> {
+  let high = parse_id_from_dec_or_hex(&captures["high"]);
+  let low = parse_id_from_dec_or_hex(&captures["low"]);
+  match (high, low) {
+    (Some(high), Some(low)) => Ok(convert_to_id(high, low)),
+    _ => Err(unrecognized(format!("invalid id {}, {}", &captures["high"], &captures["low"]))),
+  }
+}
+
+fn parse_id_from_dec_or_hex(value: &str) -> Option<i32> {
+  match value.strip_prefix("0x") {
+    Some(hex) => i32::from_str_radix(hex, 16).ok(),
+    None => value.parse::<i32>().ok(),
+  }
+}
+
+fn convert_path_to_definition(path: &Path) -> PathBuf {
+  warn!("convert path: {:?}", path);
+  let relative_to_source_root = path.components().skip(2).collect::<PathBuf>();
+  if relative_to_source_root.components().next().is_some_and(|it| it.as_os_str() == "_codec") {
+    relative_to_source_root.components().skip(1).collect::<PathBuf>()
+  } else {
+    relative_to_source_root
+  }
+}
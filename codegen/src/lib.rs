@@ -0,0 +1,538 @@
+//! Code generation from a loaded [Workspace]. Nothing is written to disk, the caller decides
+//! where the returned files go.
+//!
+//! [generate] returns a [Generation] rather than a plain `Vec<GeneratedFile>`: besides the
+//! rendered [Generation::files], callers need the per-file errors, the skipped and cached files
+//! and which source each output came from to keep the manifest up to date.
+
+pub mod cache;
+pub mod config;
+pub mod import;
+pub mod manifest;
+pub mod plugin;
+pub mod target;
+
+mod context;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Component, Path, PathBuf};
+
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use protolang_parser::diagnostic::Diagnostic;
+use protolang_parser::hl::Definition;
+use protolang_parser::module::Module;
+use protolang_parser::workspace::{SourceFile, Workspace};
+use rayon::prelude::*;
+use regex::Regex;
+use tracing::{debug, info};
+
+pub use crate::cache::Cache;
+pub use crate::context::Context;
+pub use crate::target::{Registry, Target};
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+  /// Package prepended to the packages derived from source paths
+  pub root_package: Option<String>,
+  /// Modules to generate sources for, all modules if empty
+  pub modules: Vec<String>,
+  /// Schema files that are not generated, as returned by [Workspace::path], e.g. the ones that
+  /// fail [Workspace::validate]
+  pub exclude: BTreeSet<PathBuf>,
+  /// Replaces [Target::builtin_fqn]
+  pub builtins: Option<HashMap<String, String>>,
+  /// Imports added to every generated file instead of the target defaults. Only used by targets
+  /// with a fixed import list, currently Kotlin.
+  pub imports: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFile {
+  /// Relative to the output root
+  pub path: PathBuf,
+  pub contents: String,
+}
+
+/// Definition that a target cannot generate code for
+#[derive(Debug)]
+pub enum GenerateError {
+  /// `meta <key> = "...";` that the target needs, e.g. `client_package` for ActionScript
+  MissingMeta {
+    definition: String,
+    key: String,
+  },
+  /// Template whose models or values do not match the model definitions
+  InvalidTemplate {
+    template: String,
+    message: String,
+  },
+  /// Template file of a [target::template::TemplateTarget] that failed to render
+  Render {
+    name: String,
+    error: minijinja::Error,
+  },
+  /// Output path of a [target::template::TemplateTarget] that is empty, absolute or escapes the
+  /// output directory
+  InvalidPath {
+    template: String,
+    path: PathBuf,
+  },
+  /// Output path that an earlier schema file or module directory already produced
+  DuplicateOutput {
+    path: PathBuf,
+    first: PathBuf,
+  },
+}
+
+impl Display for GenerateError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      GenerateError::MissingMeta { definition, key } => write!(f, "{} has no meta {}", definition, key),
+      GenerateError::InvalidTemplate { template, message } => write!(f, "template {} {}", template, message),
+      GenerateError::Render { name, error } => write!(f, "failed to render {}: {:#}", name, error),
+      GenerateError::InvalidPath { template, path } => write!(f, "template {} rendered invalid output path {:?}", template, path),
+      GenerateError::DuplicateOutput { path, first } => write!(f, "output {:?} is already generated from {:?}", path, first),
+    }
+  }
+}
+
+impl error::Error for GenerateError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      GenerateError::Render { error, .. } => Some(error),
+      GenerateError::MissingMeta { .. } | GenerateError::InvalidTemplate { .. } | GenerateError::InvalidPath { .. } | GenerateError::DuplicateOutput { .. } => None,
+    }
+  }
+}
+
+/// [GenerateError] of a schema file, or of a module directory for [Target::generate_module]
+#[derive(Debug)]
+pub struct FileError {
+  pub path: PathBuf,
+  pub error: GenerateError,
+}
+
+impl Display for FileError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}: {}", self.path, self.error)
+  }
+}
+
+impl error::Error for FileError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    Some(&self.error)
+  }
+}
+
+impl From<&FileError> for Diagnostic {
+  fn from(error: &FileError) -> Self {
+    let code = match error.error {
+      GenerateError::MissingMeta { .. } => "missing-meta",
+      GenerateError::InvalidTemplate { .. } => "invalid-template",
+      GenerateError::InvalidPath { .. } => "invalid-path",
+      GenerateError::DuplicateOutput { .. } => "duplicate-output",
+      GenerateError::Render { .. } => "render",
+    };
+    Diagnostic::error(code, error.error.to_string()).at(error.path.clone(), None)
+  }
+}
+
+/// Result of [generate]. Paths of schema files include their root, see [Workspace::path].
+#[derive(Debug, Default)]
+pub struct Generation {
+  pub files: Vec<GeneratedFile>,
+  /// Output path -> schema file or module directory it was generated from, including the
+  /// outputs of cached files, see [manifest]
+  pub outputs: BTreeMap<PathBuf, PathBuf>,
+  /// Schema files that were generated without errors
+  pub generated: Vec<PathBuf>,
+  /// Schema files whose outputs are up to date according to the [Cache] and were not rendered
+  pub cached: Vec<PathBuf>,
+  /// Schema files of modules that are skipped for the target
+  pub skipped: Vec<PathBuf>,
+  /// Directories of the modules whose [Target::generate_module] succeeded
+  pub modules: Vec<PathBuf>,
+  /// Nothing is returned for a failed file, the other files are still generated
+  pub errors: Vec<FileError>,
+}
+
+impl Generation {
+  /// Schema files and module directories whose outputs are complete, i.e. everything but the
+  /// failed files. Previous outputs of these that were not produced again are stale.
+  pub fn processed(&self) -> BTreeSet<&Path> {
+    self.generated.iter().chain(&self.cached).chain(&self.skipped).chain(&self.modules).map(PathBuf::as_path).collect()
+  }
+}
+
+/// Generates sources for every selected workspace file, see [Target] for the order of the hooks.
+/// Files are rendered in parallel on the rayon thread pool, but the result only depends on the
+/// workspace order: when several files produce the same output path, the later ones fail with
+/// [GenerateError::DuplicateOutput].
+pub fn generate(target: &dyn Target, workspace: &Workspace, options: &Options) -> Generation {
+  generate_cached(target, workspace, options, None)
+}
+
+/// Same as [generate], but files whose outputs are up to date according to the cache are not
+/// rendered, see [cache]. [Target::generate_module] still gets every file of the module.
+pub fn generate_incremental(target: &dyn Target, workspace: &Workspace, options: &Options, cache: &mut Cache) -> Generation {
+  cache.retain(target.name(), workspace);
+  generate_cached(target, workspace, options, Some(cache))
+}
+
+fn generate_cached(target: &dyn Target, workspace: &Workspace, options: &Options, mut cache: Option<&mut Cache>) -> Generation {
+  let context = Context::new(target, workspace, options);
+  let selected = selected_files(workspace, options);
+
+  let mut generation = Generation::default();
+  let mut pending = Vec::new();
+  for file in selected {
+    let path = workspace.path(file);
+    // Selected files always have a module
+    let Some(module) = workspace.module(file) else { continue };
+    if module.is_skipped(target.name()) {
+      debug!("Skipping {:?}, module '{}' is skipped for {}", path, module.name, target.name());
+      generation.skipped.push(path);
+      continue;
+    }
+
+    let options = module_options(target, module, options);
+    let hash = cache.as_ref().map(|_| cache::hash_file(target, workspace, file, &options));
+    let cached = match (cache.as_deref(), &hash) {
+      (Some(cache), Some(hash)) => cache.outputs(target.name(), file, hash),
+      _ => None,
+    };
+    pending.push((file, path, module, options, hash, cached));
+  }
+
+  // Files are rendered in parallel, but their outputs are collected in workspace order
+  let rendered = pending.par_iter()
+    .map(|(file, path, _, options, _, cached)| match cached {
+      Some(_) => None,
+      None => {
+        info!("Generating {:?}...", path);
+        Some(generate_file(target, &context, file, options))
+      }
+    })
+    .collect::<Vec<_>>();
+
+  let mut modules: BTreeMap<&str, Vec<&SourceFile>> = BTreeMap::new();
+  for ((file, path, module, _, hash, cached), rendered) in pending.into_iter().zip(rendered) {
+    let files = match rendered {
+      // Not rendered because its outputs are cached
+      None => {
+        debug!("Skipping {:?}, its outputs are up to date", path);
+        for output in cached.into_iter().flatten() {
+          generation.outputs.entry(output).or_insert_with(|| path.clone());
+        }
+        generation.cached.push(path);
+        modules.entry(&module.name).or_default().push(file);
+        continue;
+      }
+      Some(result) => result,
+    };
+
+    let result = files.and_then(|files| {
+      if let (Some(cache), Some(hash)) = (cache.as_deref_mut(), hash) {
+        cache.insert(target.name(), file, hash, &files);
+      }
+      push_outputs(&mut generation, &path, files)
+    });
+    if let Err(error) = result {
+      if let Some(cache) = cache.as_deref_mut() {
+        cache.remove(target.name(), file);
+      }
+      generation.errors.push(FileError { path, error });
+      continue;
+    }
+    generation.generated.push(path);
+    modules.entry(&module.name).or_default().push(file);
+  }
+
+  for (module, module_files) in &modules {
+    let module = &workspace.modules[*module];
+    let options = &module_options(target, module, options);
+    let path = workspace.roots[module.root].path.join(&module.dir);
+    match target.generate_module(&context, &module.name, module_files, options).and_then(|files| push_outputs(&mut generation, &path, files)) {
+      Ok(()) => generation.modules.push(path),
+      Err(error) => generation.errors.push(FileError { path, error }),
+    }
+  }
+  generation
+}
+
+/// Adds the outputs of a schema file or module directory. Nothing is added if any of the paths
+/// was already produced, by an earlier source or by the same one.
+fn push_outputs(generation: &mut Generation, source: &Path, files: Vec<GeneratedFile>) -> Result<(), GenerateError> {
+  let mut paths = BTreeSet::new();
+  for file in &files {
+    let first = generation.outputs.get(&file.path).map(PathBuf::as_path).or_else(|| (!paths.insert(&file.path)).then_some(source));
+    if let Some(first) = first {
+      return Err(GenerateError::DuplicateOutput { path: file.path.clone(), first: first.to_path_buf() });
+    }
+  }
+
+  for file in files {
+    generation.outputs.insert(file.path.clone(), source.to_path_buf());
+    generation.files.push(file);
+  }
+  Ok(())
+}
+
+/// Outputs of every definition of the file and of [Target::generate_file]
+fn generate_file(target: &dyn Target, context: &Context, file: &SourceFile, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+  let mut files = Vec::new();
+  for definition in &file.definitions {
+    debug!("{:?}", definition);
+    files.extend(match definition {
+      Definition::Model(model) => target.generate_model(context, file, model, options)?,
+      Definition::Type(type_def) => target.generate_type(context, file, type_def, options)?,
+      Definition::Enum(enum_def) => target.generate_enum(context, file, enum_def, options)?,
+      Definition::Template(template) => target.generate_template(context, file, template, options)?,
+      Definition::Meta(_) | Definition::Interface(_) => continue
+    });
+  }
+  files.extend(target.generate_file(context, file, options)?);
+  Ok(files)
+}
+
+/// Options with the root package replaced by the module package, if any
+fn module_options(target: &dyn Target, module: &Module, options: &Options) -> Options {
+  let mut options = options.clone();
+  if let Some(package) = module.package(target.name()) {
+    options.root_package = Some(package.to_owned());
+  }
+  options
+}
+
+/// Workspace files belonging to [Options::modules], except for read-only roots and
+/// [Options::exclude]. Files outside of any module are never generated, [Workspace::validate]
+/// reports them.
+fn selected_files<'a>(workspace: &'a Workspace, options: &Options) -> Vec<&'a SourceFile> {
+  let mut files = Vec::new();
+  for file in &workspace.files {
+    if workspace.roots[file.root].read_only || options.exclude.contains(&workspace.path(file)) {
+      continue;
+    }
+
+    debug!("Module: {:?}", file.module);
+    let Some(file_module) = &file.module else { continue };
+    if !options.modules.is_empty() && !options.modules.contains(file_module) {
+      continue;
+    }
+    files.push(file);
+  }
+  files
+}
+
+/// Whether a generated path stays inside the output directory: not empty, not absolute and
+/// without `..`
+pub(crate) fn is_output_path(path: &Path) -> bool {
+  !path.as_os_str().is_empty() && path.components().all(|it| matches!(it, Component::Normal(_) | Component::CurDir))
+}
+
+pub fn wrap_to_u64(x: i64) -> u64 {
+  (x as u64).wrapping_add(u64::MAX / 2 + 1)
+}
+
+pub(crate) fn convert_to_id(high: i32, low: i32) -> i64 {
+  ((u32::from_ne_bytes(i32::to_ne_bytes(high)) as i64) << 32) | (u32::from_ne_bytes(i32::to_ne_bytes(low)) as i64)
+}
+
+pub(crate) fn convert_from_id(id: i64) -> (i32, i32) {
+  ((id >> 32) as i32, (id & 0xffffffff) as i32)
+}
+
+lazy_static! {
+  static ref TYPES_IN_GENERIC_REGEX: Regex = Regex::new(r"\.?<([\w\s,.?]+)>").unwrap();
+}
+
+pub(crate) fn get_types_from_generic(value: &str) -> Vec<String> {
+  let captures = match TYPES_IN_GENERIC_REGEX.captures(value) {
+    Some(value) => value,
+    None => return vec![value.trim().trim_end_matches('?').to_owned()]
+  };
+
+  let inner = captures.get(1).unwrap().as_str();
+  inner.split(',').map(|it| it.trim().trim_end_matches('?').to_owned()).collect_vec()
+}
+
+/// Writes `(path, content)` pairs under `dir`, creating the directories in between
+#[cfg(test)]
+pub(crate) fn write_tree(dir: &Path, files: &[(&str, &str)]) {
+  for (path, content) in files {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+  use std::path::PathBuf;
+
+  use protolang_parser::workspace::Workspace;
+
+  use crate::target::kotlin::Kotlin;
+  use crate::{generate, write_tree, GenerateError, Options};
+
+  #[test]
+  fn generate_kotlin() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("battle/module.yaml", ""),
+      ("battle/CodegenTestModel.proto", "model CodegenTestModel = 1 {\n  client ping(team: CodegenTestTeam) = 2;\n}\n"),
+      ("lobby/module.yaml", ""),
+      ("lobby/CodegenTestTeam.proto", "enum CodegenTestTeam : i32 {\n  RED = 0;\n}\n"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+
+    let options = Options { root_package: Some("com.example".to_owned()), modules: vec!["battle".to_owned()], ..Options::default() };
+    let files = generate(&Kotlin, &workspace, &options).files;
+    assert_eq!(files.len(), 1);
+
+    let file = &files[0];
+    assert_eq!(file.path.to_string_lossy().replace('\\', "/"), "battle/CodegenTestModel.generated.kt");
+    assert!(file.contents.starts_with("package com.example.battle\n"));
+    // Definitions from other modules are still resolved
+    assert!(file.contents.contains("fun ping(team: com.example.lobby.CodegenTestTeam)"));
+  }
+
+  #[test]
+  fn module_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("battle/module.yaml", "package: com.example.fight\ntargets:\n  actionscript:\n    skip: true\n"),
+      ("battle/ModuleSettingsTeam.proto", "enum ModuleSettingsTeam : i32 {\n  RED = 0;\n}\n"),
+      ("lobby/module.yaml", ""),
+      ("lobby/ModuleSettingsType.proto", "type ModuleSettingsType {\n  meta client_name = \"ModuleSettingsType\";\n  meta client_package = \"lobby\";\n  team: ModuleSettingsTeam = 1;\n}\n"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+
+    let options = Options { root_package: Some("com.example".to_owned()), ..Options::default() };
+    let files = generate(&Kotlin, &workspace, &options).files;
+    assert_eq!(files.len(), 2);
+    assert!(files[0].contents.starts_with("package com.example.fight.battle\n"));
+    assert!(files[1].contents.starts_with("package com.example.lobby\n"));
+    assert!(files[1].contents.contains("val team: com.example.fight.battle.ModuleSettingsTeam"));
+
+    let generation = generate(&crate::target::actionscript::Actionscript, &workspace, &options);
+    assert!(!generation.files.is_empty());
+    assert!(generation.files.iter().all(|it| !it.path.starts_with("battle")));
+    assert_eq!(generation.skipped, vec![root.join("battle/ModuleSettingsTeam.proto")]);
+  }
+
+  #[test]
+  fn generation_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("GenerationErrorsTeam.proto", "enum GenerationErrorsTeam : i32 {\n  RED = 0;\n}\n"),
+      ("GenerationErrorsType.proto", "type GenerationErrorsType {\n  meta client_name = \"GenerationErrorsType\";\n  meta client_package = \"lobby\";\n  team: GenerationErrorsTeam = 1;\n}\n"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+
+    // The enum has no client package, the type is still generated
+    let generation = generate(&crate::target::actionscript::Actionscript, &workspace, &Options::default());
+    assert_eq!(generation.generated, vec![root.join("GenerationErrorsType.proto")]);
+    assert_eq!(generation.errors.len(), 1);
+    assert_eq!(generation.errors[0].path, root.join("GenerationErrorsTeam.proto"));
+    assert_eq!(generation.errors[0].error.to_string(), "GenerationErrorsTeam has no meta client_package");
+    assert_eq!(generation.files.iter().map(|it| it.path.to_str().unwrap()).collect::<Vec<_>>(), vec!["GenerationErrorsType.as", "_codec/lobby/CodecGenerationErrorsType.as"]);
+  }
+
+  #[test]
+  fn shared_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("SharedOutputsFirst.proto", "model SharedOutputsFirst = 1 {\n  constructor {\n    meta client_name = \"SharedOutputsCC\";\n    meta client_package = \"battle\";\n    speed: f32 = 1;\n  }\n}\n"),
+      ("SharedOutputsSecond.proto", "model SharedOutputsSecond = 2 {\n  constructor {\n    meta client_name = \"SharedOutputsCC\";\n    meta client_package = \"battle\";\n    health: i32 = 1;\n  }\n}\n"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+
+    // Both models produce the constructor class, the first file in workspace order wins and the
+    // second one fails without any of its outputs
+    let generation = generate(&crate::target::actionscript::Actionscript, &workspace, &Options::default());
+    let path = PathBuf::from("battle/SharedOutputsCC.as");
+    assert_eq!(generation.generated, vec![root.join("SharedOutputsFirst.proto")]);
+    assert_eq!(generation.errors.len(), 1);
+    assert_eq!(generation.errors[0].path, root.join("SharedOutputsSecond.proto"));
+    assert!(matches!(&generation.errors[0].error, GenerateError::DuplicateOutput { path: output, first } if *output == path && *first == root.join("SharedOutputsFirst.proto")));
+    let files = generation.files.iter().filter(|it| it.path == path).collect::<Vec<_>>();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].contents.contains("speed"));
+    assert!(generation.outputs.values().all(|it| *it == root.join("SharedOutputsFirst.proto")));
+  }
+
+  #[test]
+  fn reproducible_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("battle/module.yaml", ""),
+      ("battle/ReproducibleTeam.proto", "enum ReproducibleTeam : i32 {\n  meta client_package = \"battle\";\n  RED = 0;\n  BLUE = 1;\n}\n"),
+      ("battle/ReproducibleStats.proto", "type ReproducibleStats {\n  meta client_name = \"ReproducibleStats\";\n  meta client_package = \"battle\";\n  kills: Map<ReproducibleTeam, i32> = 1;\n  items: Vec = 2;\n}\n"),
+      ("battle/ReproducibleModel.proto", "model ReproducibleModel = 1 {\n  constructor {\n    meta client_name = \"ReproducibleCC\";\n    meta client_package = \"battle\";\n    stats: List<ReproducibleStats?> = 1;\n    team: ReproducibleTeam = 2;\n  }\n  client update(stats: ReproducibleStats, teams: List<ReproducibleTeam>) = 1;\n  server ready(team: ReproducibleTeam) = 2;\n}\n"),
+    ]);
+
+    // The replacement of `Vec` contains `List`, which must not be replaced again
+    let builtins = HashMap::from([
+      ("Vec".to_owned(), "util.List".to_owned()),
+      ("List".to_owned(), "kotlin.collections.List".to_owned()),
+      ("Map".to_owned(), "kotlin.collections.Map".to_owned()),
+    ]);
+    let options = Options { root_package: Some("com.example".to_owned()), builtins: Some(builtins), ..Options::default() };
+
+    // Every run has its own lookup tables, and so its own hash map order
+    let run = || {
+      let workspace = Workspace::load(root).unwrap();
+      let mut files = generate(&Kotlin, &workspace, &options).files;
+      files.extend(generate(&crate::target::actionscript::Actionscript, &workspace, &Options::default()).files);
+      files
+    };
+    let first = run();
+    for _ in 0..5 {
+      assert_eq!(run(), first);
+    }
+
+    let stats = first.iter().find(|it| it.path == PathBuf::from("battle").join("ReproducibleStats.generated.kt")).unwrap();
+    assert!(stats.contents.contains("val items: util.List"));
+    assert!(stats.contents.contains("val kills: kotlin.collections.Map<com.example.battle.ReproducibleTeam, Int>"));
+  }
+
+  #[test]
+  fn read_only_root() {
+    use protolang_parser::workspace::WorkspaceRoot;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("platform/module.yaml", "name: platform\n"),
+      ("platform/common/ReadOnlyTestTeam.proto", "enum ReadOnlyTestTeam : i32 {\n  RED = 0;\n}\n"),
+      ("game/module.yaml", ""),
+      ("game/battle/ReadOnlyTestType.proto", "type ReadOnlyTestType {\n  team: ReadOnlyTestTeam = 1;\n}\n"),
+    ]);
+
+    let roots = vec![WorkspaceRoot::new(root.join("game")), WorkspaceRoot::new(root.join("platform")).read_only(true)];
+    let workspace = Workspace::load_roots(roots).unwrap();
+
+    let files = generate(&Kotlin, &workspace, &Options::default()).files;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, PathBuf::from("battle").join("ReadOnlyTestType.generated.kt"));
+    assert!(files[0].contents.contains("val team: common.ReadOnlyTestTeam"));
+  }
+}
@@ -0,0 +1,188 @@
+//! Manifest of the generated files, stored as [MANIFEST_FILE] in the output root. It maps every
+//! output to the target and the source it was generated from, so that outputs whose source was
+//! deleted, renamed or no longer produces them can be found and removed. Files that are not in the
+//! manifest are never reported as stale, and entries that point outside of the output root are
+//! ignored, so a hand-edited manifest cannot make `--prune` delete anything else.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::is_output_path;
+
+pub const MANIFEST_FILE: &str = ".protolang-manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+  pub target: String,
+  /// Schema file or module directory relative to its input root, or plugin executable. Does not
+  /// depend on the working directory, see [Manifest::update].
+  pub source: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct Manifest {
+  pub output_root: PathBuf,
+  /// Input roots of the current run, see [Manifest::update]
+  roots: Vec<PathBuf>,
+  /// Output path relative to the output root -> entry
+  pub files: BTreeMap<PathBuf, Entry>,
+}
+
+impl Manifest {
+  /// Manifest of the given output root, empty if there is none or it cannot be read. Sources are
+  /// resolved against the input `roots`.
+  pub fn load(output_root: &Path, roots: &[&Path]) -> Manifest {
+    let path = output_root.join(MANIFEST_FILE);
+    let mut files: BTreeMap<PathBuf, Entry> = match fs::read_to_string(&path) {
+      Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+        warn!("Ignoring invalid manifest {:?}: {}", path, error);
+        BTreeMap::new()
+      }),
+      Err(_) => BTreeMap::new(),
+    };
+    files.retain(|output, _| {
+      let valid = is_output_path(output);
+      if !valid {
+        warn!("Ignoring manifest entry {:?} of {:?}, it is outside of the output root", output, path);
+      }
+      valid
+    });
+    Manifest { output_root: output_root.to_path_buf(), roots: roots.iter().map(|it| it.to_path_buf()).collect(), files }
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    fs::create_dir_all(&self.output_root)?;
+    fs::write(self.output_root.join(MANIFEST_FILE), serde_json::to_string_pretty(&self.files)?)
+  }
+
+  /// Records the outputs of a run of `target`, output path -> source, and returns the outputs of
+  /// previous runs that are stale: not produced this time although their source was `processed`,
+  /// or whose source no longer exists. Stale outputs stay in the manifest until [Manifest::remove].
+  ///
+  /// Sources are stored relative to the input root they are in and resolved against the roots
+  /// of the current run, so that the manifest stays valid when the generator runs from another
+  /// directory.
+  pub fn update(&mut self, target: &str, outputs: &BTreeMap<PathBuf, PathBuf>, processed: &BTreeSet<&Path>) -> Vec<PathBuf> {
+    let processed = processed.iter().map(|it| self.relative_source(it)).collect::<BTreeSet<_>>();
+    let stale = self.files.iter()
+      .filter(|(path, entry)| entry.target == target && !outputs.contains_key(*path))
+      .filter(|(_, entry)| processed.contains(&entry.source) || !self.source_exists(&entry.source))
+      .map(|(path, _)| path.clone())
+      .collect();
+
+    for (path, source) in outputs {
+      let source = self.relative_source(source);
+      self.files.insert(path.clone(), Entry { target: target.to_owned(), source });
+    }
+    stale
+  }
+
+  /// Deletes a stale output and the directories it leaves empty, and drops it from the manifest.
+  /// Paths outside of the output root are refused.
+  pub fn remove(&mut self, path: &Path) -> io::Result<()> {
+    if !is_output_path(path) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} is outside of the output root", path)));
+    }
+    match fs::remove_file(self.output_root.join(path)) {
+      Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+      _ => {}
+    }
+    self.files.remove(path);
+
+    // Fails on the first directory that is not empty
+    for dir in path.ancestors().skip(1).filter(|it| !it.as_os_str().is_empty()) {
+      if fs::remove_dir(self.output_root.join(dir)).is_err() {
+        break;
+      }
+    }
+    Ok(())
+  }
+
+  /// Source relative to the innermost root that contains it, unchanged if there is none
+  fn relative_source(&self, source: &Path) -> PathBuf {
+    self.roots.iter()
+      .filter_map(|root| source.strip_prefix(root).ok())
+      .min_by_key(|it| it.components().count())
+      .unwrap_or(source)
+      .to_path_buf()
+  }
+
+  fn source_exists(&self, source: &Path) -> bool {
+    self.roots.iter().any(|root| root.join(source).exists()) || source.exists()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::write_tree;
+
+  #[test]
+  fn manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let schema = root.join("schema");
+    let source = schema.join("Source.proto");
+    write_tree(root, &[
+      ("schema/Source.proto", ""),
+      ("out/battle/Old.kt", ""),
+      ("out/battle/Source.kt", ""),
+      ("out/Handwritten.kt", ""),
+    ]);
+
+    let output = root.join("out");
+    let roots = [schema.as_path()];
+    let outputs = |paths: &[&str]| paths.iter().map(|it| (PathBuf::from(it), source.clone())).collect::<BTreeMap<_, _>>();
+    let mut manifest = Manifest::load(&output, &roots);
+    assert!(manifest.update("kotlin", &outputs(&["battle/Old.kt", "battle/Source.kt"]), &BTreeSet::new()).is_empty());
+    assert_eq!(manifest.files[Path::new("battle/Old.kt")].source, PathBuf::from("Source.proto"));
+    manifest.save().unwrap();
+
+    // Sources resolve against the roots of the current run, e.g. when running from another directory
+    let moved = root.join("moved");
+    fs::rename(&schema, &moved).unwrap();
+    let mut manifest = Manifest::load(&output, &[moved.as_path()]);
+    assert!(manifest.update("kotlin", &BTreeMap::new(), &BTreeSet::new()).is_empty());
+    fs::rename(&moved, &schema).unwrap();
+    let mut manifest = Manifest::load(&output, &roots);
+
+    // Outputs of sources that were not processed in this run are kept, e.g. with `--module`
+    assert!(manifest.update("kotlin", &outputs(&["battle/Source.kt"]), &BTreeSet::new()).is_empty());
+    let processed = BTreeSet::from([source.as_path()]);
+    assert_eq!(manifest.update("kotlin", &outputs(&["battle/Source.kt"]), &processed), vec![PathBuf::from("battle/Old.kt")]);
+    manifest.remove(Path::new("battle/Old.kt")).unwrap();
+    assert!(!root.join("out/battle/Old.kt").exists());
+
+    // The source is gone, its outputs are stale even if it was not processed
+    fs::remove_file(&source).unwrap();
+    assert_eq!(manifest.update("kotlin", &BTreeMap::new(), &BTreeSet::new()), vec![PathBuf::from("battle/Source.kt")]);
+    assert!(manifest.update("actionscript", &BTreeMap::new(), &BTreeSet::new()).is_empty());
+    manifest.remove(Path::new("battle/Source.kt")).unwrap();
+    assert!(!root.join("out/battle").exists());
+    assert!(root.join("out/Handwritten.kt").exists());
+    assert!(manifest.files.is_empty());
+  }
+
+  #[test]
+  fn manifest_outside_of_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let output = root.join("out");
+    write_tree(root, &[("src/main.rs", "")]);
+    fs::create_dir_all(&output).unwrap();
+    let entry = Entry { target: "kotlin".to_owned(), source: PathBuf::from("Deleted.proto") };
+    let files = BTreeMap::from([(PathBuf::from("../src/main.rs"), entry.clone()), (PathBuf::from("battle/Old.kt"), entry)]);
+    fs::write(output.join(MANIFEST_FILE), serde_json::to_string(&files).unwrap()).unwrap();
+
+    let mut manifest = Manifest::load(&output, &[root.join("schema").as_path()]);
+    assert_eq!(manifest.files.keys().collect::<Vec<_>>(), vec![Path::new("battle/Old.kt")]);
+    assert_eq!(manifest.update("kotlin", &BTreeMap::new(), &BTreeSet::new()), vec![PathBuf::from("battle/Old.kt")]);
+    assert!(manifest.remove(Path::new("../src/main.rs")).is_err());
+    assert!(root.join("src/main.rs").exists());
+  }
+}
@@ -0,0 +1,324 @@
+//! External generators, run as a separate process in the style of `protoc` plugins.
+//!
+//! The plugin gets a [PluginRequest] as JSON on stdin and must print a [PluginResponse] as JSON on
+//! stdout, stderr is passed through. Returned paths are relative to the output root, like the ones
+//! of the built-in targets. A plugin that reports any error diagnostic fails the whole run and
+//! none of its files are written.
+//!
+//! Request:
+//! ```json
+//! {
+//!   "version": 1,
+//!   "root_package": "com.example",
+//!   "modules": [],
+//!   "files": [{
+//!     "path": "battle/TankModel.proto",
+//!     "module": "battle",
+//!     "definitions": [{ "span": { "start": 0, "end": 9, "line": 1, "column": 7 }, "kind": "model", "name": "TankModel", ... }]
+//!   }]
+//! }
+//! ```
+//!
+//! Response:
+//! ```json
+//! {
+//!   "files": [{ "path": "battle/TankModel.txt", "content": "..." }],
+//!   "diagnostics": [{ "severity": "warning", "message": "...", "path": "battle/TankModel.proto" }]
+//! }
+//! ```
+
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+use std::process::{Command, ExitStatus, Stdio};
+use std::{error, thread};
+
+use protolang_parser::diagnostic;
+use protolang_parser::hl::Definition;
+use protolang_parser::span::Span;
+use protolang_parser::workspace::{SourceFile, Workspace};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::{is_output_path, selected_files, GeneratedFile, Options};
+
+/// Incremented on incompatible changes of [PluginRequest] or [PluginResponse]
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct PluginRequest<'a> {
+  pub version: u32,
+  pub root_package: Option<&'a str>,
+  pub modules: &'a [String],
+  /// Selected files, sorted by path
+  pub files: Vec<RequestFile<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequestFile<'a> {
+  /// Relative to the workspace root, `/` separated
+  pub path: String,
+  pub module: Option<&'a str>,
+  pub definitions: Vec<RequestDefinition<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequestDefinition<'a> {
+  /// Span of the declared name
+  pub span: Span,
+  #[serde(flatten)]
+  pub definition: &'a Definition,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PluginResponse {
+  #[serde(default)]
+  pub files: Vec<ResponseFile>,
+  #[serde(default)]
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResponseFile {
+  pub path: PathBuf,
+  pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  pub message: String,
+  /// Schema file the diagnostic refers to, relative to the workspace root
+  #[serde(default)]
+  pub path: Option<String>,
+  #[serde(default)]
+  pub span: Option<Span>,
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match (&self.path, &self.span) {
+      (Some(path), Some(span)) => write!(f, "{}:{}:{}: {}", path, span.line, span.column, self.message),
+      (Some(path), None) => write!(f, "{}: {}", path, self.message),
+      _ => write!(f, "{}", self.message),
+    }
+  }
+}
+
+impl From<&Diagnostic> for diagnostic::Diagnostic {
+  fn from(diagnostic: &Diagnostic) -> Self {
+    let message = diagnostic.message.clone();
+    let converted = match diagnostic.severity {
+      Severity::Error => diagnostic::Diagnostic::error("plugin", message),
+      Severity::Warning => diagnostic::Diagnostic::warning("plugin", message),
+    };
+    match &diagnostic.path {
+      Some(path) => converted.at(PathBuf::from(path), diagnostic.span),
+      None => converted,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+  Spawn {
+    command: PathBuf,
+    error: io::Error,
+  },
+  Io(io::Error),
+  Exit {
+    command: PathBuf,
+    status: ExitStatus,
+  },
+  InvalidResponse(serde_json::Error),
+  /// Absolute path or a path leaving the output root
+  InvalidPath(PathBuf),
+  /// The plugin reported errors, all diagnostics are included
+  Failed(Vec<Diagnostic>),
+}
+
+impl Display for PluginError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      PluginError::Spawn { command, error } => write!(f, "failed to run plugin {:?}: {}", command, error),
+      PluginError::Io(error) => write!(f, "plugin i/o error: {}", error),
+      PluginError::Exit { command, status } => write!(f, "plugin {:?} exited with {}", command, status),
+      PluginError::InvalidResponse(error) => write!(f, "invalid plugin response: {}", error),
+      PluginError::InvalidPath(path) => write!(f, "plugin returned invalid output path {:?}", path),
+      PluginError::Failed(diagnostics) => {
+        let errors = diagnostics.iter().filter(|it| it.severity == Severity::Error).collect::<Vec<_>>();
+        write!(f, "plugin reported {} error(s)", errors.len())?;
+        for diagnostic in errors {
+          write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl error::Error for PluginError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      PluginError::Spawn { error, .. } => Some(error),
+      PluginError::Io(error) => Some(error),
+      PluginError::InvalidResponse(error) => Some(error),
+      PluginError::Exit { .. } | PluginError::InvalidPath(_) | PluginError::Failed(_) => None,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct PluginOutput {
+  pub files: Vec<GeneratedFile>,
+  /// Warnings reported by the plugin
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+pub fn plugin_request<'a>(workspace: &'a Workspace, options: &'a Options) -> PluginRequest<'a> {
+  let files = selected_files(workspace, options).into_iter().map(|file| RequestFile {
+    path: file.path.to_string_lossy().replace(MAIN_SEPARATOR_STR, "/"),
+    module: file.module.as_deref(),
+    definitions: request_definitions(file),
+  }).collect();
+
+  PluginRequest {
+    version: PROTOCOL_VERSION,
+    root_package: options.root_package.as_deref(),
+    modules: &options.modules,
+    files,
+  }
+}
+
+/// Pairs definitions with the AST items they were lowered from. Definitions that failed to lower
+/// or were duplicates are missing from [SourceFile::definitions], so items are matched by name in
+/// file order instead of by index.
+fn request_definitions(file: &SourceFile) -> Vec<RequestDefinition<'_>> {
+  let mut items = file.ast.body.iter();
+  file.definitions.iter().map(|definition| {
+    let span = items.by_ref()
+      .find(|item| item.name() == definition.name())
+      .map(|item| item.name_span().locate(&file.content))
+      .unwrap_or_else(Span::identity);
+    RequestDefinition { span, definition }
+  }).collect()
+}
+
+/// Runs the plugin executable on the selected workspace files
+pub fn run_plugin(command: &Path, workspace: &Workspace, options: &Options) -> Result<PluginOutput, PluginError> {
+  let request = serde_json::to_vec(&plugin_request(workspace, options)).unwrap();
+  info!("Running plugin {:?}...", command);
+
+  let mut child = Command::new(command)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::inherit())
+    .spawn()
+    .map_err(|error| PluginError::Spawn { command: command.to_path_buf(), error })?;
+
+  // Written from another thread so that a plugin that starts answering early cannot block on a full stdout
+  let mut stdin = child.stdin.take().unwrap();
+  let writer = thread::spawn(move || stdin.write_all(&request));
+  let output = child.wait_with_output().map_err(PluginError::Io)?;
+  match writer.join().unwrap() {
+    // Plugin may exit without reading the whole request
+    Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(PluginError::Io(error)),
+    _ => {}
+  }
+
+  if !output.status.success() {
+    return Err(PluginError::Exit { command: command.to_path_buf(), status: output.status });
+  }
+
+  let response: PluginResponse = serde_json::from_slice(&output.stdout).map_err(PluginError::InvalidResponse)?;
+  debug!("plugin returned {} files and {} diagnostics", response.files.len(), response.diagnostics.len());
+  if response.diagnostics.iter().any(|it| it.severity == Severity::Error) {
+    return Err(PluginError::Failed(response.diagnostics));
+  }
+
+  let mut files = Vec::new();
+  for file in response.files {
+    if !is_output_path(&file.path) {
+      return Err(PluginError::InvalidPath(file.path));
+    }
+    files.push(GeneratedFile { path: file.path, contents: file.content });
+  }
+
+  Ok(PluginOutput { files, diagnostics: response.diagnostics })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  #[cfg(unix)]
+  use std::os::unix::fs::PermissionsExt;
+
+  use protolang_parser::workspace::{Workspace, WorkspaceRoot};
+
+  use super::*;
+  use crate::write_tree;
+
+  #[cfg(unix)]
+  #[test]
+  fn plugin() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("schema/module.yaml", ""),
+      ("schema/PluginTestTeam.proto", "enum PluginTestTeam : i32 {\n  RED = 0;\n}\n"),
+    ]);
+
+    let plugin = root.join("plugin.sh");
+    let request = root.join("request.json");
+    let write_plugin = |response: &str| {
+      fs::write(&plugin, format!("#!/bin/sh\ncat > {:?}\necho '{}'\n", request, response)).unwrap();
+      fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+
+    let workspace = Workspace::load(root.join("schema")).unwrap();
+    write_plugin(r#"{"files": [{"path": "teams.txt", "content": "RED"}]}"#);
+    let output = run_plugin(&plugin, &workspace, &Options::default()).unwrap();
+    assert_eq!(output.files, vec![GeneratedFile { path: PathBuf::from("teams.txt"), contents: "RED".to_owned() }]);
+
+    let request: serde_json::Value = serde_json::from_str(&fs::read_to_string(&request).unwrap()).unwrap();
+    assert_eq!(request["version"], 1);
+    assert_eq!(request["files"][0]["path"], "PluginTestTeam.proto");
+    let definition = &request["files"][0]["definitions"][0];
+    assert_eq!((&definition["kind"], &definition["name"], &definition["span"]["line"]), (&"enum".into(), &"PluginTestTeam".into(), &0.into()));
+
+    write_plugin(r#"{"diagnostics": [{"severity": "error", "message": "unsupported"}]}"#);
+    let error = run_plugin(&plugin, &workspace, &Options::default()).unwrap_err();
+    assert!(matches!(error, PluginError::Failed(diagnostics) if diagnostics[0].message == "unsupported"));
+
+    write_plugin(r#"{"files": [{"path": "../escape.txt", "content": ""}]}"#);
+    let error = run_plugin(&plugin, &workspace, &Options::default()).unwrap_err();
+    assert!(matches!(error, PluginError::InvalidPath(_)));
+  }
+
+  #[test]
+  fn plugin_request_spans() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("A.proto", "enum Dup : i32 {\n  A = 0;\n}\n"),
+      ("B.proto", "enum Dup : i32 {\n  B = 0;\n}\n\nenum Other : i32 {\n  C = 0;\n}\n"),
+    ]);
+
+    let (workspace, errors) = Workspace::load_partial(vec![WorkspaceRoot::new(root)]);
+    assert_eq!(errors.len(), 1);
+    let options = Options::default();
+    let request = plugin_request(&workspace, &options);
+    let definitions = &request.files[1].definitions;
+    assert_eq!(definitions.len(), 1);
+    assert_eq!(definitions[0].definition.name(), "Other");
+    assert_eq!((definitions[0].span.line, definitions[0].span.column), (4, 5));
+  }
+}
@@ -0,0 +1,862 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+use tracing::{debug, info};
+
+use protolang_parser::hl::{Enum, Meta, Model, ModelConstructor, Type};
+use protolang_parser::workspace::{SourceFile, Workspace};
+
+use crate::target::Target;
+use crate::{convert_from_id, get_types_from_generic, Context, GenerateError, GeneratedFile, Options};
+
+/// ActionScript client sources. Models produce the server, base and client interface classes next
+/// to the source path, and their constructor type with its codec in the client package. Types and
+/// enums produce a class next to the source path and a codec under `_codec/`.
+pub struct Actionscript;
+
+impl Target for Actionscript {
+  fn name(&self) -> &'static str {
+    "actionscript"
+  }
+
+  fn builtin_fqn(&self) -> HashMap<String, String> {
+    let mut paths = HashMap::new();
+
+    paths.insert("Dictionary".to_owned(), "flash.utils.Dictionary".to_owned());
+
+    paths.insert("Byte".to_owned(), "alternativa.types.Byte".to_owned());
+    paths.insert("Short".to_owned(), "alternativa.types.Short".to_owned());
+    paths.insert("Long".to_owned(), "alternativa.types.Long".to_owned());
+    paths.insert("Float".to_owned(), "alternativa.types.Float".to_owned());
+    paths.insert("IGameObject".to_owned(), "platform.client.fp10.core.type.IGameObject".to_owned());
+
+    paths.insert("ObjectsData".to_owned(), "platform.client.core.general.spaces.loading.dispatcher.types.ObjectsData".to_owned());
+    paths.insert("ObjectsDependencies".to_owned(), "platform.client.core.general.spaces.loading.dispatcher.types.ObjectsDependencies".to_owned());
+    paths.insert("ModelData".to_owned(), "platform.client.core.general.spaces.loading.modelconstructors.ModelData".to_owned());
+
+    paths.insert("MoveCommand".to_owned(), "projects.tanks.client.battlefield.models.user.tank.commands.MoveCommand".to_owned());
+
+    paths.insert("Resource".to_owned(), "platform.client.fp10.core.resource.Resource".to_owned());
+    paths.insert("SoundResource".to_owned(), "platform.client.fp10.core.resource.types.SoundResource".to_owned());
+    paths.insert("MapResource".to_owned(), "projects.tanks.clients.flash.resources.resource.MapResource".to_owned());
+    paths.insert("ProplibResource".to_owned(), "projects.tanks.clients.flash.resources.resource.PropLibResource".to_owned());
+    paths.insert("TextureResource".to_owned(), "platform.client.fp10.core.resource.types.TextureResource".to_owned());
+    paths.insert("ImageResource".to_owned(), "platform.client.fp10.core.resource.types.ImageResource".to_owned());
+    paths.insert("MultiframeTextureResource".to_owned(), "platform.client.fp10.core.resource.types.MultiframeTextureResource".to_owned());
+    paths.insert("LocalizedImageResource".to_owned(), "platform.client.fp10.core.resource.types.LocalizedImageResource".to_owned());
+    paths.insert("Tanks3DSResource".to_owned(), "projects.tanks.clients.flash.resources.resource.Tanks3DSResource".to_owned());
+    paths
+  }
+
+  /// Model constructors are referenced by their client class name. Constructors without a client
+  /// name or package are left out, generating their model fails.
+  fn prepare(&self, context: &mut Context, workspace: &Workspace) {
+    info!("generating constructor index...");
+
+    for definition in workspace.models() {
+      if let Some(constructor) = &definition.constructor {
+        let (Ok(constructor_package_name), Ok(constructor_class_name)) = (
+          required_meta(&constructor.meta, "client_package", &definition.name),
+          required_meta(&constructor.meta, "client_name", &definition.name),
+        ) else {
+          continue;
+        };
+
+        let value = format!("{}.{}", constructor_package_name, constructor_class_name);
+        debug!("registered {}Base.Constructor -> {}", definition.name, constructor_class_name);
+        context.definition_fqn.insert(format!("{}.Constructor", definition.name), constructor_class_name.to_owned());
+        context.definition_fqn.insert(format!("{}Base.Constructor", definition.name), constructor_class_name.to_owned());
+        debug!("registered level 2 {} -> {}", constructor_class_name, value);
+        context.definition_fqn_2.insert(constructor_class_name.to_owned(), value);
+      }
+    }
+
+    info!("constructor index generated");
+  }
+
+  fn convert_type(&self, context: &Context, kind: &str, options: &Options) -> String {
+    convert_type(context, kind, options.root_package.as_deref())
+  }
+
+  fn generate_model(&self, context: &Context, file: &SourceFile, definition: &Model, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    let root_package = options.root_package.as_deref();
+    let relative_path = file.path.as_path();
+    let mut files = Vec::new();
+    debug!("{:?}", definition);
+
+    // Models sharing a constructor class produce the same paths, see [crate::generate]
+    if let Some(constructor) = definition.constructor.as_ref() {
+      let type_def = convert_constructor_to_type(constructor.to_owned(), &definition.name)?;
+      let client_package = required_meta(&type_def.meta, "client_package", &definition.name)?;
+
+      let class_name = if let Some(meta) = type_def.meta.iter().find(|it| it.key == "client_name") {
+        &meta.value
+      } else {
+        &type_def.name
+      };
+
+      let package = client_package.replace('.', "/");
+      push_file(&mut files, format!("{}/{}.as", package, class_name), generate_type_actionscript_code(context, &type_def, root_package));
+      push_file(&mut files, format!("_codec/{}/Codec{}.as", package, class_name), generate_type_codec_actionscript_code(context, &type_def, root_package));
+    }
+
+    let file_name = relative_path.file_name().unwrap().to_string_lossy();
+    push_file(&mut files, relative_path.with_file_name(file_name.replace(".proto", "Server.as")), generate_model_server_actionscript_code(context, definition, root_package));
+    push_file(&mut files, relative_path.with_file_name(file_name.replace(".proto", "Base.as")), generate_model_base_actionscript_code(context, definition, root_package)?);
+    push_file(&mut files, relative_path.with_file_name("I".to_owned() + &file_name.replace(".proto", "Base.as")), generate_model_client_interface_actionscript_code(context, definition, root_package));
+    Ok(files)
+  }
+
+  fn generate_type(&self, context: &Context, file: &SourceFile, definition: &Type, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    let root_package = options.root_package.as_deref();
+    debug!("{:?}", definition);
+    let code = generate_type_actionscript_code(context, definition, root_package);
+    let codec = generate_type_codec_actionscript_code(context, definition, root_package);
+    definition_files(file, &definition.name, &definition.meta, code, codec)
+  }
+
+  fn generate_enum(&self, context: &Context, file: &SourceFile, definition: &Enum, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    let root_package = options.root_package.as_deref();
+    debug!("{:?}", definition);
+    let code = generate_enum_actionscript_code(context, definition, root_package);
+    let codec = generate_enum_codec_actionscript_code(context, definition, root_package);
+    definition_files(file, &definition.name, &definition.meta, code, codec)
+  }
+}
+
+/// Class next to the source path and its codec in the client package
+fn definition_files(file: &SourceFile, name: &str, meta: &[Meta], code: String, codec: String) -> Result<Vec<GeneratedFile>, GenerateError> {
+  let client_package = required_meta(meta, "client_package", name)?;
+  let client_name = required_meta(meta, "client_name", name)?;
+
+  let mut files = Vec::new();
+  let relative_path = file.path.as_path();
+  push_file(&mut files, relative_path.with_file_name(relative_path.file_name().unwrap().to_string_lossy().replace(".proto", ".as")), code);
+
+  let package = client_package.replace('.', "/");
+  push_file(&mut files, format!("_codec/{}/Codec{}.as", package, client_name), codec);
+  Ok(files)
+}
+
+/// Value of a meta entry the generated client code cannot do without
+fn required_meta<'a>(meta: &'a [Meta], key: &str, definition: &str) -> Result<&'a str, GenerateError> {
+  match meta.iter().find(|it| it.key == key) {
+    Some(meta) => Ok(&meta.value),
+    None => Err(GenerateError::MissingMeta { definition: definition.to_owned(), key: key.to_owned() }),
+  }
+}
+
+fn push_file(files: &mut Vec<GeneratedFile>, path: impl Into<PathBuf>, contents: String) {
+  let path = path.into();
+  info!("generate actionscript code into {:?}", path);
+  debug!("{}", contents);
+  files.push(GeneratedFile { path, contents });
+}
+
+pub fn convert_constructor_to_type(constructor: ModelConstructor, model: &str) -> Result<Type, GenerateError> {
+  let name = required_meta(&constructor.meta, "client_name", model)?;
+
+  Ok(Type {
+    name: name.to_owned(),
+    fields: constructor.fields,
+    meta: constructor.meta,
+    comments: constructor.comments,
+  })
+}
+
+pub fn generate_model_server_actionscript_code(context: &Context, model: &Model, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  let mut full_package = String::new();
+  if let Some(root_package) = root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  if let Some(meta) = model.meta.iter().find(|it| it.key == "client_package") {
+    full_package.push_str(&meta.value);
+  }
+  builder.push_str(&format!("package {} {{\n", full_package));
+
+  builder.push_str(r#"  import alternativa.osgi.OSGi;
+  import alternativa.protocol.ICodec;
+  import alternativa.protocol.IProtocol;
+  import alternativa.protocol.OptionalMap;
+  import alternativa.protocol.ProtocolBuffer;
+  import alternativa.protocol.info.TypeCodecInfo;
+  import alternativa.protocol.info.EnumCodecInfo;
+  import alternativa.protocol.info.CollectionCodecInfo;
+  import alternativa.protocol.info.MapCodecInfo;
+  import alternativa.types.Long;
+  import flash.utils.ByteArray;
+  import platform.client.fp10.core.model.IModel;
+  import platform.client.fp10.core.model.impl.Model;
+  import platform.client.fp10.core.network.command.SpaceCommand;
+  import platform.client.fp10.core.type.IGameObject;
+  import platform.client.fp10.core.type.ISpace;
+"#);
+
+  let mut imports = Vec::<String>::new();
+  for method in &model.server_methods {
+    for param in &method.params {
+      imports.append(&mut get_types_from_generic(&convert_type(context, &param.kind, root_package)));
+    }
+  }
+  for method in &model.client_methods {
+    for param in &method.params {
+      imports.append(&mut get_types_from_generic(&convert_type(context, &param.kind, root_package)));
+    }
+  }
+  let imports = imports.iter().unique().sorted().map(|import| format!("  import {};", import)).join("\n");
+  builder.push_str(&imports);
+  builder.push_str("\n\n");
+
+  let class_name = if let Some(meta) = model.meta.iter().find(|it| it.key == "client_name") {
+    &meta.value
+  } else {
+    &model.name
+  };
+  builder.push_str(&format!("  public class {}Server {{\n", class_name));
+
+  builder.push_str("    private var protocol:IProtocol;\n");
+  builder.push_str("    private var protocolBuffer:ProtocolBuffer;\n");
+
+  for method in &model.server_methods {
+    builder.push_str(&format!("    private var _{}Id:Long;\n", method.name));
+
+    for param in &method.params {
+      builder.push_str(&format!("    private var _{}_{}Codec:ICodec;\n", method.name, param.name));
+    }
+    builder.push('\n');
+  }
+
+  builder.push_str("    private var model:IModel;\n");
+  builder.push('\n');
+
+  builder.push_str(&format!("    public function {}Server(model:IModel) {{\n", class_name));
+  for method in &model.server_methods {
+    let (high, low) = convert_from_id(method.id);
+    builder.push_str(&format!("      this._{}Id = Long.getLong({},{});\n", method.name, high, low));
+  }
+  builder.push_str("      super();\n");
+  builder.push_str("      this.model = model;\n");
+  builder.push_str("      var buffer:ByteArray = new ByteArray();\n");
+  builder.push_str("      this.protocol = IProtocol(OSGi.getInstance().getService(IProtocol));\n");
+  builder.push_str("      this.protocolBuffer = new ProtocolBuffer(buffer,buffer,new OptionalMap());\n");
+  for method in &model.server_methods {
+    for param in &method.params {
+      builder.push_str(&format!("      this._{}_{}Codec = this.protocol.getCodec({});\n", method.name, param.name, convert_type(context, &param.codec, root_package)));
+    }
+  }
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  for method in &model.server_methods {
+    let params = method.params.iter().map(|param| format!("{}:{}", param.name, convert_type_to_native_final(&convert_type(context, &param.kind, root_package)))).join(", ");
+    builder.push_str(&format!("    public function {}({}) : void {{\n", method.name, params));
+    builder.push_str("      ByteArray(this.protocolBuffer.writer).position = 0;\n");
+    builder.push_str("      ByteArray(this.protocolBuffer.writer).length = 0;\n");
+    for param in &method.params {
+      builder.push_str(&format!("      this._{}_{}Codec.encode(this.protocolBuffer,{});\n", method.name, param.name, param.name));
+    }
+    builder.push_str("      ByteArray(this.protocolBuffer.writer).position = 0;\n");
+    builder.push_str("      if(Model.object == null) {\n");
+    builder.push_str("        throw new Error(\"Execute method without model context.\");\n");
+    builder.push_str("      }\n");
+    builder.push_str(&format!("      var spaceCommand:SpaceCommand = new SpaceCommand(Model.object.id,this._{}Id,this.protocolBuffer);\n", method.name));
+    builder.push_str("      var gameObject:IGameObject = Model.object;\n");
+    builder.push_str("      var space:ISpace = gameObject.space;\n");
+    builder.push_str("      space.commandSender.sendCommand(spaceCommand);\n");
+    builder.push_str("      this.protocolBuffer.optionalMap.clear();\n");
+    builder.push_str("    }\n");
+    builder.push('\n');
+  }
+
+  builder.push_str("  }\n");
+
+  builder.push_str("}\n");
+
+  builder
+}
+
+pub fn generate_model_base_actionscript_code(context: &Context, model: &Model, root_package: Option<&str>) -> Result<String, GenerateError> {
+  let mut builder = String::new();
+
+  let mut full_package = String::new();
+  if let Some(root_package) = root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  if let Some(meta) = model.meta.iter().find(|it| it.key == "client_package") {
+    full_package.push_str(&meta.value);
+  }
+  builder.push_str(&format!("package {} {{\n", full_package));
+
+  builder.push_str(r#"  import alternativa.osgi.OSGi;
+  import alternativa.protocol.ICodec;
+  import alternativa.protocol.IProtocol;
+  import alternativa.protocol.ProtocolBuffer;
+  import alternativa.protocol.info.TypeCodecInfo;
+  import alternativa.protocol.info.EnumCodecInfo;
+  import alternativa.protocol.info.CollectionCodecInfo;
+  import alternativa.protocol.info.MapCodecInfo;
+  import alternativa.types.Long;
+  import platform.client.fp10.core.model.IModel;
+  import platform.client.fp10.core.model.impl.Model;
+  import platform.client.fp10.core.registry.ModelRegistry;
+"#);
+
+  let mut imports = Vec::<String>::new();
+  if model.constructor.is_some() {
+    imports.append(&mut get_types_from_generic(&convert_type(context, &format!("{}Base.Constructor", model.name), root_package)));
+  }
+  for method in &model.server_methods {
+    for param in &method.params {
+      imports.append(&mut get_types_from_generic(&convert_type(context, &param.kind, root_package)));
+    }
+  }
+  for method in &model.client_methods {
+    for param in &method.params {
+      imports.append(&mut get_types_from_generic(&convert_type(context, &param.kind, root_package)));
+    }
+  }
+  let imports = imports.iter().unique().sorted().map(|import| format!("  import {};", import)).join("\n");
+  builder.push_str(&imports);
+  builder.push_str("\n\n");
+
+  let class_name = if let Some(meta) = model.meta.iter().find(|it| it.key == "client_name") {
+    &meta.value
+  } else {
+    &model.name
+  };
+  builder.push_str(&format!("  public class {}Base extends Model {{\n", class_name));
+
+  builder.push_str("    private var _protocol:IProtocol;\n");
+  builder.push_str(&format!("    protected var server:{}Server;\n", class_name));
+  builder.push_str(&format!("    private var client:I{}Base;\n", class_name));
+  builder.push_str("    private var modelId:Long;\n");
+  builder.push('\n');
+
+  for method in &model.client_methods {
+    builder.push_str(&format!("    private var _{}Id:Long;\n", method.name));
+
+    for param in &method.params {
+      builder.push_str(&format!("    private var _{}_{}Codec:ICodec;\n", method.name, param.name));
+    }
+    builder.push('\n');
+  }
+
+  builder.push_str(&format!("    public function {}Base() {{\n", class_name));
+  builder.push_str("      this._protocol = IProtocol(OSGi.getInstance().getService(IProtocol));\n");
+  builder.push_str(&format!("      this.client = I{}Base(this);\n", class_name));
+  let (high, low) = convert_from_id(model.id);
+  builder.push_str(&format!("      this.modelId = Long.getLong({},{});\n", high, low));
+  for method in &model.client_methods {
+    let (high, low) = convert_from_id(method.id);
+    builder.push_str(&format!("      this._{}Id = Long.getLong({},{});\n", method.name, high, low));
+  }
+  builder.push_str("      super();\n");
+  builder.push_str("      this.initCodecs();\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    protected function initCodecs() : void {\n");
+  builder.push_str(&format!("      this.server = new {}Server(IModel(this));\n", class_name));
+  builder.push_str("      var modelRegistry:ModelRegistry = ModelRegistry(OSGi.getInstance().getService(ModelRegistry));\n");
+  if let Some(constructor) = &model.constructor {
+    let constructor_class_name = required_meta(&constructor.meta, "client_name", &model.name)?;
+    builder.push_str(&format!("      modelRegistry.registerModelConstructorCodec(this.modelId,this._protocol.getCodec(new TypeCodecInfo({},false)));\n", convert_type(context, constructor_class_name, root_package)));
+  }
+  for method in &model.client_methods {
+    for param in &method.params {
+      builder.push_str(&format!("      this._{}_{}Codec = this._protocol.getCodec({});\n", method.name, param.name, convert_type(context, &param.codec, root_package)));
+    }
+  }
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  if let Some(constructor) = &model.constructor {
+    let constructor_class_name = required_meta(&constructor.meta, "client_name", &model.name)?;
+    builder.push_str(&format!("    protected function getInitParam() : {} {{\n", convert_type(context, constructor_class_name, root_package)));
+    builder.push_str(&format!("      return {}(initParams[Model.object]);\n", convert_type(context, constructor_class_name, root_package)));
+    builder.push_str("    }\n");
+    builder.push('\n');
+  }
+
+  builder.push_str("    override public function invoke(methodId:Long, buffer:ProtocolBuffer) : void {\n");
+  builder.push_str("      switch(methodId) {\n");
+  for method in &model.client_methods {
+    let mut params = Vec::new();
+    for param in &method.params {
+      let native_type = convert_type_to_native_final(&convert_type(context, &param.kind, root_package));
+      params.push(format!("{}(this._{}_{}Codec.decode(buffer))", native_type, method.name, param.name));
+    }
+
+    builder.push_str(&format!("        case this._{}Id:\n", method.name));
+    builder.push_str(&format!("          this.client.{}({});\n", method.name, params.join(", ")));
+    builder.push_str("          break;\n");
+  }
+  builder.push_str("      }\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    override public function get id() : Long {\n");
+  builder.push_str("      return this.modelId;\n");
+  builder.push_str("    }\n");
+
+  builder.push_str("  }\n");
+
+  builder.push_str("}\n");
+
+  Ok(builder)
+}
+
+pub fn generate_model_client_interface_actionscript_code(context: &Context, model: &Model, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  let mut full_package = String::new();
+  if let Some(root_package) = root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  if let Some(meta) = model.meta.iter().find(|it| it.key == "client_package") {
+    full_package.push_str(&meta.value);
+  }
+  builder.push_str(&format!("package {} {{\n", full_package));
+
+  let mut imports = Vec::<String>::new();
+  for method in &model.server_methods {
+    for param in &method.params {
+      imports.append(&mut get_types_from_generic(&convert_type_to_native_final(&convert_type(context, &param.kind, root_package))));
+    }
+  }
+  for method in &model.client_methods {
+    for param in &method.params {
+      imports.append(&mut get_types_from_generic(&convert_type_to_native_final(&convert_type(context, &param.kind, root_package))));
+    }
+  }
+  let imports = imports.iter().unique().sorted().map(|import| format!("  import {};", import)).join("\n");
+  builder.push_str(&imports);
+  builder.push_str("\n\n");
+
+  let class_name = if let Some(meta) = model.meta.iter().find(|it| it.key == "client_name") {
+    &meta.value
+  } else {
+    &model.name
+  };
+  builder.push_str(&format!("  public interface I{}Base {{\n", class_name));
+
+  for method in &model.client_methods {
+    let params = method.params.iter().map(|param| format!("{}:{}", param.name, convert_type_to_native_final(&convert_type(context, &param.kind, root_package)))).join(", ");
+    builder.push_str(&format!(
+      "    function {}({}) : void;\n",
+      method.name,
+      params
+    ));
+  }
+
+  builder.push_str("  }\n");
+
+  builder.push_str("}\n");
+
+  builder
+}
+
+pub fn generate_type_actionscript_code(context: &Context, type_def: &Type, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  let mut full_package = String::new();
+  if let Some(root_package) = root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  if let Some(meta) = type_def.meta.iter().find(|it| it.key == "client_package") {
+    full_package.push_str(&meta.value);
+  }
+  builder.push_str(&format!("package {} {{\n", full_package));
+
+  let mut imports = Vec::<String>::new();
+  for field in &type_def.fields {
+    imports.append(&mut get_types_from_generic(&convert_type_to_native_final(&convert_type(context, &field.kind, root_package))));
+  }
+  let imports = imports.iter().unique().sorted().map(|import| format!("  import {};", import)).join("\n");
+  builder.push_str(&imports);
+  builder.push_str("\n\n");
+
+  let class_name = if let Some(meta) = type_def.meta.iter().find(|it| it.key == "client_name") {
+    &meta.value
+  } else {
+    &type_def.name
+  };
+  builder.push_str(&format!("  public class {} {{\n", class_name));
+
+  for field in &type_def.fields {
+    let native_type = &convert_type_to_native_final(&convert_type(context, &field.kind, root_package));
+    builder.push_str(&format!(
+      "    private var _{}:{};\n",
+      field.name,
+      native_type
+    ));
+  }
+  if !type_def.fields.is_empty() {
+    builder.push('\n');
+  }
+
+  let mut params = Vec::new();
+  for field in &type_def.fields {
+    let native_type = convert_type_to_native_final(&convert_type(context, &field.kind, root_package));
+    let default = match native_type.as_str() {
+      "int" => "0",
+      "Number" => "0",
+      "Boolean" => "false",
+      _ => "null"
+    };
+    params.push(format!("{}:{} = {}", field.name, native_type, default));
+  }
+  builder.push_str(&format!("    public function {}({}) {{\n", class_name, params.join(", ")));
+  builder.push_str("      super();\n");
+  for field in &type_def.fields {
+    builder.push_str(&format!("      this._{} = {};\n", field.name, field.name));
+  }
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  for field in &type_def.fields {
+    let native_type = convert_type_to_native_final(&convert_type(context, &field.kind, root_package));
+    builder.push_str(&format!("    public function get {}() : {} {{\n", field.name, native_type));
+    builder.push_str(&format!("      return this._{};\n", field.name));
+    builder.push_str("    }\n");
+    builder.push('\n');
+    builder.push_str(&format!("    public function set {}(value:{}) : void {{\n", field.name, native_type));
+    builder.push_str(&format!("      this._{} = value;\n", field.name));
+    builder.push_str("    }\n");
+    builder.push('\n');
+  }
+
+  builder.push_str("    public function toString() : String {\n");
+  builder.push_str(&format!("      var string:String = \"{} [\";\n", class_name));
+  for field in &type_def.fields {
+    builder.push_str(&format!("      string += \"{} = \" + this._{} + \" \";\n", field.name, field.name));
+  }
+  builder.push_str("      return string + \"]\";\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("  }\n");
+
+  builder.push_str("}\n");
+
+  builder
+}
+
+pub fn generate_enum_actionscript_code(context: &Context, enum_def: &Enum, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  let mut full_package = String::new();
+  if let Some(root_package) = root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  if let Some(meta) = enum_def.meta.iter().find(|it| it.key == "client_package") {
+    full_package.push_str(&meta.value);
+  }
+  builder.push_str(&format!("package {} {{\n", full_package));
+
+  let class_name = if let Some(meta) = enum_def.meta.iter().find(|it| it.key == "client_name") {
+    &meta.value
+  } else {
+    &enum_def.name
+  };
+  builder.push_str(&format!("  public class {} {{\n", class_name));
+
+  for variant in &enum_def.variants {
+    builder.push_str(&format!(
+      "    public static const {}:{} = new {}({},\"{}\");\n",
+      variant.name,
+      class_name,
+      class_name,
+      variant.value,
+      variant.name
+    ));
+  }
+  if !enum_def.variants.is_empty() {
+    builder.push('\n');
+  }
+
+  let native_repr = convert_type(context, &enum_def.repr, root_package);
+
+  builder.push_str(&format!("    private var _value:{};\n", native_repr));
+  builder.push_str("    private var _name:String;\n");
+  builder.push('\n');
+
+  builder.push_str(&format!("    public function {}(value:{}, name:String) {{\n", class_name, native_repr));
+  builder.push_str("      super();\n");
+  builder.push_str("      this._value = value;\n");
+  builder.push_str("      this._name = name;\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str(&format!("    public static function get values() : Vector.<{}> {{\n", class_name));
+  builder.push_str(&format!("      var values:Vector.<{}> = new Vector.<{}>();\n", class_name, class_name));
+  for variant in &enum_def.variants {
+    builder.push_str(&format!("      values.push({});\n", variant.name));
+  }
+  builder.push_str("      return values;\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    public function toString() : String {\n");
+  builder.push_str(&format!("      return \"{} [\" + this._name + \"]\";\n", class_name));
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str(&format!("    public function get value() : {} {{\n", native_repr));
+  builder.push_str("      return this._value;\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    public function get name() : String {\n");
+  builder.push_str("      return this._name;\n");
+  builder.push_str("    }\n");
+
+  builder.push_str("  }\n");
+
+  builder.push_str("}\n");
+
+  builder
+}
+
+pub fn generate_type_codec_actionscript_code(context: &Context, type_def: &Type, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  let mut full_package = String::new();
+  full_package.push_str("_codec.");
+  if let Some(root_package) = root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  if let Some(meta) = type_def.meta.iter().find(|it| it.key == "client_package") {
+    full_package.push_str(&meta.value);
+  }
+  builder.push_str(&format!("package {} {{\n", full_package));
+
+  let class_name = if let Some(meta) = type_def.meta.iter().find(|it| it.key == "client_name") {
+    &meta.value
+  } else {
+    &type_def.name
+  };
+
+  builder.push_str(r#"  import alternativa.osgi.OSGi;
+  import alternativa.osgi.service.clientlog.IClientLog;
+  import alternativa.protocol.ICodec;
+  import alternativa.protocol.IProtocol;
+  import alternativa.protocol.ProtocolBuffer;
+  import alternativa.protocol.info.TypeCodecInfo;
+  import alternativa.protocol.info.EnumCodecInfo;
+  import alternativa.protocol.info.CollectionCodecInfo;
+  import alternativa.protocol.info.MapCodecInfo;
+"#);
+
+  let mut imports = Vec::<String>::new();
+  imports.append(&mut get_types_from_generic(&convert_type(context, class_name, root_package)));
+  for field in &type_def.fields {
+    imports.append(&mut get_types_from_generic(&convert_type(context, &field.kind, root_package)));
+  }
+  let imports = imports.iter().unique().sorted().map(|import| format!("  import {};", import)).join("\n");
+  builder.push_str(&imports);
+  builder.push_str("\n\n");
+
+  builder.push_str(&format!("  public class Codec{} implements ICodec {{\n", class_name));
+  builder.push_str("    public static var log:IClientLog = IClientLog(OSGi.getInstance().getService(IClientLog));\n\n");
+
+  for field in &type_def.fields {
+    builder.push_str(&format!(
+      "    private var codec_{}:ICodec;\n",
+      field.name
+    ));
+  }
+  if !type_def.fields.is_empty() {
+    builder.push('\n');
+  }
+
+  builder.push_str(&format!("    public function Codec{}() {{\n", class_name));
+  builder.push_str("      super();\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    public function init(protocol:IProtocol) : void {\n");
+  for field in &type_def.fields {
+    // Do not call [convert_type_to_native_final] because int conflicts with Short and Byte
+    let native_codec = convert_type(context, &field.codec, root_package);
+    builder.push_str(&format!("      this.codec_{} = protocol.getCodec({});\n", field.name, native_codec));
+  }
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    public function decode(buffer:ProtocolBuffer) : Object {\n");
+  builder.push_str(&format!("      var result:{} = new {}();\n", convert_type_to_native_final(&convert_type(context, class_name, root_package)), convert_type_to_native_final(&convert_type(context, class_name, root_package))));
+  for field in &type_def.fields {
+    let native_type = convert_type_to_native_final(&convert_type(context, &field.kind, root_package));
+    builder.push_str(&format!("      result.{} = this.codec_{}.decode(buffer) as {};\n", field.name, field.name, native_type));
+  }
+  builder.push_str("      return result;\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    public function encode(buffer:ProtocolBuffer, value:Object) : void {\n");
+  builder.push_str("      if(value == null) {\n");
+  builder.push_str("        throw new Error(\"Object is null. Use @ProtocolOptional annotation.\");\n");
+  builder.push_str("      }\n");
+  builder.push_str(&format!("      var castValue:{} = {}(value);\n", convert_type_to_native_final(&convert_type(context, class_name, root_package)), convert_type_to_native_final(&convert_type(context, class_name, root_package))));
+  for field in &type_def.fields {
+    builder.push_str(&format!("      this.codec_{}.encode(buffer,castValue.{});\n", field.name, field.name));
+  }
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("  }\n");
+
+  builder.push_str("}\n");
+
+  builder
+}
+
+pub fn generate_enum_codec_actionscript_code(context: &Context, enum_def: &Enum, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  let mut full_package = String::new();
+  full_package.push_str("_codec.");
+  if let Some(root_package) = root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  if let Some(meta) = enum_def.meta.iter().find(|it| it.key == "client_package") {
+    full_package.push_str(&meta.value);
+  }
+  builder.push_str(&format!("package {} {{\n", full_package));
+
+  let class_name = if let Some(meta) = enum_def.meta.iter().find(|it| it.key == "client_name") {
+    &meta.value
+  } else {
+    &enum_def.name
+  };
+
+  builder.push_str(r#"  import alternativa.protocol.ICodec;
+  import alternativa.protocol.IProtocol;
+  import alternativa.protocol.ProtocolBuffer;
+"#);
+
+  let mut imports = Vec::<String>::new();
+  imports.append(&mut get_types_from_generic(&convert_type(context, class_name, root_package)));
+  let imports = imports.iter().unique().sorted().map(|import| format!("  import {};", import)).join("\n");
+  builder.push_str(&imports);
+  builder.push_str("\n\n");
+
+  builder.push_str(&format!("  public class Codec{} implements ICodec {{\n", class_name));
+
+  builder.push_str(&format!("    public function Codec{}() {{\n", class_name));
+  builder.push_str("      super();\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    public function init(protocol:IProtocol) : void {\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  let native_type = convert_type(context, class_name, root_package);
+  let native_repr = convert_type(context, &enum_def.repr, root_package);
+  builder.push_str("    public function decode(buffer:ProtocolBuffer) : Object {\n");
+  builder.push_str(&format!("      var result:{} = null;\n", native_type));
+  assert_eq!(enum_def.repr, "i32");
+  builder.push_str(&format!("      var repr:{} = {}(buffer.reader.readInt());\n", native_repr, native_repr));
+  builder.push_str("      switch(repr) {\n");
+  for variant in &enum_def.variants {
+    builder.push_str(&format!("        case {}:\n", variant.value));
+    builder.push_str(&format!("          result = {}.{};\n", native_type, variant.name));
+    builder.push_str("          break;\n");
+  }
+  builder.push_str("      }\n");
+  builder.push_str("      return result;\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("    public function encode(buffer:ProtocolBuffer, value:Object) : void {\n");
+  builder.push_str("      if(value == null) {\n");
+  builder.push_str("        throw new Error(\"Object is null. Use @ProtocolOptional annotation.\");\n");
+  builder.push_str("      }\n");
+  builder.push_str(&format!("      var repr:{} = {}(value.value);\n", native_repr, native_repr));
+  assert_eq!(enum_def.repr, "i32");
+  builder.push_str("      buffer.writer.writeInt(repr);\n");
+  builder.push_str("    }\n");
+  builder.push('\n');
+
+  builder.push_str("  }\n");
+
+  builder.push_str("}\n");
+
+  builder
+}
+
+lazy_static! {
+  static ref REGEX_1: Regex = Regex::new(r"\bbool\b").unwrap();
+  static ref REGEX_2: Regex = Regex::new(r"\bi8\b").unwrap();
+  static ref REGEX_3: Regex = Regex::new(r"\bi16\b").unwrap();
+  static ref REGEX_4: Regex = Regex::new(r"\bi32\b").unwrap();
+  static ref REGEX_5: Regex = Regex::new(r"\bi64\b").unwrap();
+  static ref REGEX_6: Regex = Regex::new(r"\bf32\b").unwrap();
+  static ref REGEX_7: Regex = Regex::new(r"\bf64\b").unwrap();
+  static ref REGEX_8: Regex = Regex::new(r"\bObject3DResource\b").unwrap();
+  static ref REGEX_9: Regex = Regex::new(r"\bInstant\b").unwrap();
+  static ref REGEX_10: Regex = Regex::new(r"\bList<").unwrap();
+  static ref REGEX_11: Regex = Regex::new(r"\bMap<.+>").unwrap();
+  static ref REGEX_NULLABLE: Regex = Regex::new(r"\?").unwrap();
+
+  static ref REGEX_12: Regex = Regex::new(r"\balternativa\.types\.(Byte|Short)\b").unwrap();
+  static ref REGEX_13: Regex = Regex::new(r"\balternativa\.types\.Float\b").unwrap();
+}
+
+pub fn convert_type_to_native_final(value: &str) -> String {
+  let value = REGEX_12.replace_all(value, "int");
+  let value = REGEX_13.replace_all(&value, "Number");
+  value.to_string()
+}
+
+pub fn convert_type(context: &Context, value: &str, root_package: Option<&str>) -> String {
+  let value = REGEX_1.replace_all(value, "Boolean");
+  let value = REGEX_2.replace_all(&value, "Byte");
+  let value = REGEX_3.replace_all(&value, "Short");
+  let value = REGEX_4.replace_all(&value, "int");
+  let value = REGEX_5.replace_all(&value, "Long");
+  let value = REGEX_6.replace_all(&value, "Float");
+  let value = REGEX_7.replace_all(&value, "Number");
+  let value = REGEX_8.replace_all(&value, "Tanks3DSResource");
+  let value = REGEX_9.replace_all(&value, "Date");
+  let value = REGEX_10.replace_all(&value, "Vector.<");
+  let value = REGEX_11.replace_all(&value, "Dictionary");
+  let value = REGEX_NULLABLE.replace_all(&value, "");
+
+  // Replace all "ShortName" with "fqn.FullName"
+  let qualify = |simple_name: &str, full_name: &str| {
+    let mut fqn = String::new();
+    if let Some(root_package) = context.root_package(simple_name, root_package) {
+      fqn.push_str(root_package);
+      fqn.push('.');
+    }
+    fqn.push_str(full_name);
+    fqn
+  };
+
+  let value = context.replace_names("builtin", &context.builtin_fqn, &value, |_, full_name| full_name.to_owned());
+  let old_value = value.clone();
+  let value = context.replace_names("level1", &context.definition_fqn, &value, qualify);
+  if value != old_value {
+    debug!("replaced level 1 {old_value} -> {value}");
+  }
+  let old_value = value.clone();
+  let value = context.replace_names("level2", &context.definition_fqn_2, &value, qualify);
+  if value != old_value {
+    debug!("replaced level 2 {old_value} -> {value}");
+  }
+  value
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::path::MAIN_SEPARATOR_STR;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use protolang_parser::hl::{Enum, Model, Template, Type, Value};
+use protolang_parser::workspace::SourceFile;
+use regex::Regex;
+use tracing::{debug, info};
+
+use crate::target::Target;
+use crate::{Context, GenerateError, GeneratedFile, Options};
+
+/// Used unless [Options::imports] is set
+pub const DEFAULT_IMPORTS: [&str; 3] = [
+  "jp.assasans.araumi.models.*",
+  "jp.assasans.araumi.protocol.codec.wired.*",
+  "jp.assasans.araumi.architecture.spaces.*",
+];
+
+/// Kotlin sources for the araumi server, one `.generated.kt` file per definition next to the
+/// source path
+pub struct Kotlin;
+
+impl Target for Kotlin {
+  fn name(&self) -> &'static str {
+    "kotlin"
+  }
+
+  /// Primitives are globally available
+  fn builtin_fqn(&self) -> HashMap<String, String> {
+    let mut paths = HashMap::new();
+    for primitive in ["bool", "i8", "i16", "i32", "i64", "f32", "f64", "String"] {
+      paths.insert(primitive.to_owned(), primitive.to_owned());
+    }
+
+    paths.insert("Instant".to_owned(), "kotlinx.datetime.Instant".to_owned());
+    paths.insert("IGameObject".to_owned(), "jp.assasans.araumi.architecture.objects.IGameObject".to_owned());
+
+    paths.insert("ObjectsData".to_owned(), "jp.assasans.araumi.protocol.codec.ObjectsData".to_owned());
+    paths.insert("ObjectsDependencies".to_owned(), "jp.assasans.araumi.protocol.codec.ObjectsDependencies".to_owned());
+    paths.insert("ModelData".to_owned(), "jp.assasans.araumi.protocol.codec.ModelData".to_owned());
+
+    paths.insert("MoveCommand".to_owned(), "jp.assasans.araumi.protocol.codec.MoveCommand".to_owned());
+
+    paths.insert("Resource".to_owned(), "jp.assasans.araumi.resources.Resource".to_owned());
+    paths.insert("SoundResource".to_owned(), "jp.assasans.araumi.resources.SoundResource".to_owned());
+    paths.insert("MapResource".to_owned(), "jp.assasans.araumi.resources.MapResource".to_owned());
+    paths.insert("ProplibResource".to_owned(), "jp.assasans.araumi.resources.ProplibResource".to_owned());
+    paths.insert("TextureResource".to_owned(), "jp.assasans.araumi.resources.TextureResource".to_owned());
+    paths.insert("ImageResource".to_owned(), "jp.assasans.araumi.resources.ImageResource".to_owned());
+    paths.insert("MultiframeTextureResource".to_owned(), "jp.assasans.araumi.resources.MultiframeTextureResource".to_owned());
+    paths.insert("LocalizedImageResource".to_owned(), "jp.assasans.araumi.resources.LocalizedImageResource".to_owned());
+    paths.insert("Object3DResource".to_owned(), "jp.assasans.araumi.resources.Object3DResource".to_owned());
+    paths
+  }
+
+  fn convert_type(&self, context: &Context, kind: &str, options: &Options) -> String {
+    convert_type(context, kind, options.root_package.as_deref())
+  }
+
+  fn generate_model(&self, context: &Context, file: &SourceFile, model: &Model, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(vec![wrap(file, generate_model_kotlin_code(context, model, options.root_package.as_deref()), options)])
+  }
+
+  fn generate_type(&self, context: &Context, file: &SourceFile, type_def: &Type, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(vec![wrap(file, generate_type_kotlin_code(context, type_def, options.root_package.as_deref()), options)])
+  }
+
+  fn generate_enum(&self, context: &Context, file: &SourceFile, enum_def: &Enum, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(vec![wrap(file, generate_enum_kotlin_code(context, enum_def, options.root_package.as_deref()), options)])
+  }
+
+  fn generate_template(&self, context: &Context, file: &SourceFile, template: &Template, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(vec![wrap(file, generate_template_kotlin_code(context, template, options.root_package.as_deref())?, options)])
+  }
+}
+
+/// Adds the package declaration and the imports, see [DEFAULT_IMPORTS]
+fn wrap(file: &SourceFile, code: String, options: &Options) -> GeneratedFile {
+  let relative_path = file.path.with_file_name(file.path.file_name().unwrap().to_string_lossy().replace(".proto", ".generated.kt"));
+  let package = relative_path.parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".");
+  info!("generate kotlin code into {:?}", relative_path);
+
+  let mut full_package = String::new();
+  if let Some(root_package) = &options.root_package {
+    full_package.push_str(root_package);
+    full_package.push('.');
+  }
+  full_package.push_str(&package);
+
+  let mut wrapped_code = String::new();
+  wrapped_code.push_str(&format!("package {}\n\n", full_package));
+  let default_imports = DEFAULT_IMPORTS.map(ToOwned::to_owned);
+  let imports = options.imports.as_deref().unwrap_or(&default_imports[..]);
+  for import in imports {
+    wrapped_code.push_str(&format!("import {}\n", import));
+  }
+  if !imports.is_empty() {
+    wrapped_code.push('\n');
+  }
+  wrapped_code.push_str(&code);
+  debug!("{}", wrapped_code);
+
+  GeneratedFile { path: relative_path, contents: wrapped_code }
+}
+
+/*
+@ModelInfo(6071565290933648049)
+abstract class ChatModelBase : Model(),
+                               IConstructableModel<ChatModelBase.Constructor>,
+                               IModelCI<ChatModelBase.Client> by ModelCI(Client::class),
+                               IModelSI<ChatModelBase.ServerBase> by ModelSI(ServerBase::class) {
+  @Wired
+  data class Constructor(
+    @Wire(0) val admin: Boolean,
+    @Wire(1) val antifloodEnabled: Boolean
+  ) : ModelConstructor
+
+  interface Client : ClientInterface {
+    @ModelMethod(3430453981713932879) suspend fun cleanUsersMessages(username: String)
+    @ModelMethod(4202027557179282961) suspend fun showMessages(messages: List<ChatMessage>)
+  }
+
+  sealed class ServerBase : ServerInterface {
+    override lateinit var client: ISpaceClient
+
+    @ModelMethod(6683616035809206555) abstract suspend fun changeChannel(channel: String)
+    @ModelMethod(3122753540375943279) abstract suspend fun sendMessage(params: SendMessageParams)
+  }
+}
+*/
+
+pub fn generate_model_kotlin_code(context: &Context, model: &Model, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  if !model.comments.is_empty() {
+    builder.push_str("/**\n");
+    for comment in &model.comments {
+      builder.push_str(&format!(" * {}\n", comment));
+    }
+    builder.push_str(" */\n");
+  }
+
+  builder.push_str(&format!("@ModelInfo({})\n", model.id));
+  builder.push_str(&format!("abstract class {}Base : ", model.name));
+
+  let mut supertypes = Vec::new();
+  supertypes.push("Model()".to_owned());
+  if model.constructor.is_some() {
+    supertypes.push(format!("  IConstructableModel<{}Base.Constructor>", model.name));
+  }
+  if !model.client_methods.is_empty() {
+    supertypes.push(format!("  IModelCI<{}Base.Client> by ModelCI(Client::class)", model.name));
+  }
+  if !model.server_methods.is_empty() {
+    supertypes.push(format!("  IModelSI<{}Base.ServerBase> by ModelSI(ServerBase::class)", model.name));
+  }
+  builder.push_str(&supertypes.join(",\n"));
+  builder.push_str(" {\n");
+
+  let mut segments = Vec::new();
+  if let Some(constructor) = &model.constructor {
+    let mut builder = String::new();
+
+    if !constructor.comments.is_empty() {
+      builder.push_str("  /**\n");
+      for comment in &constructor.comments {
+        builder.push_str(&format!("   * {}\n", comment));
+      }
+      builder.push_str("   */\n");
+    }
+
+    builder.push_str("  @Wired\n");
+    builder.push_str("  data class Constructor(\n");
+    for field in &constructor.fields {
+      if !field.comments.is_empty() {
+        builder.push_str("    /**\n");
+        for comment in &field.comments {
+          builder.push_str(&format!("     * {}\n", comment));
+        }
+        builder.push_str("     */\n");
+      }
+      builder.push_str(&format!("    @Wire({}) val {}: {},\n", field.position - 1, field.name, convert_type(context, &field.kind, root_package)));
+    }
+    builder.push_str("  ) : ModelConstructor\n");
+    segments.push(builder);
+  }
+
+  if !model.client_methods.is_empty() {
+    let mut builder = String::new();
+
+    builder.push_str("  interface Client : ClientInterface {\n");
+    for method in &model.client_methods {
+      let params = method.params.iter().map(|it| format!("{}: {}", it.name, convert_type(context, &it.kind, root_package))).join(", ");
+      builder.push_str(&format!("    @ModelMethod({}) fun {}({})\n", method.id, method.name, params))
+    }
+    builder.push_str("  }\n");
+
+    segments.push(builder);
+  }
+
+  if !model.server_methods.is_empty() {
+    let mut builder = String::new();
+
+    builder.push_str("  sealed class ServerBase : ServerInterface {\n");
+    builder.push_str("    override lateinit var client: ISpaceClient\n");
+    builder.push('\n');
+    for method in &model.server_methods {
+      let params = method.params.iter().map(|it| format!("{}: {}", it.name, convert_type(context, &it.kind, root_package))).join(", ");
+      if !method.comments.is_empty() {
+        builder.push_str("    /**\n");
+        for comment in &method.comments {
+          builder.push_str(&format!("     * {}\n", comment));
+        }
+        builder.push_str("     */\n");
+      }
+
+      builder.push_str(&format!("    @ModelMethod({}) abstract suspend fun {}({})\n", method.id, method.name, params))
+    }
+    builder.push_str("  }\n");
+
+    segments.push(builder);
+  }
+
+  builder.push_str(&segments.join("\n"));
+  builder.push_str("}\n");
+
+  builder
+}
+
+/*
+@Wired
+data class SomeConstructor(
+  @Wire(0) val admin: Boolean,
+  @Wire(1) val antifloodEnabled: Boolean
+)
+*/
+pub fn generate_type_kotlin_code(context: &Context, type_def: &Type, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  if !type_def.comments.is_empty() {
+    builder.push_str("/**\n");
+    for comment in &type_def.comments {
+      builder.push_str(&format!(" * {}\n", comment));
+    }
+    builder.push_str(" */\n");
+  }
+
+  builder.push_str("@Wired\n");
+  builder.push_str(&format!("data class {}(\n", type_def.name));
+  for field in &type_def.fields {
+    if !field.comments.is_empty() {
+      builder.push_str("  /**\n");
+      for comment in &field.comments {
+        builder.push_str(&format!("   * {}\n", comment));
+      }
+      builder.push_str("   */\n");
+    }
+    builder.push_str(&format!("  @Wire({}) val {}: {},\n", field.position - 1, field.name, convert_type(context, &field.kind, root_package)));
+  }
+  builder.push_str(")\n");
+
+  builder
+}
+
+/*
+@WiredEnum(Int::class)
+enum class BattleTeam(override val value: Int) : IWiredEnum<Int> {
+  RED(0),
+  BLUE(1),
+  NONE(2);
+}
+*/
+pub fn generate_enum_kotlin_code(context: &Context, enum_def: &Enum, root_package: Option<&str>) -> String {
+  let mut builder = String::new();
+
+  if !enum_def.comments.is_empty() {
+    builder.push_str("/**\n");
+    for comment in &enum_def.comments {
+      builder.push_str(&format!(" * {}\n", comment));
+    }
+    builder.push_str(" */\n");
+  }
+
+  let repr_converted = convert_type(context, &enum_def.repr, root_package);
+  builder.push_str(&format!("@WiredEnum({}::class)\n", repr_converted));
+  builder.push_str(&format!("enum class {}(override val value: {}) : IWiredEnum<{}> {{\n", enum_def.name, repr_converted, repr_converted));
+  for variant in &enum_def.variants {
+    if !variant.comments.is_empty() {
+      builder.push_str("  /**\n");
+      for comment in &variant.comments {
+        builder.push_str(&format!("   * {}\n", comment));
+      }
+      builder.push_str("   */\n");
+    }
+    builder.push_str(&format!("  {}({}),\n", variant.name, variant.value));
+  }
+  builder.push_str("}\n");
+
+  builder
+}
+
+/*
+object TankObjectTemplate {
+  fun create(
+    tankModel: TankModelBase.Constructor,
+    healthModelHealth: Int,
+  ): List<ModelData> = listOf(
+    ModelData(6071565290933648049, tankModel),
+    ModelData(3430453981713932879, HealthModelBase.Constructor(
+      maxHealth = 100,
+      health = healthModelHealth,
+    )),
+    ModelData(4202027557179282961, null),
+  )
+}
+*/
+pub fn generate_template_kotlin_code(context: &Context, template: &Template, root_package: Option<&str>) -> Result<String, GenerateError> {
+  let mut builder = String::new();
+
+  if !template.comments.is_empty() {
+    builder.push_str("/**\n");
+    for comment in &template.comments {
+      builder.push_str(&format!(" * {}\n", comment));
+    }
+    builder.push_str(" */\n");
+  }
+
+  let model_data = convert_type(context, "ModelData", root_package);
+  let models = &context.model_definitions;
+  let mut params = Vec::new();
+  let mut items = Vec::new();
+  for item in &template.models {
+    let invalid = |message: String| GenerateError::InvalidTemplate { template: template.name.clone(), message };
+    let model = match models.get(&item.name) {
+      Some(model) => model,
+      None => return Err(invalid(format!("uses unknown model {}", item.name)))
+    };
+
+    let variable = lowercase_first(&item.name);
+    let data = match &model.constructor {
+      None if item.values.is_empty() => "null".to_owned(),
+      None => return Err(invalid(format!("sets constructor values for model {} which has no constructor", item.name))),
+      Some(_) if item.values.is_empty() => {
+        params.push(format!("    {}: {}.Constructor,\n", variable, convert_type(context, &model.name, root_package)));
+        variable
+      }
+      Some(constructor) => {
+        for value in &item.values {
+          if !constructor.fields.iter().any(|it| it.name == value.name) {
+            return Err(invalid(format!("sets unknown constructor field {} of model {}", value.name, item.name)));
+          }
+        }
+
+        let mut args = String::new();
+        for field in &constructor.fields {
+          let value = match item.values.iter().find(|it| it.name == field.name) {
+            Some(value) => convert_value(context, &value.value, &field.kind, root_package),
+            None => {
+              // Fields without a fixed value become factory parameters
+              let param = format!("{}{}", variable, uppercase_first(&field.name));
+              params.push(format!("    {}: {},\n", param, convert_type(context, &field.kind, root_package)));
+              param
+            }
+          };
+          args.push_str(&format!("      {} = {},\n", field.name, value));
+        }
+        format!("{}.Constructor(\n{}    )", convert_type(context, &model.name, root_package), args)
+      }
+    };
+
+    for comment in &item.comments {
+      items.push(format!("    // {}\n", comment));
+    }
+    items.push(format!("    {}({}, {}),\n", model_data, model.id, data));
+  }
+
+  builder.push_str(&format!("object {}Template {{\n", template.name));
+  builder.push_str("  fun create(\n");
+  builder.push_str(&params.join(""));
+  builder.push_str(&format!("  ): List<{}> = listOf(\n", model_data));
+  builder.push_str(&items.join(""));
+  builder.push_str("  )\n");
+  builder.push_str("}\n");
+
+  Ok(builder)
+}
+
+fn convert_value(context: &Context, value: &Value, kind: &str, root_package: Option<&str>) -> String {
+  let kind = kind.trim_end_matches('?');
+  match value {
+    Value::Number(value) => match kind {
+      "f32" => format!("{}f", value),
+      "f64" => format!("{}.0", value),
+      _ => value.to_string()
+    },
+    Value::String(value) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "\\$")),
+    Value::Ident(value) if context.enum_types.contains(kind) => format!("{}.{}", convert_type(context, kind, root_package), value),
+    Value::Ident(value) => value.to_owned()
+  }
+}
+
+fn lowercase_first(value: &str) -> String {
+  let mut chars = value.chars();
+  match chars.next() {
+    Some(first) => first.to_lowercase().chain(chars).collect(),
+    None => String::new()
+  }
+}
+
+fn uppercase_first(value: &str) -> String {
+  let mut chars = value.chars();
+  match chars.next() {
+    Some(first) => first.to_uppercase().chain(chars).collect(),
+    None => String::new()
+  }
+}
+
+lazy_static! {
+  static ref REGEX_1: Regex = Regex::new(r"\bbool\b").unwrap();
+  static ref REGEX_2: Regex = Regex::new(r"\bi8\b").unwrap();
+  static ref REGEX_3: Regex = Regex::new(r"\bi16\b").unwrap();
+  static ref REGEX_4: Regex = Regex::new(r"\bi32\b").unwrap();
+  static ref REGEX_5: Regex = Regex::new(r"\bi64\b").unwrap();
+  static ref REGEX_6: Regex = Regex::new(r"\bf32\b").unwrap();
+  static ref REGEX_7: Regex = Regex::new(r"\bf64\b").unwrap();
+}
+
+pub fn convert_type(context: &Context, value: &str, root_package: Option<&str>) -> String {
+  let value = REGEX_1.replace_all(value, "Boolean");
+  let value = REGEX_2.replace_all(&value, "Byte");
+  let value = REGEX_3.replace_all(&value, "Short");
+  let value = REGEX_4.replace_all(&value, "Int");
+  let value = REGEX_5.replace_all(&value, "Long");
+  let value = REGEX_6.replace_all(&value, "Float");
+  let value = REGEX_7.replace_all(&value, "Double");
+
+  let qualify = |simple_name: &str, full_name: &str| {
+    let mut full_package = String::new();
+    if let Some(root_package) = context.root_package(simple_name, root_package) {
+      full_package.push_str(root_package);
+      full_package.push('.');
+    }
+    full_package.push_str(full_name);
+    full_package
+  };
+
+  let value = context.replace_names("builtin", &context.builtin_fqn, &value, |_, full_name| full_name.to_owned());
+  let value = context.replace_names("definition", &context.definition_fqn, &value, qualify);
+  context.replace_names("definition_2", &context.definition_fqn_2, &value, qualify)
+}
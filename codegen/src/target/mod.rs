@@ -0,0 +1,159 @@
+use std::collections::{BTreeMap, HashMap};
+
+use protolang_parser::hl::{Enum, Model, Template, Type};
+use protolang_parser::workspace::{SourceFile, Workspace};
+
+use crate::{Context, GenerateError, GeneratedFile, Options};
+
+pub mod kotlin;
+pub mod protolang;
+pub mod actionscript;
+pub mod template;
+
+/// Output language. [crate::generate] calls the definition hooks for every definition of a
+/// selected file in source order, then [Target::generate_file] for the file, and after all files
+/// [Target::generate_module] for every module in name order. Files are rendered in parallel, so
+/// the hooks only get a shared [Context]. Hooks that a target does not need produce nothing by
+/// default. A hook error skips the outputs of the whole file, see [crate::Generation].
+pub trait Target: Sync {
+  /// Name used to select the target, e.g. `kotlin`
+  fn name(&self) -> &'static str;
+
+  /// Simple name -> fully qualified name of types provided by the target runtime
+  fn builtin_fqn(&self) -> HashMap<String, String>;
+
+  /// Changes whenever the same definitions would be rendered differently, e.g. with the
+  /// templates of a [template::TemplateTarget]. Built-in targets only change with the generator
+  /// version, which the [crate::cache] already covers.
+  fn fingerprint(&self) -> String {
+    String::new()
+  }
+
+  /// Fills target specific lookup tables before anything is generated
+  fn prepare(&self, _context: &mut Context, _workspace: &Workspace) {}
+
+  /// Maps a protolang type reference (`List<i32>?`) to the target type
+  fn convert_type(&self, context: &Context, kind: &str, options: &Options) -> String;
+
+  fn generate_model(&self, _context: &Context, _file: &SourceFile, _model: &Model, _options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(Vec::new())
+  }
+
+  fn generate_type(&self, _context: &Context, _file: &SourceFile, _type_def: &Type, _options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(Vec::new())
+  }
+
+  fn generate_enum(&self, _context: &Context, _file: &SourceFile, _enum_def: &Enum, _options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(Vec::new())
+  }
+
+  fn generate_template(&self, _context: &Context, _file: &SourceFile, _template: &Template, _options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(Vec::new())
+  }
+
+  /// Called after all definitions of a file
+  fn generate_file(&self, _context: &Context, _file: &SourceFile, _options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(Vec::new())
+  }
+
+  /// Called once per module with its selected files
+  fn generate_module(&self, _context: &Context, _module: &str, _files: &[&SourceFile], _options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    Ok(Vec::new())
+  }
+}
+
+/// Targets by name
+#[derive(Default)]
+pub struct Registry {
+  targets: BTreeMap<&'static str, Box<dyn Target>>,
+}
+
+impl Registry {
+  /// Registry with the targets shipped with this crate
+  pub fn builtin() -> Registry {
+    let mut registry = Registry::default();
+    registry.register(kotlin::Kotlin);
+    registry.register(actionscript::Actionscript);
+    registry
+  }
+
+  /// Adds a target, replacing a target with the same name
+  pub fn register(&mut self, target: impl Target + 'static) {
+    self.targets.insert(target.name(), Box::new(target));
+  }
+
+  pub fn get(&self, name: &str) -> Option<&dyn Target> {
+    self.targets.get(name).map(|it| it.as_ref())
+  }
+
+  /// Sorted target names
+  pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+    self.targets.keys().copied()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use protolang_parser::hl::Definition;
+  use protolang_parser::workspace::{SourceFile, Workspace};
+
+  use super::*;
+  use crate::{generate, write_tree, Context, GenerateError, GeneratedFile, Options};
+
+  /// Lists enums per module
+  struct EnumIndex;
+
+  impl Target for EnumIndex {
+    fn name(&self) -> &'static str {
+      "enum-index"
+    }
+
+    fn builtin_fqn(&self) -> HashMap<String, String> {
+      HashMap::new()
+    }
+
+    fn convert_type(&self, _context: &Context, kind: &str, _options: &Options) -> String {
+      kind.to_owned()
+    }
+
+    fn generate_module(&self, context: &Context, module: &str, files: &[&SourceFile], _options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+      let mut names = files.iter()
+        .flat_map(|file| file.definitions.iter())
+        .filter_map(|it| match it {
+          Definition::Enum(enum_def) if context.enum_types.contains(&enum_def.name) => Some(enum_def.name.clone()),
+          _ => None
+        })
+        .collect::<Vec<_>>();
+      names.sort();
+      Ok(vec![GeneratedFile { path: PathBuf::from(format!("{}.txt", module)), contents: names.join("\n") }])
+    }
+  }
+
+  #[test]
+  fn registry() {
+    let mut registry = Registry::builtin();
+    assert_eq!(registry.names().collect::<Vec<_>>(), vec!["actionscript", "kotlin"]);
+    registry.register(EnumIndex);
+    assert!(registry.get("swift").is_none());
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("battle/module.yaml", ""),
+      ("battle/RegistryTestTeam.proto", "enum RegistryTestTeam : i32 {\n  RED = 0;\n}\n"),
+      ("battle/RegistryTestMode.proto", "enum RegistryTestMode : i32 {\n  DM = 0;\n}\n"),
+      ("Common.proto", "type RegistryTestType {\n  value: i32 = 1;\n}\n"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+
+    let files = generate(registry.get("enum-index").unwrap(), &workspace, &Options::default()).files;
+    assert_eq!(files, vec![
+      GeneratedFile { path: PathBuf::from("battle.txt"), contents: "RegistryTestMode\nRegistryTestTeam".to_owned() },
+      GeneratedFile { path: PathBuf::from("root.txt"), contents: String::new() },
+    ]);
+  }
+}
@@ -0,0 +1,388 @@
+//! Target rendered from a directory of [minijinja](https://docs.rs/minijinja) templates, for
+//! outputs that differ only a little from a built-in target and do not warrant a Rust fork.
+//!
+//! The directory contains a `target.yaml`:
+//! ```yaml
+//! # Simple name -> target type, used by the `convert_type` filter
+//! types:
+//!   i32: Int
+//!   string: String
+//!   List: List
+//! outputs:
+//!   - kind: model
+//!     template: model.kt.jinja
+//!     path: "{{ file.directory }}/{{ definition.name }}.kt"
+//!   # Codecs are just a second output of the same kind
+//!   - kind: type
+//!     template: codec.kt.jinja
+//!     path: "{{ file.directory }}/Codec{{ definition.name }}.kt"
+//! ```
+//!
+//! Both the templates and the `path` expressions get:
+//! - `kind`: `model`, `type`, `enum` or `template`
+//! - `definition`: the definition as printed by `dump --stage hl`
+//! - `meta`: meta values of the definition by key
+//! - `file`: `path`, `directory` (`/` separated), `name` without extension and `module` of the source file
+//! - `package`: `directory` with `.` separators, prefixed with `root_package`
+//! - `root_package`
+//! - `types`, `definitions` and `packages`: lookup tables behind `convert_type`
+//!
+//! Filters, in addition to the minijinja built-ins:
+//! - `convert_type`: maps a type reference (`List<TankModel>?`) using `types`, definitions are
+//!   replaced with their fully qualified name
+//! - `snake_case`, `camel_case`, `pascal_case`, `kebab_case`, `shouty_snake_case`
+//! - `doc_comment(indent=0)`: renders a list of comments as a `/** */` block
+//! - `line_comment(prefix="// ", indent=0)`: renders a list of comments as line comments
+
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+
+use heck::{ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use minijinja::{Environment, State, Value};
+use protolang_parser::hl::{Definition, Enum, Meta, Model, Template, Type};
+use protolang_parser::workspace::{SourceFile, Workspace};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::target::Target;
+use crate::{is_output_path, Context, GenerateError, GeneratedFile, Options};
+
+pub const TARGET_DESCRIPTOR: &str = "target.yaml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputKind {
+  Model,
+  Type,
+  Enum,
+  Template,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Output {
+  pub kind: OutputKind,
+  /// Relative to the template directory
+  pub template: String,
+  /// Template expression for the output path, relative to the output root
+  pub path: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TargetDescriptor {
+  #[serde(default)]
+  pub types: HashMap<String, String>,
+  #[serde(default)]
+  pub outputs: Vec<Output>,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+  Io {
+    path: PathBuf,
+    error: io::Error,
+  },
+  InvalidDescriptor {
+    path: PathBuf,
+    error: serde_yaml::Error,
+  },
+  /// Syntax error in a template or a path expression
+  Template(minijinja::Error),
+}
+
+impl Display for TemplateError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      TemplateError::Io { path, error } => write!(f, "{:?}: {}", path, error),
+      TemplateError::InvalidDescriptor { path, error } => write!(f, "{:?}: {}", path, error),
+      TemplateError::Template(error) => write!(f, "{:#}", error),
+    }
+  }
+}
+
+impl error::Error for TemplateError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      TemplateError::Io { error, .. } => Some(error),
+      TemplateError::InvalidDescriptor { error, .. } => Some(error),
+      TemplateError::Template(error) => Some(error),
+    }
+  }
+}
+
+pub struct TemplateTarget {
+  descriptor: TargetDescriptor,
+  environment: Environment<'static>,
+  /// Descriptor and template sources, see [Target::fingerprint]
+  fingerprint: String,
+}
+
+impl TemplateTarget {
+  /// Reads [TARGET_DESCRIPTOR] and every template it references from `dir`
+  pub fn load(dir: &Path) -> Result<TemplateTarget, TemplateError> {
+    let descriptor_path = dir.join(TARGET_DESCRIPTOR);
+    let descriptor = fs::read_to_string(&descriptor_path).map_err(|error| TemplateError::Io { path: descriptor_path.clone(), error })?;
+    let descriptor = serde_yaml::from_str(&descriptor).map_err(|error| TemplateError::InvalidDescriptor { path: descriptor_path, error })?;
+    TemplateTarget::new(descriptor, |name| {
+      let path = dir.join(name);
+      fs::read_to_string(&path).map_err(|error| TemplateError::Io { path, error })
+    })
+  }
+
+  /// Builds the target from a descriptor, `read` returns the source of a template by its name
+  pub fn new(descriptor: TargetDescriptor, mut read: impl FnMut(&str) -> Result<String, TemplateError>) -> Result<TemplateTarget, TemplateError> {
+    let mut environment = Environment::new();
+    environment.set_keep_trailing_newline(true);
+    environment.add_filter("convert_type", convert_type_filter);
+    environment.add_filter("snake_case", |value: &str| value.to_snake_case());
+    environment.add_filter("camel_case", |value: &str| value.to_lower_camel_case());
+    environment.add_filter("pascal_case", |value: &str| value.to_upper_camel_case());
+    environment.add_filter("kebab_case", |value: &str| value.to_kebab_case());
+    environment.add_filter("shouty_snake_case", |value: &str| value.to_shouty_snake_case());
+    environment.add_filter("doc_comment", doc_comment);
+    environment.add_filter("line_comment", line_comment);
+
+    let mut fingerprint = format!("{:?}\n", descriptor.types.iter().collect::<BTreeMap<_, _>>());
+    for (index, output) in descriptor.outputs.iter().enumerate() {
+      fingerprint.push_str(&format!("{:?}\n", output));
+      if environment.get_template(&output.template).is_err() {
+        debug!("loading template {}", output.template);
+        let source = read(&output.template)?;
+        fingerprint.push_str(&source);
+        environment.add_template_owned(output.template.clone(), source).map_err(TemplateError::Template)?;
+      }
+      environment.add_template_owned(path_template_name(index), output.path.clone()).map_err(TemplateError::Template)?;
+    }
+
+    Ok(TemplateTarget { descriptor, environment, fingerprint })
+  }
+
+  fn render<T: Serialize>(&self, context: &Context, file: &SourceFile, kind: OutputKind, definition: &T, meta: &[Meta], options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    let directory = file.path.parent().map(|it| it.to_string_lossy().replace(MAIN_SEPARATOR_STR, "/")).unwrap_or_default();
+    let mut package = options.root_package.clone().unwrap_or_default();
+    if !package.is_empty() && !directory.is_empty() {
+      package.push('.');
+    }
+    package.push_str(&directory.replace('/', "."));
+
+    let values = minijinja::context! {
+      kind,
+      definition,
+      meta => meta.iter().map(|it| (it.key.as_str(), it.value.as_str())).collect::<BTreeMap<_, _>>(),
+      file => minijinja::context! {
+        path => file.path.to_string_lossy().replace(MAIN_SEPARATOR_STR, "/"),
+        directory,
+        name => file.path.file_stem().unwrap().to_string_lossy(),
+        module => file.module,
+      },
+      package,
+      root_package => options.root_package,
+      types => context.builtin_fqn,
+      definitions => context.definition_fqn,
+      packages => context.definition_packages,
+    };
+
+    let mut files = Vec::new();
+    for (index, output) in self.descriptor.outputs.iter().enumerate().filter(|(_, it)| it.kind == kind) {
+      let path = self.render_template(&path_template_name(index), &values)?;
+      let contents = self.render_template(&output.template, &values)?;
+      let path = PathBuf::from(path.trim());
+      if !is_output_path(&path) {
+        return Err(GenerateError::InvalidPath { template: output.template.clone(), path });
+      }
+      info!("generate {} into {:?}", output.template, path);
+      files.push(GeneratedFile { path, contents });
+    }
+    Ok(files)
+  }
+
+  fn render_template(&self, name: &str, values: &Value) -> Result<String, GenerateError> {
+    self.environment.get_template(name)
+      .and_then(|template| template.render(values))
+      .map_err(|error| GenerateError::Render { name: name.to_owned(), error })
+  }
+}
+
+impl Target for TemplateTarget {
+  fn name(&self) -> &'static str {
+    "template"
+  }
+
+  fn builtin_fqn(&self) -> HashMap<String, String> {
+    self.descriptor.types.clone()
+  }
+
+  fn fingerprint(&self) -> String {
+    self.fingerprint.clone()
+  }
+
+  fn prepare(&self, context: &mut Context, workspace: &Workspace) {
+    // Unlike the Kotlin definition index, the plain definition paths without the `Base` suffix
+    for (file, definition) in workspace.definitions() {
+      let name = match definition {
+        Definition::Model(model) => &model.name,
+        Definition::Type(type_def) => &type_def.name,
+        Definition::Enum(enum_def) => &enum_def.name,
+        Definition::Template(template) => &template.name,
+        Definition::Meta(_) | Definition::Interface(_) => continue
+      };
+      context.definition_fqn.insert(name.to_owned(), file.dotted_path());
+    }
+  }
+
+  fn convert_type(&self, context: &Context, kind: &str, options: &Options) -> String {
+    convert_type(kind, |name| {
+      context.builtin_fqn.get(name).cloned()
+        .or_else(|| context.definition_fqn.get(name).map(|path| qualify(context.root_package(name, options.root_package.as_deref()), path)))
+    })
+  }
+
+  fn generate_model(&self, context: &Context, file: &SourceFile, model: &Model, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    self.render(context, file, OutputKind::Model, model, &model.meta, options)
+  }
+
+  fn generate_type(&self, context: &Context, file: &SourceFile, type_def: &Type, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    self.render(context, file, OutputKind::Type, type_def, &type_def.meta, options)
+  }
+
+  fn generate_enum(&self, context: &Context, file: &SourceFile, enum_def: &Enum, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    self.render(context, file, OutputKind::Enum, enum_def, &enum_def.meta, options)
+  }
+
+  fn generate_template(&self, context: &Context, file: &SourceFile, template: &Template, options: &Options) -> Result<Vec<GeneratedFile>, GenerateError> {
+    self.render(context, file, OutputKind::Template, template, &template.meta, options)
+  }
+}
+
+fn path_template_name(index: usize) -> String {
+  format!("outputs[{}].path", index)
+}
+
+fn qualify(root_package: Option<&str>, path: &str) -> String {
+  match root_package {
+    Some(root_package) => format!("{}.{}", root_package, path),
+    None => path.to_owned(),
+  }
+}
+
+/// Replaces every name in a type reference that `resolve` knows. For dotted names that are not
+/// known as a whole, such as `TankModel.Constructor`, only the first segment is replaced.
+fn convert_type(kind: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+  let mut result = String::new();
+  let mut name = String::new();
+  let flush = |name: &mut String, result: &mut String| {
+    if name.is_empty() {
+      return;
+    }
+
+    if let Some(resolved) = resolve(name) {
+      result.push_str(&resolved);
+    } else if let Some(resolved) = name.split_once('.').and_then(|(head, rest)| resolve(head).map(|it| format!("{}.{}", it, rest))) {
+      result.push_str(&resolved);
+    } else {
+      result.push_str(name);
+    }
+    name.clear();
+  };
+
+  for char in kind.chars() {
+    if char.is_alphanumeric() || char == '_' || char == '.' {
+      name.push(char);
+    } else {
+      flush(&mut name, &mut result);
+      result.push(char);
+    }
+  }
+  flush(&mut name, &mut result);
+  result
+}
+
+fn convert_type_filter(state: &State, kind: &str) -> String {
+  let types = state.lookup("types").unwrap_or_default();
+  let definitions = state.lookup("definitions").unwrap_or_default();
+  let packages = state.lookup("packages").unwrap_or_default();
+  let root_package = state.lookup("root_package").and_then(|it| it.as_str().map(ToOwned::to_owned));
+  let lookup = |table: &Value, name: &str| table.get_attr(name).ok().and_then(|it| it.as_str().map(ToOwned::to_owned));
+
+  convert_type(kind, |name| {
+    lookup(&types, name).or_else(|| {
+      let path = lookup(&definitions, name)?;
+      Some(qualify(lookup(&packages, name).as_deref().or(root_package.as_deref()), &path))
+    })
+  })
+}
+
+fn doc_comment(comments: Vec<String>, indent: Option<usize>) -> String {
+  if comments.is_empty() {
+    return String::new();
+  }
+
+  let indent = " ".repeat(indent.unwrap_or(0));
+  let mut builder = String::new();
+  builder.push_str(&format!("{}/**\n", indent));
+  for comment in &comments {
+    builder.push_str(&format!("{} * {}\n", indent, comment));
+  }
+  builder.push_str(&format!("{} */\n", indent));
+  builder
+}
+
+fn line_comment(comments: Vec<String>, prefix: Option<&str>, indent: Option<usize>) -> String {
+  let indent = " ".repeat(indent.unwrap_or(0));
+  let prefix = prefix.unwrap_or("// ");
+  comments.iter().map(|comment| format!("{}{}{}\n", indent, prefix, comment)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use protolang_parser::workspace::Workspace;
+
+  use super::*;
+  use crate::{generate, write_tree};
+
+  #[test]
+  fn template_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("battle/TemplateTestType.proto", "/// Tank state\ntype TemplateTestType {\n  tank_team: List<TemplateTestTeam>? = 1;\n}\n"),
+      ("lobby/TemplateTestTeam.proto", "enum TemplateTestTeam : i32 {\n  RED = 0;\n}\n"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+
+    let descriptor: TargetDescriptor = serde_yaml::from_str(r#"
+types:
+  List: MutableList
+outputs:
+  - kind: type
+    template: type.jinja
+    path: "{{ file.directory }}/{{ definition.name | snake_case }}.txt"
+"#).unwrap();
+    let target = TemplateTarget::new(descriptor, |name| {
+      assert_eq!(name, "type.jinja");
+      Ok("package {{ package }}\n{{ definition.comments | doc_comment }}{% for field in definition.fields %}{{ field.name | camel_case }}: {{ field.kind | convert_type }}\n{% endfor %}".to_owned())
+    }).unwrap();
+
+    let options = Options { root_package: Some("com.example".to_owned()), ..Options::default() };
+    let files = generate(&target, &workspace, &options).files;
+    assert_eq!(files, vec![GeneratedFile {
+      path: PathBuf::from("battle/template_test_type.txt"),
+      contents: "package com.example.battle\n/**\n * Tank state\n */\ntankTeam: MutableList<com.example.lobby.TemplateTestTeam>?\n".to_owned(),
+    }]);
+    let descriptor: TargetDescriptor = serde_yaml::from_str(r#"
+outputs:
+  - kind: type
+    template: type.jinja
+    path: "../{{ definition.name }}.txt"
+"#).unwrap();
+    let target = TemplateTarget::new(descriptor, |_| Ok(String::new())).unwrap();
+    let generation = generate(&target, &workspace, &options);
+    assert!(generation.files.is_empty());
+    assert!(matches!(&generation.errors[0].error, GenerateError::InvalidPath { path, .. } if path == std::path::Path::new("../TemplateTestType.txt")));
+  }
+}
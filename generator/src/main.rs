@@ -1,1562 +1,794 @@
-pub mod target;
-
-use std::collections::{HashMap, HashSet};
-use std::fmt::format;
-use std::{fs, i64, path::Path};
-use std::borrow::ToOwned;
-use std::ffi::OsStr;
-use std::ops::Index;
-use std::path::{Component, PathBuf, MAIN_SEPARATOR_STR};
-use std::sync::Mutex;
-use clap::{Parser, Subcommand};
-use itertools::Itertools;
-
-use lazy_static::lazy_static;
-use tracing::{debug, error, info, trace, warn};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+use std::time::Instant;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use tracing::{debug, error, info};
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
-use walkdir::WalkDir;
-use protolang_parser::{enum_to_definition, hl, model_to_definition, type_to_definition, ProgramItem, convert_meta, type_to_hl_codec, ENUM_TYPES};
-use regex::Regex;
-use once_cell::sync::Lazy;
-use protolang_parser::hl::{Meta, ModelConstructor, Type};
-use crate::target::actionscript::{convert_type, generate_enum_actionscript_code, generate_enum_codec_actionscript_code, generate_model_base_actionscript_code, generate_model_client_interface_actionscript_code, generate_model_server_actionscript_code, generate_type_actionscript_code, generate_type_codec_actionscript_code};
-use crate::target::kotlin::{generate_enum_kotlin_code, generate_model_kotlin_code, generate_type_kotlin_code};
-use crate::target::protolang::{generate_protolang_code, generate_protolang_code_enum, generate_protolang_code_type};
-
-fn generate_kotlin(root_package: Option<&str>, module: Option<&str>, input_root: &Path, output_root: &Path) {
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
-    }
+use protolang_codegen::config::{ProjectConfig, CONFIG_FILE};
+use protolang_codegen::import::{import_actionscript, MODEL_FILES};
+use protolang_codegen::plugin::{run_plugin, PluginError};
+use protolang_codegen::target::template::TemplateTarget;
+use protolang_codegen::cache::CACHE_FILE;
+use protolang_codegen::manifest::{Manifest, MANIFEST_FILE};
+use protolang_codegen::{generate, generate_incremental, Cache, GeneratedFile, Options, Registry, Target};
+use protolang_parser::span::Positioned;
+use protolang_parser::{Program, Token};
+use protolang_parser::check::{lint, unresolved_references};
+use protolang_parser::diagnostic::Diagnostic;
+use protolang_parser::discovery::Discovery;
+use protolang_parser::hl::Definition;
+use protolang_parser::workspace::{discover_sources, Workspace, WorkspaceError, WorkspaceRoot};
+use serde::Serialize;
+use similar::TextDiff;
+
+use crate::report::{MessageFormat, Reporter, Summary};
+use crate::watch::Watcher;
+
+mod report;
+mod watch;
 
-    if !entry.file_type().is_file() {
-      continue;
-    }
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Args {
+  #[command(subcommand)]
+  command: Actions,
 
-    if !path.extension().is_some_and(|it| it == "proto") {
-      continue;
-    }
+  /// Format of the reported diagnostics, logs always go to stderr
+  #[arg(long, value_enum, global = true, default_value_t = MessageFormat::Human)]
+  message_format: MessageFormat,
 
-    let file_module = get_path_module(input_root, relative_path);
-    debug!("Module: {:?}", file_module);
-    let (file_module, module_root) = match file_module {
-      Some(module) => module,
-      None => {
-        error!("File {:?} is not attached to any module", path);
-        todo!();
-      }
-    };
+  /// Number of threads that parse and render files, the number of CPUs by default
+  #[arg(short, long, global = true)]
+  jobs: Option<NonZeroUsize>,
+}
 
-    if let Some(expected_module) = module {
-      let expected_modules = expected_module.split(',').collect_vec();
-      if !expected_modules.contains(&file_module.as_str()) {
-        continue;
-      }
-    }
+#[derive(Subcommand, Debug)]
+enum Actions {
+  /// Import the models of existing ActionScript sources into .proto schema files
+  GenerateProtolang {
+    /// ActionScript source root
+    input: PathBuf,
 
-    info!("Parsing {:?}...", path);
-    let content = fs::read_to_string(path).unwrap();
+    #[arg(short, long)]
+    output: PathBuf,
 
-    let tokens = protolang_parser::tokenizer(&content).unwrap();
-    for token in &tokens {
-      trace!("{:?}", token);
-    }
+    /// Files scanned for model classes, `*Model*.as` by default
+    #[arg(long)]
+    models: Vec<String>,
 
-    let mut iter = itertools::multipeek(&tokens);
-    let ast = protolang_parser::parse_program(&mut iter).unwrap();
-    debug!("{:?}", ast);
-
-    let mut meta = Vec::new();
-    for item in &ast.body {
-      match item {
-        ProgramItem::Meta(item) => meta.push(Meta {
-          key: item.key.value.0.to_owned(),
-          value: item.key.value.0.to_owned(),
-        }),
-        _ => continue
-      };
-    }
+    #[command(flatten)]
+    filters: Filters,
+  },
+  /// Generate sources for one or more registered targets
+  Generate {
+    /// Schema root, the configured `inputs` by default
+    input: Option<PathBuf>,
 
-    for item in &ast.body {
-      let code = match item {
-        ProgramItem::Model(model) => {
-          let definition = model_to_definition(model).unwrap();
-          debug!("{:?}", definition);
+    /// Additional schema root, all roots are loaded into one namespace
+    #[arg(short = 'i', long = "input")]
+    inputs: Vec<PathBuf>,
 
-          generate_model_kotlin_code(&definition, root_package)
-        }
-        ProgramItem::Type(type_def) => {
-          let definition = type_to_definition(type_def).unwrap();
-          debug!("{:?}", definition);
+    /// Schema root whose definitions can be referenced, but are not generated. The configured
+    /// `read_only_inputs` by default.
+    #[arg(long = "read-only-input")]
+    read_only_inputs: Vec<PathBuf>,
 
-          generate_type_kotlin_code(&definition, root_package)
-        }
-        ProgramItem::Enum(enum_def) => {
-          let definition = enum_to_definition(enum_def).unwrap();
-          debug!("{:?}", definition);
+    /// Output root of every target, overrides the configured outputs
+    #[arg(short, long)]
+    output: Option<PathBuf>,
 
-          generate_enum_kotlin_code(&definition, root_package)
-        }
-        _ => continue
-      };
+    /// Comma separated target names, e.g. `kotlin,actionscript`, the configured targets by default
+    #[arg(short, long, value_delimiter = ',')]
+    target: Vec<String>,
 
-      // let relative_path = relative_path.strip_prefix(&module_root).unwrap();
-      let relative_path = relative_path.with_file_name(relative_path.file_name().unwrap().to_string_lossy().replace(".proto", ".generated.kt"));
-      let output_path = output_root.join(&relative_path);
-      let package = relative_path.parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".");
-      info!("generate kotlin code into {:?}", output_path);
+    /// Template directory with a `target.yaml`, see `protolang_codegen::target::template`
+    #[arg(long)]
+    templates: Vec<PathBuf>,
 
-      let mut full_package = String::new();
-      if let Some(root_package) = root_package {
-        full_package.push_str(root_package);
-        full_package.push_str(".");
-      }
-      full_package.push_str(&package);
-
-      let mut wrapped_code = String::new();
-      wrapped_code.push_str(&format!("package {}\n\n", full_package));
-      wrapped_code.push_str("import jp.assasans.araumi.models.*\n");
-      wrapped_code.push_str("import jp.assasans.araumi.protocol.codec.wired.*\n");
-      wrapped_code.push_str("import jp.assasans.araumi.architecture.spaces.*\n");
-      wrapped_code.push_str("\n");
-      wrapped_code.push_str(&code);
-      debug!("{}", wrapped_code);
-
-      fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-      fs::write(output_path, wrapped_code).unwrap();
-    }
-  }
-}
+    /// External generator executable, see `protolang_codegen::plugin` for the protocol
+    #[arg(long)]
+    plugin: Vec<PathBuf>,
 
-fn generate_actionscript(root_package: Option<&str>, module: Option<&str>, input_root: &Path, output_root: &Path) {
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
-    }
+    #[arg(long)]
+    package: Option<String>,
 
-    if !entry.file_type().is_file() {
-      continue;
-    }
+    /// Module to generate sources for
+    #[arg(long)]
+    module: Option<String>,
 
-    if !path.extension().is_some_and(|it| it == "proto") {
-      continue;
-    }
+    /// Also generate the modules that `--module` depends on
+    #[arg(long, requires = "module")]
+    with_dependencies: bool,
 
-    let file_module = get_path_module(input_root, relative_path);
-    debug!("Module: {:?}", file_module);
-    let (file_module, module_root) = match file_module {
-      Some(module) => module,
-      None => {
-        error!("File {:?} is not attached to any module", path);
-        todo!();
-      }
-    };
-
-    if let Some(expected_module) = module {
-      let expected_modules = expected_module.split(',').collect_vec();
-      if !expected_modules.contains(&file_module.as_str()) {
-        continue;
-      }
-    }
+    /// Compare the outputs with the files on disk instead of writing them, and exit with 1 if any
+    /// of them is missing or different
+    #[arg(long)]
+    check: bool,
 
-    info!("Parsing {:?}...", path);
-    let content = fs::read_to_string(path).unwrap();
+    /// Same as `--check`, and print a unified diff of every file that would change to stderr
+    #[arg(long)]
+    diff: bool,
 
-    let tokens = protolang_parser::tokenizer(&content).unwrap();
-    for token in &tokens {
-      trace!("{:?}", token);
-    }
+    /// Render every file, instead of only the ones whose inputs changed since the last run, see
+    /// `protolang_codegen::cache`
+    #[arg(long)]
+    no_cache: bool,
 
-    let mut iter = itertools::multipeek(&tokens);
-    let ast = protolang_parser::parse_program(&mut iter).unwrap();
-    debug!("{:?}", ast);
-
-    let mut meta = Vec::new();
-    for item in &ast.body {
-      match item {
-        ProgramItem::Meta(item) => meta.push(Meta {
-          key: item.key.value.0.to_owned(),
-          value: item.key.value.0.to_owned(),
-        }),
-        _ => continue
-      };
-    }
+    /// Delete the outputs of previous runs whose source was deleted or no longer produces them,
+    /// see `protolang_codegen::manifest`. With `--check`, report them instead.
+    #[arg(long)]
+    prune: bool,
 
-    for item in &ast.body {
-      if let ProgramItem::Model(model) = &item {
-        let definition = model_to_definition(model).unwrap();
-        debug!("{:?}", definition);
-
-        'ctor: {
-          if let Some(constructor) = definition.constructor.as_ref() {
-            debug!("shitman {:?}", definition.name);
-            let type_def = convert_constructor_to_type(constructor.to_owned());
-            let client_package = if let Some(meta) = type_def.meta.iter().find(|it| it.key == "client_package") {
-              &meta.value
-            } else {
-              todo!()
-            };
-
-            let class_name = if let Some(meta) = type_def.meta.iter().find(|it| it.key == "client_name") {
-              &meta.value
-            } else {
-              &type_def.name
-            };
-
-            if EXISTING_TYPES.lock().unwrap().contains(class_name) {
-              debug!("skip {} ({}) due to already existing", class_name, definition.name);
-              break 'ctor;
-            }
-            debug!("add {} ({}) to existing types", class_name, definition.name);
-            EXISTING_TYPES.lock().unwrap().insert(class_name.to_owned());
-
-            // type
-            {
-              let code = generate_type_actionscript_code(&type_def, root_package);
-              let package = client_package.replace('.', "/");
-              let relative_path = format!("{}/{}.as", package, class_name);
-              let output_path = output_root.join(&relative_path);
-              info!("generate actionscript code into {:?}", output_path);
-
-              let mut full_package = String::new();
-              if let Some(root_package) = root_package {
-                full_package.push_str(root_package);
-                full_package.push_str(".");
-              }
-              full_package.push_str(&package);
-
-              let mut wrapped_code = String::new();
-              wrapped_code.push_str(&code);
-              debug!("{}", wrapped_code);
-
-              fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-              fs::write(output_path, wrapped_code).unwrap();
-            }
-
-            // type codec
-            {
-              let code = generate_type_codec_actionscript_code(&type_def, root_package);
-              let package = client_package.replace('.', "/");
-              let relative_path = format!("_codec/{}/Codec{}.as", package, class_name);
-              let output_path = output_root.join(&relative_path);
-              info!("generate actionscript code into {:?}", output_path);
-
-              let mut wrapped_code = String::new();
-              wrapped_code.push_str(&code);
-              debug!("{}", wrapped_code);
-
-              fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-              fs::write(output_path, wrapped_code).unwrap();
-            }
-          }
-        }
+    /// Keep running and regenerate whenever a schema file or `module.yaml` in the inputs changes
+    #[arg(long, conflicts_with_all = ["check", "diff"])]
+    watch: bool,
 
-        {
-          let code = generate_model_server_actionscript_code(&definition, root_package);
+    /// Project configuration, `protolang.toml` in the input directory or its parents by default
+    #[arg(long)]
+    config: Option<PathBuf>,
 
-          let relative_path = relative_path.with_file_name(relative_path.file_name().unwrap().to_string_lossy().replace(".proto", "Server.as"));
-          let output_path = output_root.join(&relative_path);
-          let package = relative_path.parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".");
-          info!("generate actionscript code into {:?}", output_path);
+    #[command(flatten)]
+    filters: Filters,
+  },
+  /// Same as `generate --target kotlin`
+  GenerateKotlin {
+    input: PathBuf,
 
-          let mut full_package = String::new();
-          if let Some(root_package) = root_package {
-            full_package.push_str(root_package);
-            full_package.push_str(".");
-          }
-          full_package.push_str(&package);
+    #[arg(short, long)]
+    output: PathBuf,
 
-          let mut wrapped_code = String::new();
-          wrapped_code.push_str(&code);
-          debug!("{}", wrapped_code);
+    #[arg(long)]
+    package: Option<String>,
 
-          fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-          fs::write(output_path, wrapped_code).unwrap();
-        }
+    /// Module to generate sources for
+    #[arg(long)]
+    module: Option<String>,
 
-        {
-          let code = generate_model_base_actionscript_code(&definition, root_package);
+    /// Only render the files whose inputs changed since the last run, see
+    /// `protolang_codegen::cache`. The cache is stored in the output directory.
+    #[arg(long)]
+    cache: bool,
 
-          let relative_path = relative_path.with_file_name(relative_path.file_name().unwrap().to_string_lossy().replace(".proto", "Base.as"));
-          let output_path = output_root.join(&relative_path);
-          let package = relative_path.parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".");
-          info!("generate actionscript code into {:?}", output_path);
+    #[command(flatten)]
+    filters: Filters,
+  },
+  /// Same as `generate --target actionscript`
+  GenerateActionscript {
+    input: PathBuf,
 
-          let mut full_package = String::new();
-          if let Some(root_package) = root_package {
-            full_package.push_str(root_package);
-            full_package.push_str(".");
-          }
-          full_package.push_str(&package);
+    #[arg(short, long)]
+    output: PathBuf,
 
-          let mut wrapped_code = String::new();
-          wrapped_code.push_str(&code);
-          debug!("{}", wrapped_code);
+    #[arg(long)]
+    package: Option<String>,
 
-          fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-          fs::write(output_path, wrapped_code).unwrap();
-        }
+    /// Module to generate sources for
+    #[arg(long)]
+    module: Option<String>,
 
-        {
-          let code = generate_model_client_interface_actionscript_code(&definition, root_package);
+    /// Only render the files whose inputs changed since the last run, see
+    /// `protolang_codegen::cache`. The cache is stored in the output directory.
+    #[arg(long)]
+    cache: bool,
 
-          let relative_path = relative_path.with_file_name("I".to_owned() + &*relative_path.file_name().unwrap().to_string_lossy().replace(".proto", "Base.as"));
-          let output_path = output_root.join(&relative_path);
-          let package = relative_path.parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".");
-          info!("generate actionscript code into {:?}", output_path);
+    #[command(flatten)]
+    filters: Filters,
+  },
+  /// Parse, resolve and validate the schema without generating anything. Exits with 0 if there
+  /// are no diagnostics, 1 if there are only warnings and 2 if there are errors.
+  Check {
+    /// Schema root, the configured `inputs` by default
+    input: Option<PathBuf>,
+
+    /// Additional schema root, all roots are loaded into one namespace
+    #[arg(short = 'i', long = "input")]
+    inputs: Vec<PathBuf>,
+
+    /// Schema root whose definitions can be referenced, but are not checked. The configured
+    /// `read_only_inputs` by default.
+    #[arg(long = "read-only-input")]
+    read_only_inputs: Vec<PathBuf>,
+
+    /// Comma separated target names whose builtin types resolve, the configured targets or all
+    /// registered targets by default
+    #[arg(short, long, value_delimiter = ',')]
+    target: Vec<String>,
+
+    /// Comma separated lints to run in addition to the configured `lints`
+    #[arg(long, value_delimiter = ',')]
+    lint: Vec<String>,
+
+    /// Project configuration, `protolang.toml` in the input directory or its parents by default
+    #[arg(long)]
+    config: Option<PathBuf>,
 
-          let mut full_package = String::new();
-          if let Some(root_package) = root_package {
-            full_package.push_str(root_package);
-            full_package.push_str(".");
-          }
-          full_package.push_str(&package);
+    #[command(flatten)]
+    filters: Filters,
+  },
+  /// Reformat .proto files in place
+  Fmt {
+    /// Files or directories to format
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
 
-          let mut wrapped_code = String::new();
-          wrapped_code.push_str(&code);
-          debug!("{}", wrapped_code);
+    /// Do not write anything, exit with an error if any file is not formatted
+    #[arg(long)]
+    check: bool,
 
-          fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-          fs::write(output_path, wrapped_code).unwrap();
-        }
-      } else {
-        let (client_package, client_name, code) = match item {
-          ProgramItem::Type(type_def) => {
-            let definition = type_to_definition(type_def).unwrap();
-            debug!("{:?}", definition);
-
-            let client_package = if let Some(meta) = type_def.meta.iter().find(|it| it.key.value.0 == "client_package") {
-              meta.value.value.0.to_owned()
-            } else {
-              todo!()
-            };
-            let class_name = if let Some(meta) = type_def.meta.iter().find(|it| it.key.value.0 == "client_name") {
-              meta.value.value.0.to_owned()
-            } else {
-              todo!()
-            };
-
-            (client_package, class_name, generate_type_actionscript_code(&definition, root_package))
-          }
-          ProgramItem::Enum(enum_def) => {
-            let definition = enum_to_definition(enum_def).unwrap();
-            debug!("{:?}", definition);
-
-            let client_package = if let Some(meta) = enum_def.meta.iter().find(|it| it.key.value.0 == "client_package") {
-              meta.value.value.0.to_owned()
-            } else {
-              todo!()
-            };
-            let class_name = if let Some(meta) = enum_def.meta.iter().find(|it| it.key.value.0 == "client_name") {
-              meta.value.value.0.to_owned()
-            } else {
-              todo!()
-            };
-
-            (client_package, class_name, generate_enum_actionscript_code(&definition, root_package))
-          }
-          _ => continue
-        };
-
-        // let relative_path = relative_path.strip_prefix(&module_root).unwrap();
-        let relative_path = relative_path.with_file_name(relative_path.file_name().unwrap().to_string_lossy().replace(".proto", ".as"));
-        let output_path = output_root.join(&relative_path);
-        let package = relative_path.parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".");
-        info!("generate type actionscript code into {:?}", output_path);
-
-        let mut full_package = String::new();
-        if let Some(root_package) = root_package {
-          full_package.push_str(root_package);
-          full_package.push_str(".");
-        }
-        full_package.push_str(&package);
-
-        let mut wrapped_code = String::new();
-        wrapped_code.push_str(&code);
-        debug!("{}", wrapped_code);
-
-        fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-        fs::write(output_path, wrapped_code).unwrap();
-
-        // type codec
-        {
-          let code = match item {
-            ProgramItem::Type(type_def) => {
-              let definition = type_to_definition(type_def).unwrap();
-              debug!("{:?}", definition);
-
-              generate_type_codec_actionscript_code(&definition, root_package)
-            }
-            ProgramItem::Enum(enum_def) => {
-              let definition = enum_to_definition(enum_def).unwrap();
-              debug!("{:?}", definition);
-
-              generate_enum_codec_actionscript_code(&definition, root_package)
-            }
-            _ => continue
-          };
-
-          let package = client_package.replace('.', "/");
-          let relative_path = format!("_codec/{}/Codec{}.as", package, client_name);
-          let output_path = output_root.join(&relative_path);
-          info!("generate type codec actionscript code into {:?}", output_path);
-
-          let mut wrapped_code = String::new();
-          wrapped_code.push_str(&code);
-          debug!("{}", wrapped_code);
-
-          fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-          fs::write(output_path, wrapped_code).unwrap();
-        }
-      }
-    }
-  }
-}
+    #[command(flatten)]
+    filters: Filters,
+  },
+  /// Print parsed schema files as JSON or YAML
+  Dump {
+    /// File or directory to dump
+    input: PathBuf,
 
-pub fn convert_constructor_to_type(constructor: ModelConstructor) -> Type {
-  let name = if let Some(meta) = constructor.meta.iter().find(|it| it.key == "client_name") {
-    &meta.value
-  } else {
-    todo!()
-  };
+    #[arg(long, value_enum, default_value_t = DumpStage::Ast)]
+    stage: DumpStage,
 
-  Type {
-    name: name.to_owned(),
-    fields: constructor.fields,
-    meta: constructor.meta,
-    comments: constructor.comments,
-  }
+    #[arg(long, value_enum, default_value_t = DumpFormat::Json)]
+    format: DumpFormat,
+  },
 }
 
-#[derive(Debug)]
-struct ParsedField {
-  pub name: String,
-  pub codec: String,
-  pub kind: String,
+/// Input discovery overrides, added after the configured globs, see `protolang_parser::discovery`
+#[derive(clap::Args, Debug)]
+struct Filters {
+  /// Only read files matching this gitignore-style glob, relative to the input
+  #[arg(long)]
+  include: Vec<String>,
+
+  /// Skip files and directories matching this gitignore-style glob, relative to the input.
+  /// `!excluded/` reads the `excluded/` directories that are skipped by default.
+  #[arg(long)]
+  exclude: Vec<String>,
 }
 
-#[derive(Debug)]
-struct ParsedVariant {
-  pub name: String,
-  pub value: i64,
-}
+impl Filters {
+  fn discovery(&self, extension: &str) -> Discovery {
+    Discovery::new(extension).include(self.include.clone()).exclude(self.exclude.clone())
+  }
 
-#[derive(Debug)]
-struct ParsedMethod {
-  pub name: String,
-  pub id: i64,
-  pub params: Vec<ParsedMethodParam>,
+  fn root(&self, config: &ProjectConfig, path: &Path) -> WorkspaceRoot {
+    WorkspaceRoot::new(path)
+      .include(config.include.iter().chain(&self.include).cloned().collect())
+      .exclude(config.exclude.iter().chain(&self.exclude).cloned().collect())
+  }
 }
 
-#[derive(Debug)]
-struct ParsedMethodParam {
-  pub name: String,
-  pub codec: String,
-  pub kind: String,
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum DumpStage {
+  /// Token stream with spans
+  Tokens,
+  /// Syntax tree with spans
+  Ast,
+  /// Resolved definitions, as passed to the code generators
+  Hl,
 }
 
-// name -> source root
-pub static MODULES: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
-pub static EXISTING_TYPES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
-pub static MODEL_TYPES: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
-// file name -> path
-pub static BUILTIN_FQN: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-pub static DEFINITION_FQN: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-pub static DEFINITION_FQN_2: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-pub static REGEX_CACHE: Lazy<Mutex<HashMap<String, Regex>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
-fn generate_definition_index(input_root: &Path) {
-  info!("generating definition index...");
-
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
-    }
-
-    if !entry.file_type().is_file() {
-      continue;
-    }
-
-    if !path.extension().is_some_and(|it| it == "proto") {
-      continue;
-    }
-
-    debug!("Parsing {:?}...", path);
-    let content = fs::read_to_string(path).unwrap();
-
-    let tokens = protolang_parser::tokenizer(&content).unwrap();
-    let mut iter = itertools::multipeek(&tokens);
-    let ast = protolang_parser::parse_program(&mut iter).unwrap();
-
-    let relative_path = relative_path.to_string_lossy().replace(".proto", "").replace(MAIN_SEPARATOR_STR, ".");
-
-    for item in &ast.body {
-      let relative_path = relative_path.clone();
-      let (simple_name, relative_path) = match item {
-        ProgramItem::Model(model) => {
-          let definition = model_to_definition(model).unwrap();
-          (definition.name, format!("{}Base", relative_path))
-        }
-        ProgramItem::Type(type_def) => {
-          let definition = type_to_definition(type_def).unwrap();
-          (definition.name, relative_path)
-        }
-        ProgramItem::Enum(enum_def) => {
-          let definition = enum_to_definition(enum_def).unwrap();
-          ENUM_TYPES.lock().unwrap().insert(definition.name.clone());
-          (definition.name, relative_path)
-        }
-        _ => continue
-      };
-
-      debug!("registered definition {} -> {}", simple_name, relative_path);
-      DEFINITION_FQN_2.lock().unwrap().insert(simple_name, relative_path);
-    }
-  }
-
-  info!("definition index generated");
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum DumpFormat {
+  Json,
+  Yaml,
 }
 
-lazy_static! {
-  static ref MODEL_CLASS: Regex = Regex::new(r"class (\w+) extends (\w+) implements (\w+)").unwrap();
-  static ref CONSTRUCTOR_REGEX: Regex = Regex::new(r"registerModelConstructorCodec.+\((.+?),\s*false\)\)\);").unwrap();
-
-  static ref MODEL_ID_REGEX: Regex = Regex::new(r"this.modelId = Long.getLong\((?<high>-?(0x)?[0-9a-f]+),(?<low>-?(0x)?[0-9a-f]+)\)").unwrap();
-  static ref MODEL_CONSTRUCTOR_REGEX: Regex = Regex::new(r"registerModelConstructorCodec\(this.modelId,this._protocol.getCodec\((?<codec>.+)\)\)").unwrap();
-  static ref MODEL_METHOD_REGEX: Regex = Regex::new(r"this._(?<method>[A-Za-z0-9_]+)Id = Long.getLong\((?<high>-?(0x)?[0-9a-f]+),(?<low>-?(0x)?[0-9a-f]+)\)").unwrap();
-  static ref MODEL_CLIENT_METHOD_PARAM_REGEX: Regex = Regex::new(r"this._(?<method>[A-Za-z0-9_]+)_(?<param>[A-Za-z0-9_]+)Codec = this._protocol.getCodec\((?<codec>.+)\)").unwrap();
-  static ref MODEL_SERVER_METHOD_PARAM_REGEX: Regex = Regex::new(r"this._(?<method>[A-Za-z0-9_]+)_(?<param>[A-Za-z0-9_]+)Codec = this.protocol.getCodec\((?<codec>.+)\)").unwrap();
-
-  static ref FIELD_REGEX: Regex = Regex::new(r"this.codec_(?<field>[A-Za-z0-9_]+) = param1.getCodec\((?<codec>.+)\)").unwrap();
-  static ref VARIANT_REGEX: Regex = Regex::new(r"case (?<value>\d+):\s*.+\.(?<variant>\w+);").unwrap();
+#[derive(Serialize)]
+#[serde(untagged)]
+enum DumpOutput<'a> {
+  Tokens(Vec<Positioned<Token>>),
+  Ast(&'a Program),
+  Hl(&'a [Definition]),
 }
 
-fn generate_model_index(input_root: &Path) {
-  info!("generating model index...");
-
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
-    }
-
-    if !entry.file_type().is_file() {
-      continue;
-    }
-
-    if !path.extension().is_some_and(|it| it == "as") {
-      continue;
-    }
-
-    if path.to_string_lossy().contains("excluded/") {
-      continue;
-    }
-
-    let file_name = path.file_name().unwrap().to_string_lossy();
-    if !file_name.contains("Model") {
-      continue;
-    }
-
-    // debug!("Parsing {:?}...", path);
-    let content = fs::read_to_string(path).unwrap();
-    if !content.contains("[ModelInfo]") {
-      continue;
-    }
-
-    let captures = MODEL_CLASS.captures(&content).expect("no model class capture");
-    let model_name = captures.get(1).expect("no model name").as_str();
-    let model_base_name = captures.get(2).expect("no model base name").as_str();
-
-    if model_name != model_base_name.replace("ModelBase", "Model") {
-      // debug!("{:?} {:?}", model_name, model_base_name.replace("ModelBase", "Model"));
-    }
-
-    let class_import = Regex::new(&format!(r"import ([\w.]+\.{})", model_base_name));
-    let captures = class_import.unwrap().captures(&content).expect("no class import capture");
-    let model_base_import_fqdn = captures.get(1).expect("no import path").as_str();
-    let model_base_import_path = model_base_import_fqdn.replace('.', "/") + ".as";
-    let model_base_import_path = Path::new(&model_base_import_path);
-    // debug!("{:?} {:?}", model_base_import_fqdn, model_base_import_path);
-
-    let mut sources_root = path;
-    while let Some(parent) = sources_root.parent() {
-      sources_root = parent;
-      if parent.file_name().unwrap().to_string_lossy() == "src" {
-        break;
-      }
-    }
-    // debug!("{:?}", sources_root);
-
-    let model_base_path = sources_root.join(model_base_import_path);
-    // debug!("{:?}", model_base_path);
-    let (model_base_path, model_base_contents) = match fs::read_to_string(&model_base_path) {
-      Ok(contents) => (model_base_path, contents),
-      Err(error) => {
-        // debug!("failed to read model base file (trying entrance) {:?}: {:?}", model_base_path, error);
-
-        // Try "entrance"
-        let model_base_path = model_base_path.components().map(|it| if it.as_os_str().to_string_lossy() == "game" { Component::Normal(OsStr::new("entrance")) } else { it }).collect::<PathBuf>();
-        // debug!("{:?}", model_base_path);
-        match fs::read_to_string(&model_base_path) {
-          Ok(contents) => (model_base_path, contents),
-          Err(error) => {
-            error!("failed to read model base file {:?}: {:?}", model_base_path, error);
-            todo!();
-          }
+/// Output of every workspace file up to the given stage, keyed by the path relative to the root.
+fn dump<'a>(workspace: &'a Workspace, stage: DumpStage, reporter: &mut Reporter) -> BTreeMap<String, DumpOutput<'a>> {
+  let mut files = BTreeMap::new();
+  for file in &workspace.files {
+    let output = match stage {
+      DumpStage::Tokens => match protolang_parser::tokenizer(&file.content) {
+        Ok(tokens) => DumpOutput::Tokens(tokens),
+        Err(error) => {
+          reporter.report(Diagnostic::from(&WorkspaceError::Syntax { path: workspace.path(file), error: error.locate(&file.content) }));
+          continue;
         }
-      }
+      },
+      DumpStage::Ast => DumpOutput::Ast(&file.ast),
+      DumpStage::Hl => DumpOutput::Hl(&file.definitions),
     };
-    // debug!("{}", model_base_contents);
-
-    if !model_base_contents.contains("registerModelConstructorCodec") {
-      continue;
-    }
-
-    // debug!("{}", content);
-
-    let captures = CONSTRUCTOR_REGEX.captures(&model_base_contents).expect("no model constructor capture");
-    let constructor_name = captures.get(1).unwrap().as_str();
-
-    let model_name = model_base_name.replace("ModelBase", "Model");
-
-    MODEL_TYPES.lock().unwrap().insert(constructor_name.to_owned(), model_name.clone());
-
-    // Do not generate definition files
-    // EXISTING_TYPES.lock().unwrap().insert(constructor_name.to_owned());
-    EXISTING_TYPES.lock().unwrap().insert(format!("{}.Constructor", model_name));
-
-    info!("registered {}", format!("{}.Constructor", model_name.clone()));
+    files.insert(file.path.to_string_lossy().replace(MAIN_SEPARATOR_STR, "/"), output);
   }
-
-  info!("model index generated");
+  files
 }
 
-fn generate_constructor_index(input_root: &Path) {
-  info!("generating constructor index...");
-
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
-    }
-
-    if !entry.file_type().is_file() {
-      continue;
-    }
-
-    if !path.extension().is_some_and(|it| it == "proto") {
-      continue;
-    }
-
-    debug!("Parsing {:?}...", path);
-    let content = fs::read_to_string(path).unwrap();
-
-    let tokens = protolang_parser::tokenizer(&content).unwrap();
-    let mut iter = itertools::multipeek(&tokens);
-    let ast = protolang_parser::parse_program(&mut iter).unwrap();
-
-    let relative_path = relative_path.to_string_lossy().replace(".proto", "").replace(MAIN_SEPARATOR_STR, ".");
-
-    for item in &ast.body {
-      let relative_path = relative_path.clone();
-      match item {
-        ProgramItem::Model(model) => {
-          let definition = model_to_definition(model).unwrap();
-          if let Some(constructor) = &definition.constructor {
-            let constructor_package_name = if let Some(meta) = constructor.meta.iter().find(|it| it.key == "client_package") {
-              &meta.value
-            } else {
-              todo!()
-            };
-            let constructor_class_name = if let Some(meta) = constructor.meta.iter().find(|it| it.key == "client_name") {
-              &meta.value
-            } else {
-              todo!()
-            };
-
-            let value = format!("{}.{}", constructor_package_name, constructor_class_name.clone());
-            debug!("registered {} -> {}", format!("{}Base.Constructor", definition.name), constructor_class_name);
-            DEFINITION_FQN.lock().unwrap().insert(format!("{}.Constructor", definition.name), constructor_class_name.clone());
-            DEFINITION_FQN.lock().unwrap().insert(format!("{}Base.Constructor", definition.name), constructor_class_name.clone());
-            debug!("registered level 2 {} -> {}", constructor_class_name, value);
-            DEFINITION_FQN_2.lock().unwrap().insert(constructor_class_name.clone(), value);
-          }
+/// Reports the files that could not be parsed, or in check mode, would be changed
+fn format_files(paths: &[PathBuf], check: bool, filters: &Filters, reporter: &mut Reporter) {
+  for root in paths {
+    // Explicitly passed files are formatted regardless of the extension
+    let files = if root.is_dir() {
+      match discover_sources(root, filters.discovery("proto")) {
+        Ok(files) => files.into_iter().map(|it| root.join(it)).collect(),
+        Err(error) => {
+          reporter.report(Diagnostic::from(&error));
+          continue;
         }
-        _ => continue
-      };
-    }
-  }
-
-  info!("constructor index generated");
-}
-
-fn generate_protolang_model(input_root: &Path, output_root: &Path) {
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
-    }
-
-    if !entry.file_type().is_file() {
-      continue;
-    }
-
-    if !path.extension().is_some_and(|it| it == "as") {
-      continue;
-    }
-
-    if path.to_string_lossy().contains("excluded/") {
-      continue;
-    }
-
-    let file_name = path.file_name().unwrap().to_string_lossy();
-    if !file_name.contains("Model") {
-      continue;
-    }
-
-    // debug!("Parsing {:?}...", path);
-    let content = fs::read_to_string(path).unwrap();
-    if !content.contains("[ModelInfo]") {
-      continue;
-    }
-
-    // debug!("{}", content);
-
-    let captures = MODEL_CLASS.captures(&content).expect("no model class capture");
-    // let model_name = captures.get(1).expect("no model name").as_str();
-    let model_base_name = captures.get(2).expect("no model base name").as_str();
-    let model_interface_name = captures.get(3).expect("no model interface name").as_str();
-    let model_server_name = model_base_name.replace("ModelBase", "ModelServer");
-    debug!("{:?} {:?} {:?} {:?}", captures.get(1).expect("no model name").as_str(), model_base_name, model_interface_name, model_server_name);
-
-    let class_import = Regex::new(&format!(r"import ([\w.]+\.{})", model_base_name));
-    let captures = class_import.unwrap().captures(&content).expect("no class import capture");
-    let model_base_import_fqdn = captures.get(1).expect("no import path").as_str();
-    let model_base_import_path = model_base_import_fqdn.replace('.', "/") + ".as";
-    let model_base_import_path = Path::new(&model_base_import_path);
-    // debug!("{:?} {:?}", model_base_import_fqdn, model_base_import_path);
-
-    let mut sources_root = path;
-    while let Some(parent) = sources_root.parent() {
-      sources_root = parent;
-      if parent.file_name().unwrap().to_string_lossy() == "src" {
-        break;
       }
-    }
-    // debug!("{:?}", sources_root);
-
-    let model_base_path = sources_root.join(model_base_import_path);
-    // debug!("{:?}", model_base_path);
-    let (model_base_path, model_base_contents) = match fs::read_to_string(&model_base_path) {
-      Ok(contents) => (model_base_path, contents),
-      Err(error) => {
-        // debug!("failed to read model base file (trying entrance) {:?}: {:?}", model_base_path, error);
-
-        // Try "entrance"
-        let model_base_path = model_base_path.components().map(|it| if it.as_os_str().to_string_lossy() == "game" { Component::Normal(OsStr::new("entrance")) } else { it }).collect::<PathBuf>();
-        // debug!("{:?}", model_base_path);
-        match fs::read_to_string(&model_base_path) {
-          Ok(contents) => (model_base_path, contents),
-          Err(error) => {
-            error!("failed to read model base file {:?}: {:?}", model_base_path, error);
-            todo!();
-          }
-        }
-      }
-    };
-    let relative_model_base_path = model_base_path.strip_prefix(input_root).unwrap();
-    // debug!("{}", model_base_contents);
-
-    let captures = MODEL_ID_REGEX.captures(&model_base_contents).expect("no model id");
-    let model_id = convert_to_id(parse_id_from_dec_or_hex(captures.name("high").unwrap().as_str()), parse_id_from_dec_or_hex(captures.name("low").unwrap().as_str()));
-    debug!("model id: {}", model_id);
-
-    let model_constructor = if let Some(captures) = MODEL_CONSTRUCTOR_REGEX.captures(&model_base_contents) {
-      let model_constructor = captures.name("codec").unwrap().as_str();
-      debug!("model constructor: {}", model_constructor);
-      Some(model_constructor.to_owned())
     } else {
-      None
+      vec![root.to_owned()]
     };
 
-    let mut client_methods = Vec::new();
-    let captures = MODEL_METHOD_REGEX.captures_iter(&model_base_contents);
-    for capture in captures {
-      let method_name = capture.name("method").unwrap().as_str();
-      let model_id = convert_to_id(parse_id_from_dec_or_hex(capture.name("high").unwrap().as_str()), parse_id_from_dec_or_hex(capture.name("low").unwrap().as_str()));
-      // debug!("client method: {} = {}", method_name, model_id);
-
-      client_methods.push(ParsedMethod {
-        name: method_name.to_owned(),
-        id: model_id,
-        params: Vec::new(),
-      });
-    }
-
-    let captures = MODEL_CLIENT_METHOD_PARAM_REGEX.captures_iter(&model_base_contents);
-    for capture in captures {
-      let method_name = capture.name("method").unwrap().as_str();
-      let param = capture.name("param").unwrap().as_str();
-      let codec = capture.name("codec").unwrap().as_str();
-      // debug!("client method: {} = {} by {}", method_name, param, codec);
-      //
-      // debug!("searching for {method_name}");
-      // debug!("{:?}", client_methods);
-      let method = client_methods.iter_mut().find(|it| it.name == method_name).unwrap();
-      method.params.push(ParsedMethodParam {
-        name: param.to_owned(),
-        codec: codec.to_owned(),
-        kind: codec_to_type(codec, false),
-      });
-    }
-
-    let model_server_contents = fs::read_to_string(model_base_path.parent().unwrap().to_path_buf().join(model_server_name + ".as")).unwrap();
-    // debug!("{}", model_server_contents);
-
-    let mut server_methods = Vec::new();
-    let captures = MODEL_METHOD_REGEX.captures_iter(&model_server_contents);
-    for capture in captures {
-      let method_name = capture.name("method").unwrap().as_str();
-      let model_id = convert_to_id(parse_id_from_dec_or_hex(capture.name("high").unwrap().as_str()), parse_id_from_dec_or_hex(capture.name("low").unwrap().as_str()));
-      // debug!("server method: {} = {}", method_name, model_id);
-
-      server_methods.push(ParsedMethod {
-        name: method_name.to_owned(),
-        id: model_id,
-        params: Vec::new(),
-      });
-    }
-
-    let captures = MODEL_SERVER_METHOD_PARAM_REGEX.captures_iter(&model_server_contents);
-    for capture in captures {
-      let method_name = capture.name("method").unwrap().as_str();
-      let param = capture.name("param").unwrap().as_str();
-      let codec = capture.name("codec").unwrap().as_str();
-      // debug!("server method: {} = {} by {}", method_name, param, codec);
-
-      let method = server_methods.iter_mut().find(|it| it.name == method_name).unwrap();
-      method.params.push(ParsedMethodParam {
-        name: param.to_owned(),
-        codec: codec.to_owned(),
-        kind: codec_to_type(codec, false),
-      });
-    }
-
-    debug!("CI {:?}", client_methods);
-    debug!("SI {:?}", server_methods);
-
-    let project = relative_model_base_path.components().nth(0).map(|it| it.as_os_str().to_string_lossy()).unwrap();
-    let model_name = model_base_name.replace("ModelBase", "Model");
-    let model = hl::Model {
-      name: model_name.clone(),
-      id: model_id,
-      constructor: model_constructor.map(|it| {
-        let kind = codec_to_type(&it, true);
-        let (_, type_def) = generate_protolang_type(&kind, input_root, output_root).unwrap();
-        hl::ModelConstructor {
-          fields: type_def.fields,
-          meta: type_def.meta,
-          comments: type_def.comments,
+    for path in &files {
+      let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) => {
+          reporter.report(Diagnostic::from(&WorkspaceError::Io { path: path.clone(), error }));
+          continue;
         }
-      }),
-      client_methods: client_methods.iter().map(|it| hl::ClientMethod {
-        name: it.name.to_owned(),
-        id: it.id,
-        params: it.params.iter().map(|it| hl::Param {
-          name: it.name.to_owned(),
-          kind: it.kind.to_owned(),
-          codec: it.codec.to_owned(),
-        }).collect_vec(),
-        comments: vec![],
-      }).collect_vec(),
-      server_methods: server_methods.iter().map(|it| hl::ServerMethod {
-        name: it.name.to_owned(),
-        id: it.id,
-        params: it.params.iter().map(|it| hl::Param {
-          name: it.name.to_owned(),
-          kind: it.kind.to_owned(),
-          codec: it.codec.to_owned(),
-        }).collect_vec(),
-        comments: vec![],
-      }).collect_vec(),
-      meta: vec![
-        Meta {
-          key: "client_package".to_owned(),
-          // value: format!("{}:{}", project, convert_path_to_definition(&relative_model_base_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, "."))
-          value: convert_path_to_definition(&relative_model_base_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".")
-        },
-        Meta { key: "client_name".to_owned(), value: model_name.to_owned() },
-      ],
-      comments: vec![
-        format!("TODO: This is an automatically generated model definition for \"{}\"", model_name)
-      ],
-    };
-    let definition = generate_protolang_code(&model);
-    debug!("{}", definition);
-
-    if let Some(constructor) = &model.constructor {
-      for field in &constructor.fields {
-        let types = get_types_from_generic(&field.kind);
-        for name in &types {
-          if !EXISTING_TYPES.lock().unwrap().contains(name) {
-            debug!("generating constructor type for {}", name);
-
-            let project = relative_model_base_path.components().nth(0).map(|it| it.as_os_str().to_string_lossy()).unwrap().to_string();
-            let (relative_model_base_path, definition) = generate_type_code_for(name, &project, input_root, output_root);
-            debug!("{}", definition);
-
-            let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
-            let output_path = output_root.join(convert_path_to_definition(&relative_model_base_path));
-            info!("generate type into {:?}", output_path);
-            fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-            fs::write(output_path, definition).unwrap();
-          }
+      };
+      let formatted = match protolang_parser::cst::format(&content) {
+        Ok(formatted) => formatted,
+        Err(errors) => {
+          reporter.extend(errors.into_iter().map(|message| Diagnostic::error("syntax", message).at(path.clone(), None)));
+          continue;
         }
-      }
-    }
+      };
 
-    for method in &model.client_methods {
-      for param in &method.params {
-        let types = get_types_from_generic(&param.kind);
-        // debug!("{:?}", types);
-        for name in &types {
-          if !EXISTING_TYPES.lock().unwrap().contains(name) {
-            debug!("generating type for {}", name);
-
-            // TODO
-            let project = relative_model_base_path.components().nth(0).map(|it| it.as_os_str().to_string_lossy()).unwrap().to_string();
-            let (relative_model_base_path, definition) = generate_type_code_for(name, &project, input_root, output_root);
-            debug!("{}", definition);
-
-            let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
-            let output_path = output_root.join(convert_path_to_definition(&relative_model_base_path));
-            info!("generate type into {:?}", output_path);
-            fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-            fs::write(output_path, definition).unwrap();
-          }
-        }
+      if formatted == content {
+        continue;
       }
-    }
 
-    for method in &model.server_methods {
-      for param in &method.params {
-        let types = get_types_from_generic(&param.kind);
-        // debug!("{:?}", types);
-        for name in &types {
-          if !EXISTING_TYPES.lock().unwrap().contains(name) {
-            debug!("generating type for {}", name);
-
-            // TODO
-            let project = relative_model_base_path.components().nth(0).map(|it| it.as_os_str().to_string_lossy()).unwrap().to_string();
-            let (relative_model_base_path, definition) = generate_type_code_for(name, &project, input_root, output_root);
-            debug!("{}", definition);
-
-            let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
-            let output_path = output_root.join(convert_path_to_definition(&relative_model_base_path));
-            info!("generate type into {:?}", output_path);
-            fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-            fs::write(output_path, definition).unwrap();
-          }
+      if check {
+        reporter.report(Diagnostic::error("unformatted", "file is not formatted".to_owned()).at(path.clone(), None));
+      } else {
+        info!("Formatting {:?}", path);
+        if let Err(error) = fs::write(path, formatted) {
+          reporter.report(Diagnostic::from(&WorkspaceError::Io { path: path.clone(), error }));
         }
       }
     }
-
-    let relative_model_base_path = relative_model_base_path.with_file_name(relative_model_base_path.file_name().unwrap().to_string_lossy().replace("ModelBase.as", "Model.proto"));
-    let output_path = output_root.join(convert_path_to_definition(&relative_model_base_path));
-    info!("generate model into {:?}", output_path);
-    fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-    fs::write(output_path, definition).unwrap();
-
-    // break;
   }
 }
 
-fn generate_type_code_for(name: &str, project: &str, input_root: &Path, output_root: &Path) -> (PathBuf, String) {
-  match generate_protolang_type(name, input_root, output_root) {
-    Some((relative_path, type_def)) => {
-      let definition = generate_protolang_code_type(&type_def);
-      (relative_path, definition)
-    }
+fn main() {
+  tracing_subscriber::registry()
+    .with(fmt::layer().with_writer(std::io::stderr))
+    .with(EnvFilter::from_default_env())
+    .init();
 
-    None => match generate_protolang_enum(name, input_root) {
-      Some((relative_path, enum_def)) => {
-        let project = relative_path.components().nth(0).map(|it| it.as_os_str().to_string_lossy()).unwrap();
-        let definition = generate_protolang_code_enum(&enum_def);
-        debug!("{}", definition);
+  let args = Args::parse();
+  if let Some(jobs) = args.jobs {
+    rayon::ThreadPoolBuilder::new().num_threads(jobs.get()).build_global().expect("thread pool is not built yet");
+  }
 
-        (relative_path, definition)
+  match &args.command {
+    Actions::Fmt { paths, check, filters } => {
+      let mut reporter = Reporter::new(args.message_format);
+      format_files(paths, *check, filters, &mut reporter);
+      reporter.finish();
+      if reporter.errors() > 0 {
+        std::process::exit(1);
       }
-
-      None => panic!("cannot generate type/enum definition for {}", name)
     }
-  }
-}
 
-fn generate_protolang_type(name: &str, input_root: &Path, output_root: &Path) -> Option<(PathBuf, hl::Type)> {
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
+    Actions::Dump { input, stage, format } => {
+      let mut reporter = Reporter::new(args.message_format);
+      let (workspace, errors) = Workspace::load_partial(vec![WorkspaceRoot::new(input)]);
+      reporter.extend(errors.iter().map(Diagnostic::from));
+      let files = dump(&workspace, *stage, &mut reporter);
+      // The dump goes to stdout as well, so it is only written without diagnostics
+      if reporter.errors() > 0 {
+        reporter.finish();
+        std::process::exit(1);
+      }
+      let output = match format {
+        DumpFormat::Json => serde_json::to_string_pretty(&files).unwrap(),
+        DumpFormat::Yaml => serde_yaml::to_string(&files).unwrap(),
+      };
+      println!("{}", output);
     }
 
-    if !entry.file_type().is_file() {
-      continue;
-    }
+    Actions::GenerateProtolang { input, output, models, filters } => {
+      let models = if models.is_empty() { MODEL_FILES.iter().map(|it| (*it).to_owned()).collect() } else { models.clone() };
+      let import = match import_actionscript(input, &filters.discovery("as"), &models) {
+        Ok(import) => import,
+        Err(error) => {
+          error!("{}", error);
+          std::process::exit(1);
+        }
+      };
 
-    if !path.extension().is_some_and(|it| it == "as") {
-      continue;
+      let mut reporter = Reporter::new(args.message_format);
+      let mut summary = Summary {
+        generated: import.models.len(),
+        failed: import.errors.iter().map(|it| &it.path).collect::<HashSet<_>>().len(),
+        ..Summary::default()
+      };
+      reporter.extend(import.errors.iter().map(Diagnostic::from));
+      write_files(output, &import.files, WriteMode::Write, &mut summary, &mut reporter);
+      reporter.finish();
+      eprintln!("{}", summary);
+      if !summary.is_success() {
+        std::process::exit(1);
+      }
     }
 
-    if path.to_string_lossy().contains("excluded/") {
-      continue;
+    Actions::Check { input, inputs, read_only_inputs, target, lint, config, filters } => {
+      let inputs = input.iter().chain(inputs).cloned().collect::<Vec<_>>();
+      let config = load_config(config.as_deref(), inputs.first().map(PathBuf::as_path));
+      let roots = workspace_roots(inputs, read_only_inputs, &config, filters);
+      let lints = config.lints.iter().chain(lint).cloned().collect::<Vec<_>>();
+      let mut reporter = Reporter::new(args.message_format);
+      reporter.extend(check(target, roots, &config, &lints));
+      reporter.finish();
+      if reporter.errors() > 0 {
+        std::process::exit(2);
+      } else if reporter.warnings() > 0 {
+        std::process::exit(1);
+      }
     }
 
-    let file_name = path.file_name().unwrap().to_string_lossy();
-    if file_name != format!("Codec{}.as", name) {
-      continue;
-    }
+    Actions::Generate { input, inputs, read_only_inputs, output, target, templates, plugin, package, module, with_dependencies, check, diff, no_cache, prune, watch, config, filters } => {
+      let inputs = input.iter().chain(inputs).cloned().collect::<Vec<_>>();
+      let config = load_config(config.as_deref(), inputs.first().map(PathBuf::as_path));
+      let roots = workspace_roots(inputs, read_only_inputs, &config, filters);
 
-    debug!("Parsing {:?}...", relative_path);
-    let content = fs::read_to_string(path).unwrap();
-    // debug!("{}", content);
+      let mut names = target.clone();
+      if names.is_empty() && templates.is_empty() && plugin.is_empty() {
+        names = config.targets.keys().cloned().collect();
+      }
+      if names.is_empty() && templates.is_empty() && plugin.is_empty() {
+        error!("No target given and no targets configured in {}", CONFIG_FILE);
+        std::process::exit(1);
+      }
 
-    if content.contains(" switch(") {
-      // Enum
-      return None;
+      let write = if *diff { WriteMode::Diff } else if *check { WriteMode::Check } else { WriteMode::Write };
+      let overrides = Overrides { output: output.as_deref(), package, module, with_dependencies: *with_dependencies, write, cache: !*no_cache, manifest: true, prune: *prune, watch: *watch };
+      run_targets(&names, templates, plugin, roots, &config, &overrides, &mut Reporter::new(args.message_format));
     }
 
-    let mut fields = Vec::new();
-    let captures = FIELD_REGEX.captures_iter(&content);
-    for capture in captures {
-      let field_name = capture.name("field").unwrap().as_str();
-      let codec = capture.name("codec").unwrap().as_str();
-      // debug!("field: {} by {}", field_name, codec =);
-
-      fields.push(ParsedField {
-        name: field_name.to_owned(),
-        codec: codec.to_owned(),
-        kind: codec_to_type(codec, false),
-      });
+    Actions::GenerateKotlin { input, output, package, module, cache, filters } => {
+      let overrides = Overrides { output: Some(output), package, module, with_dependencies: false, write: WriteMode::Write, cache: *cache, manifest: false, prune: false, watch: false };
+      let config = load_config(None, Some(input));
+      let roots = vec![filters.root(&config, input)];
+      run_targets(&["kotlin".to_owned()], &[], &[], roots, &config, &overrides, &mut Reporter::new(args.message_format));
     }
 
-    debug!("{:?}", fields);
-
-    let type_def = hl::Type {
-      name: name.to_owned(),
-      fields: fields.iter().enumerate().map(|(index, it)| hl::Field {
-        name: it.name.to_owned(),
-        kind: it.kind.to_owned(),
-        codec: it.codec.to_owned(),
-        position: index + 1,
-        comments: vec![],
-      }).collect_vec(),
-      meta: vec![
-        Meta { key: "client_package".to_owned(), value: convert_path_to_definition(&relative_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".") },
-        Meta { key: "client_name".to_owned(), value: name.to_owned() },
-      ],
-      comments: vec![
-        format!("TODO: This is an automatically generated type definition for \"{}\"", name)
-      ],
-    };
-
-    EXISTING_TYPES.lock().unwrap().insert(name.to_owned());
-
-    for field in &type_def.fields {
-      let types = get_types_from_generic(&field.kind);
-      for name in &types {
-        if !EXISTING_TYPES.lock().unwrap().contains(name) {
-          debug!("generating recursive type for {}", name);
-
-          let project = relative_path.components().nth(0).map(|it| it.as_os_str().to_string_lossy()).unwrap().to_string();
-          let (relative_path, definition) = generate_type_code_for(name, &project, input_root, output_root);
-          debug!("{}", definition);
-
-          let relative_path = relative_path.with_file_name(relative_path.file_name().unwrap().to_string_lossy().replacen("Codec", "", 1).replace(".as", ".proto"));
-          let output_path = output_root.join(convert_path_to_definition(&relative_path));
-          info!("generate type into {:?}", output_path);
-          fs::create_dir_all(output_path.parent().unwrap()).unwrap();
-          fs::write(output_path, definition).unwrap();
-        }
-      }
+    Actions::GenerateActionscript { input, output, package, module, cache, filters } => {
+      let overrides = Overrides { output: Some(output), package, module, with_dependencies: false, write: WriteMode::Write, cache: *cache, manifest: false, prune: false, watch: false };
+      let config = load_config(None, Some(input));
+      let roots = vec![filters.root(&config, input)];
+      run_targets(&["actionscript".to_owned()], &[], &[], roots, &config, &overrides, &mut Reporter::new(args.message_format));
     }
-
-    return Some((relative_path.to_path_buf(), type_def));
   }
-  panic!("cannot find type {}", name);
 }
 
-fn generate_protolang_enum(name: &str, input_root: &Path) -> Option<(PathBuf, hl::Enum)> {
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
-    }
-
-    if !entry.file_type().is_file() {
-      continue;
-    }
+/// Command line flags that take precedence over the project configuration
+struct Overrides<'a> {
+  output: Option<&'a Path>,
+  package: &'a Option<String>,
+  module: &'a Option<String>,
+  with_dependencies: bool,
+  write: WriteMode,
+  /// Only render the files whose inputs changed, never used with [WriteMode::Check]
+  cache: bool,
+  /// Record the outputs in the manifest of the output root, see [update_manifest]. Only the
+  /// `generate` command keeps a manifest, the legacy commands leave the output root alone.
+  manifest: bool,
+  prune: bool,
+  watch: bool,
+}
 
-    if !path.extension().is_some_and(|it| it == "as") {
-      continue;
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WriteMode {
+  Write,
+  /// Only compare the outputs with the files on disk
+  Check,
+  /// [WriteMode::Check] and print a unified diff of every difference
+  Diff,
+}
 
-    if path.to_string_lossy().contains("excluded/") {
-      continue;
+impl Overrides<'_> {
+  fn apply(&self, mut options: Options, workspace: &Workspace) -> Options {
+    if let Some(package) = self.package {
+      options.root_package = Some(package.clone());
     }
-
-    let file_name = path.file_name().unwrap().to_string_lossy();
-    if file_name != format!("Codec{}.as", name) {
-      continue;
+    if let Some(module) = self.module {
+      options.modules = module.split(',').map(ToOwned::to_owned).collect();
     }
-
-    debug!("Parsing {:?}...", relative_path);
-    let content = fs::read_to_string(path).unwrap();
-    // debug!("{}", content);
-
-    if !content.contains(" switch(") {
-      return None;
+    if self.with_dependencies {
+      options.modules = match workspace.with_dependencies(&options.modules) {
+        Ok(modules) => modules,
+        Err(error) => {
+          error!("{}", error);
+          std::process::exit(1);
+        }
+      };
+      info!("Generating modules {}", options.modules.join(", "));
     }
+    options
+  }
 
-    let mut variants = Vec::new();
-    let captures = VARIANT_REGEX.captures_iter(&content);
-    for capture in captures {
-      let variant = capture.name("variant").unwrap().as_str();
-      let value = capture.name("value").unwrap().as_str().parse::<i64>().unwrap();
-      // debug!("variant: {} = {}", variant, value);
-
-      variants.push(ParsedVariant {
-        name: variant.to_owned(),
-        value,
-      });
+  fn output(&self, config: &ProjectConfig, target: &str) -> PathBuf {
+    match self.output.map(Path::to_path_buf).or_else(|| config.output(target)) {
+      Some(output) => output,
+      None => {
+        error!("No output directory for target '{}', pass --output or set targets.{}.output in {}", target, target, CONFIG_FILE);
+        std::process::exit(1);
+      }
     }
-
-    debug!("{:?}", variants);
-
-    let enum_def = hl::Enum {
-      name: name.to_owned(),
-      repr: "i32".to_owned(),
-      variants: variants.iter().map(|it| hl::Variant {
-        name: it.name.to_owned(),
-        value: it.value,
-        comments: vec![],
-      }).collect_vec(),
-      meta: vec![
-        Meta { key: "client_package".to_owned(), value: convert_path_to_definition(&relative_path).parent().unwrap().to_string_lossy().replace(MAIN_SEPARATOR_STR, ".") },
-        Meta { key: "client_name".to_owned(), value: name.to_owned() }
-      ],
-      comments: vec![
-        format!("TODO: This is an automatically generated enum definition for \"{}\"", name)
-      ],
-    };
-
-    EXISTING_TYPES.lock().unwrap().insert(name.to_owned());
-
-    return Some((relative_path.to_path_buf(), enum_def));
   }
-  panic!("cannot find enum {}", name);
 }
 
-pub fn wrap_to_u64(x: i64) -> u64 {
-  (x as u64).wrapping_add(u64::MAX / 2 + 1)
-}
+/// Explicitly given configuration or the one discovered from the input, an empty one if there is none
+fn load_config(path: Option<&Path>, input: Option<&Path>) -> ProjectConfig {
+  let config = match path {
+    Some(path) => ProjectConfig::load(path).map(Some),
+    None => ProjectConfig::discover(input.unwrap_or(Path::new("."))),
+  };
 
-#[derive(Parser, Debug)]
-#[command(version)]
-struct Args {
-  #[command(subcommand)]
-  command: Actions,
+  match config {
+    Ok(config) => config.unwrap_or_default(),
+    Err(error) => {
+      error!("{}", error);
+      std::process::exit(1);
+    }
+  }
 }
 
-#[derive(Subcommand, Debug)]
-enum Actions {
-  GenerateProtolang {
-    input: PathBuf,
-
-    #[arg(short, long)]
-    output: PathBuf,
-  },
-  GenerateKotlin {
-    input: PathBuf,
-
-    #[arg(short, long)]
-    output: PathBuf,
-
-    #[arg(long)]
-    package: Option<String>,
-
-    /// Module to generate sources for
-    #[arg(long)]
-    module: Option<String>,
-  },
-  GenerateActionscript {
-    input: PathBuf,
-
-    #[arg(short, long)]
-    output: PathBuf,
-
-    #[arg(long)]
-    package: Option<String>,
+/// Given inputs or the configured ones, exits if there are none
+fn workspace_roots(mut inputs: Vec<PathBuf>, read_only_inputs: &[PathBuf], config: &ProjectConfig, filters: &Filters) -> Vec<WorkspaceRoot> {
+  if inputs.is_empty() {
+    inputs = config.inputs();
+  }
+  let read_only_inputs = if read_only_inputs.is_empty() { config.read_only_inputs() } else { read_only_inputs.to_vec() };
+  if inputs.is_empty() {
+    error!("No input given and no inputs configured in {}", CONFIG_FILE);
+    std::process::exit(1);
+  }
 
-    /// Module to generate sources for
-    #[arg(long)]
-    module: Option<String>,
-  },
+  inputs.iter().map(|it| filters.root(config, it))
+    .chain(read_only_inputs.iter().map(|it| filters.root(config, it).read_only(true)))
+    .collect()
 }
 
-fn main() {
-  tracing_subscriber::registry()
-    .with(fmt::layer())
-    .with(EnvFilter::from_default_env())
-    .init();
-
-  let args = Args::parse();
-
-  match &args.command {
-    Actions::GenerateProtolang { input, output } => {
-      {
-        let mut types = EXISTING_TYPES.lock().unwrap();
-        let mut paths = BUILTIN_FQN.lock().unwrap();
-
-        types.insert("bool".to_owned());
-        types.insert("i8".to_owned());
-        types.insert("i16".to_owned());
-        types.insert("i32".to_owned());
-        types.insert("i64".to_owned());
-        types.insert("f32".to_owned());
-        types.insert("f64".to_owned());
-        types.insert("String".to_owned());
-
-        for ty in types.iter() {
-          // Primitives are globally available
-          paths.insert(ty.to_owned(), ty.to_owned());
-        }
-
-        types.insert("Instant".to_owned());
-        paths.insert("Instant".to_owned(), "kotlinx.datetime.Instant".to_owned());
-        types.insert("IGameObject".to_owned());
-        paths.insert("IGameObject".to_owned(), "jp.assasans.araumi.architecture.objects.IGameObject".to_owned());
-
-        types.insert("Object".to_owned()); // synthetic
-
-        types.insert("ObjectsData".to_owned());
-        paths.insert("ObjectsData".to_owned(), "jp.assasans.araumi.protocol.codec.ObjectsData".to_owned());
-        types.insert("ObjectsDependencies".to_owned());
-        paths.insert("ObjectsDependencies".to_owned(), "jp.assasans.araumi.protocol.codec.ObjectsDependencies".to_owned());
-        types.insert("ModelData".to_owned());
-        paths.insert("ModelData".to_owned(), "jp.assasans.araumi.protocol.codec.ModelData".to_owned());
-
-        types.insert("MoveCommand".to_owned());
-        paths.insert("MoveCommand".to_owned(), "jp.assasans.araumi.protocol.codec.MoveCommand".to_owned());
-
-        types.insert("Resource".to_owned());
-        paths.insert("Resource".to_owned(), "jp.assasans.araumi.resources.Resource".to_owned());
-        types.insert("SoundResource".to_owned());
-        paths.insert("SoundResource".to_owned(), "jp.assasans.araumi.resources.SoundResource".to_owned());
-        types.insert("MapResource".to_owned());
-        paths.insert("MapResource".to_owned(), "jp.assasans.araumi.resources.MapResource".to_owned());
-        types.insert("ProplibResource".to_owned());
-        paths.insert("ProplibResource".to_owned(), "jp.assasans.araumi.resources.ProplibResource".to_owned());
-        types.insert("TextureResource".to_owned());
-        paths.insert("TextureResource".to_owned(), "jp.assasans.araumi.resources.TextureResource".to_owned());
-        types.insert("ImageResource".to_owned());
-        paths.insert("ImageResource".to_owned(), "jp.assasans.araumi.resources.ImageResource".to_owned());
-        types.insert("MultiframeTextureResource".to_owned());
-        paths.insert("MultiframeTextureResource".to_owned(), "jp.assasans.araumi.resources.MultiframeTextureResource".to_owned());
-        types.insert("LocalizedImageResource".to_owned());
-        paths.insert("LocalizedImageResource".to_owned(), "jp.assasans.araumi.resources.LocalizedImageResource".to_owned());
-        types.insert("Object3DResource".to_owned());
-        paths.insert("Object3DResource".to_owned(), "jp.assasans.araumi.resources.Object3DResource".to_owned());
-      }
-
-      generate_model_index(input);
-      for (constructor_name, model_name) in MODEL_TYPES.lock().unwrap().iter() {
-        debug!("{} -> {}", constructor_name, model_name);
-      }
-
-      generate_protolang_model(input, output);
-    }
-
-    Actions::GenerateKotlin { input, output, package, module } => {
-      {
-        let mut types = EXISTING_TYPES.lock().unwrap();
-        let mut paths = BUILTIN_FQN.lock().unwrap();
-
-        types.insert("bool".to_owned());
-        types.insert("i8".to_owned());
-        types.insert("i16".to_owned());
-        types.insert("i32".to_owned());
-        types.insert("i64".to_owned());
-        types.insert("f32".to_owned());
-        types.insert("f64".to_owned());
-        types.insert("String".to_owned());
-
-        for ty in types.iter() {
-          // Primitives are globally available
-          paths.insert(ty.to_owned(), ty.to_owned());
-        }
-
-        types.insert("Instant".to_owned());
-        paths.insert("Instant".to_owned(), "kotlinx.datetime.Instant".to_owned());
-        types.insert("IGameObject".to_owned());
-        paths.insert("IGameObject".to_owned(), "jp.assasans.araumi.architecture.objects.IGameObject".to_owned());
-
-        types.insert("Object".to_owned()); // synthetic
-
-        types.insert("ObjectsData".to_owned());
-        paths.insert("ObjectsData".to_owned(), "jp.assasans.araumi.protocol.codec.ObjectsData".to_owned());
-        types.insert("ObjectsDependencies".to_owned());
-        paths.insert("ObjectsDependencies".to_owned(), "jp.assasans.araumi.protocol.codec.ObjectsDependencies".to_owned());
-        types.insert("ModelData".to_owned());
-        paths.insert("ModelData".to_owned(), "jp.assasans.araumi.protocol.codec.ModelData".to_owned());
-
-        types.insert("MoveCommand".to_owned());
-        paths.insert("MoveCommand".to_owned(), "jp.assasans.araumi.protocol.codec.MoveCommand".to_owned());
-
-        types.insert("Resource".to_owned());
-        paths.insert("Resource".to_owned(), "jp.assasans.araumi.resources.Resource".to_owned());
-        types.insert("SoundResource".to_owned());
-        paths.insert("SoundResource".to_owned(), "jp.assasans.araumi.resources.SoundResource".to_owned());
-        types.insert("MapResource".to_owned());
-        paths.insert("MapResource".to_owned(), "jp.assasans.araumi.resources.MapResource".to_owned());
-        types.insert("ProplibResource".to_owned());
-        paths.insert("ProplibResource".to_owned(), "jp.assasans.araumi.resources.ProplibResource".to_owned());
-        types.insert("TextureResource".to_owned());
-        paths.insert("TextureResource".to_owned(), "jp.assasans.araumi.resources.TextureResource".to_owned());
-        types.insert("ImageResource".to_owned());
-        paths.insert("ImageResource".to_owned(), "jp.assasans.araumi.resources.ImageResource".to_owned());
-        types.insert("MultiframeTextureResource".to_owned());
-        paths.insert("MultiframeTextureResource".to_owned(), "jp.assasans.araumi.resources.MultiframeTextureResource".to_owned());
-        types.insert("LocalizedImageResource".to_owned());
-        paths.insert("LocalizedImageResource".to_owned(), "jp.assasans.araumi.resources.LocalizedImageResource".to_owned());
-        types.insert("Object3DResource".to_owned());
-        paths.insert("Object3DResource".to_owned(), "jp.assasans.araumi.resources.Object3DResource".to_owned());
-      }
-
-      generate_module_index(input);
-      generate_definition_index(input);
-      generate_kotlin(package.as_deref(), module.as_deref(), input, output);
-    }
-
-    Actions::GenerateActionscript { input, output, package, module } => {
-      {
-        let mut paths = BUILTIN_FQN.lock().unwrap();
-
-        paths.insert("Dictionary".to_owned(), "flash.utils.Dictionary".to_owned());
-
-        paths.insert("Byte".to_owned(), "alternativa.types.Byte".to_owned());
-        paths.insert("Short".to_owned(), "alternativa.types.Short".to_owned());
-        paths.insert("Long".to_owned(), "alternativa.types.Long".to_owned());
-        paths.insert("Float".to_owned(), "alternativa.types.Float".to_owned());
-        paths.insert("IGameObject".to_owned(), "platform.client.fp10.core.type.IGameObject".to_owned());
-
-        paths.insert("ObjectsData".to_owned(), "platform.client.core.general.spaces.loading.dispatcher.types.ObjectsData".to_owned());
-        paths.insert("ObjectsDependencies".to_owned(), "platform.client.core.general.spaces.loading.dispatcher.types.ObjectsDependencies".to_owned());
-        paths.insert("ModelData".to_owned(), "platform.client.core.general.spaces.loading.modelconstructors.ModelData".to_owned());
-
-        paths.insert("MoveCommand".to_owned(), "projects.tanks.client.battlefield.models.user.tank.commands.MoveCommand".to_owned());
-
-        paths.insert("Resource".to_owned(), "platform.client.fp10.core.resource.Resource".to_owned());
-        paths.insert("SoundResource".to_owned(), "platform.client.fp10.core.resource.types.SoundResource".to_owned());
-        paths.insert("MapResource".to_owned(), "projects.tanks.clients.flash.resources.resource.MapResource".to_owned());
-        paths.insert("ProplibResource".to_owned(), "projects.tanks.clients.flash.resources.resource.PropLibResource".to_owned());
-        paths.insert("TextureResource".to_owned(), "platform.client.fp10.core.resource.types.TextureResource".to_owned());
-        paths.insert("ImageResource".to_owned(), "platform.client.fp10.core.resource.types.ImageResource".to_owned());
-        paths.insert("MultiframeTextureResource".to_owned(), "platform.client.fp10.core.resource.types.MultiframeTextureResource".to_owned());
-        paths.insert("LocalizedImageResource".to_owned(), "platform.client.fp10.core.resource.types.LocalizedImageResource".to_owned());
-        paths.insert("Tanks3DSResource".to_owned(), "projects.tanks.clients.flash.resources.resource.Tanks3DSResource".to_owned());
-      }
+/// Every diagnostic of loading, module validation, type resolution and the given lints
+fn check(names: &[String], roots: Vec<WorkspaceRoot>, config: &ProjectConfig, lints: &[String]) -> Vec<Diagnostic> {
+  let registry = Registry::builtin();
+  let mut names = names.to_vec();
+  if names.is_empty() {
+    names = config.targets.keys().cloned().collect();
+  }
+  if names.is_empty() {
+    names = registry.names().map(ToOwned::to_owned).collect();
+  }
 
-      generate_module_index(input);
-      generate_definition_index(input);
-      generate_constructor_index(input);
-      generate_actionscript(package.as_deref(), module.as_deref(), input, output);
+  // Builtins of any of the targets, the generators report the ones a target does not support
+  let mut diagnostics = Vec::new();
+  let mut builtins = HashSet::new();
+  for name in names {
+    match registry.get(&name) {
+      Some(target) => builtins.extend(config.options(target).builtins.unwrap_or_else(|| target.builtin_fqn()).into_keys()),
+      None => diagnostics.push(Diagnostic::error("unknown-target", format!("unknown target '{}', available targets: {}", name, registry.names().collect::<Vec<_>>().join(", ")))),
     }
   }
 
-  // let (_, type_def) = generate_protolang_type("ExternalAuthParameters");
-  // let definition = generate_protolang_code_type(&type_def, &[]);
-  // let (_, enum_def) = generate_protolang_enum("TargetingMode");
-  // let definition = generate_protolang_code_enum(&enum_def, &[]);
-  // info!("{}", definition);
+  let (workspace, errors) = Workspace::load_partial(roots);
+  diagnostics.extend(errors.iter().map(Diagnostic::from));
+  diagnostics.extend(workspace.validate().iter().map(Diagnostic::from));
+  diagnostics.extend(unresolved_references(&workspace, &builtins));
+  diagnostics.extend(lint(&workspace, lints));
+  diagnostics
 }
 
-fn generate_module_index(input_root: &Path) {
-  let mut modules = MODULES.lock().unwrap();
-  for entry in WalkDir::new(input_root) {
-    let entry = entry.unwrap();
-    let path = entry.path();
-    let relative_path = path.strip_prefix(input_root).unwrap();
-    if is_path_hidden(relative_path) {
-      continue;
+/// Exits after reporting every problem with the targets, the schema or the plugins
+fn run_targets(names: &[String], templates: &[PathBuf], plugins: &[PathBuf], roots: Vec<WorkspaceRoot>, config: &ProjectConfig, overrides: &Overrides, reporter: &mut Reporter) {
+  let registry = Registry::builtin();
+  let mut targets = Vec::new();
+  for name in names {
+    match registry.get(name) {
+      Some(target) => targets.push(target),
+      None => reporter.report(Diagnostic::error("unknown-target", format!("unknown target '{}', available targets: {}", name, registry.names().collect::<Vec<_>>().join(", ")))),
     }
+  }
 
-    if !entry.file_type().is_file() {
-      continue;
+  let mut template_targets = Vec::new();
+  for dir in templates {
+    match TemplateTarget::load(dir) {
+      Ok(target) => template_targets.push(target),
+      Err(error) => reporter.report(Diagnostic::error("template", error.to_string()).at(dir.clone(), None)),
     }
+  }
+  targets.extend(template_targets.iter().map(|it| it as &dyn Target));
+  exit_on_errors(reporter);
 
-    if path.to_string_lossy().contains("excluded/") {
-      continue;
+  let watched = roots.iter().map(|it| it.path.clone()).collect::<Vec<_>>();
+  let (mut workspace, mut errors) = Workspace::load_partial(roots);
+  let success = generate_workspace(&targets, plugins, &workspace, &errors, config, overrides, reporter);
+  if !overrides.watch {
+    if !success {
+      std::process::exit(1);
     }
+    return;
+  }
 
-    let file_name = path.file_name().unwrap().to_string_lossy();
-    if file_name != "module.yaml" {
-      continue;
+  let watcher = match Watcher::new(&watched) {
+    Ok(watcher) => watcher,
+    Err(error) => {
+      error!("Cannot watch the inputs: {}", error);
+      std::process::exit(1);
     }
-
-    let module_name = path.parent().unwrap().file_name().unwrap().to_string_lossy().to_string();
-    let sources_root = relative_path.parent().unwrap().to_string_lossy().to_string();
-
-    info!("Found module '{}' ({}) descriptor at {:?}", module_name, sources_root, relative_path);
-    let content = fs::read_to_string(path).unwrap();
-    // debug!("{}", content);
-
-    modules.insert(module_name, sources_root);
+  };
+  eprintln!("Watching {} for changes, press Ctrl-C to stop", watched.iter().map(|it| format!("{:?}", it)).collect::<Vec<_>>().join(", "));
+  while let Some(changed) = watcher.next_change() {
+    let mut names = changed.iter().take(3).map(|it| format!("{:?}", it)).collect::<Vec<_>>();
+    if changed.len() > names.len() {
+      names.push(format!("{} more", changed.len() - names.len()));
+    }
+    eprintln!("\nChanged {}, regenerating...", names.join(", "));
+
+    let started = Instant::now();
+    (workspace, errors) = workspace.reload();
+    reporter.restart();
+    generate_workspace(&targets, plugins, &workspace, &errors, config, overrides, reporter);
+    eprintln!("Finished in {:.2?}, watching for changes", started.elapsed());
   }
 }
 
-fn get_path_module(input_root: &Path, relative_path: &Path) -> Option<(String, String)> {
-  let module_dir = relative_path.to_path_buf();
-  let mut module_dir = module_dir.parent();
-  while let Some(some_module_dir) = module_dir {
-    let module_path = input_root.join(some_module_dir).join("module.yaml");
-    if module_path.exists() {
-      let module_name = some_module_dir.to_string_lossy().to_string();
-      if module_name == "" {
-        debug!("module \"root\" for {:?}", relative_path);
-        return Some(("root".to_owned(), "".to_owned()));
-      }
-
-      debug!("module {:?} for {:?}", module_name, relative_path);
-      return Some((module_name, "".to_owned()));
+/// Generates every target and plugin and prints the summary. Returns `false` if anything failed.
+fn generate_workspace(targets: &[&dyn Target], plugins: &[PathBuf], workspace: &Workspace, errors: &[WorkspaceError], config: &ProjectConfig, overrides: &Overrides, reporter: &mut Reporter) -> bool {
+  // Files that cannot be loaded are left out, the module rules are checked on the loaded ones
+  let mut summary = Summary { check: overrides.write != WriteMode::Write, ..Summary::default() };
+  summary.failed += errors.iter().filter_map(|it| it.path()).collect::<HashSet<_>>().len();
+  reporter.extend(errors.iter().map(Diagnostic::from));
+  let module_errors = workspace.validate();
+  reporter.extend(module_errors.iter().map(Diagnostic::from));
+  // Files that break the module rules fail on their own, an unknown module affects all of them
+  if module_errors.iter().any(|it| it.path().is_none()) {
+    reporter.finish();
+    return false;
+  }
+  let invalid = module_errors.iter().filter_map(|it| it.path()).map(Path::to_path_buf).collect::<BTreeSet<_>>();
+  summary.failed += invalid.len();
+
+  let roots = workspace.roots.iter().map(|it| it.path.as_path()).collect::<Vec<_>>();
+  for &target in targets {
+    info!("Generating {} sources...", target.name());
+    let options = Options { exclude: invalid.clone(), ..overrides.apply(config.options(target), workspace) };
+    let output = overrides.output(config, target.name());
+    let mut cache = (overrides.cache && overrides.write == WriteMode::Write).then(|| Cache::load(&output));
+    let generation = match &mut cache {
+      Some(cache) => generate_incremental(target, workspace, &options, cache),
+      None => generate(target, workspace, &options),
+    };
+    summary.generated += generation.generated.len();
+    summary.cached += generation.cached.len();
+    summary.skipped += generation.skipped.len();
+    summary.failed += generation.errors.iter().map(|it| &it.path).collect::<HashSet<_>>().len();
+    reporter.extend(generation.errors.iter().map(Diagnostic::from));
+    write_files(&output, &generation.files, overrides.write, &mut summary, reporter);
+    if overrides.manifest {
+      update_manifest(Manifest::load(&output, &roots), target.name(), &generation.outputs, &generation.processed(), overrides, &mut summary, reporter);
+    }
+    if let Some(Err(error)) = cache.as_mut().map(Cache::save) {
+      reporter.report(Diagnostic::warning("io", format!("failed to write the cache: {}", error)).at(output.join(CACHE_FILE), None));
     }
-
-    module_dir = some_module_dir.parent();
   }
-  None
-}
 
-fn is_path_hidden<P: AsRef<Path>>(path: P) -> bool {
-  path.as_ref().components().any(|component| {
-    if let Some(name) = component.as_os_str().to_str() {
-      name.starts_with('.')
-    } else {
-      false
+  for plugin in plugins {
+    let options = overrides.apply(Options { root_package: config.root_package.clone(), ..Options::default() }, workspace);
+    match run_plugin(plugin, workspace, &options) {
+      Ok(plugin_output) => {
+        reporter.extend(plugin_output.diagnostics.iter().map(Diagnostic::from));
+        let output = overrides.output(config, "plugin");
+        write_files(&output, &plugin_output.files, overrides.write, &mut summary, reporter);
+        if overrides.manifest {
+          let outputs = plugin_output.files.iter().map(|it| (it.path.clone(), plugin.clone())).collect();
+          update_manifest(Manifest::load(&output, &roots), "plugin", &outputs, &BTreeSet::from([plugin.as_path()]), overrides, &mut summary, reporter);
+        }
+      }
+      Err(PluginError::Failed(diagnostics)) => reporter.extend(diagnostics.iter().map(Diagnostic::from)),
+      Err(error) => reporter.report(Diagnostic::error("plugin", error.to_string()).at(plugin.clone(), None)),
     }
-  })
+  }
+  reporter.finish();
+  eprintln!("{}", summary);
+  summary.is_success() && reporter.errors() == 0
 }
 
-fn parse_id_from_dec_or_hex(value: &str) -> i32 {
-  if value.starts_with("0x") {
-    i32::from_str_radix(value.strip_prefix("0x").unwrap(), 16).unwrap()
+/// Records the outputs of a target in the manifest of its output root and handles the stale
+/// outputs, see [Manifest::update]. In check mode, the manifest is left as it is.
+fn update_manifest(mut manifest: Manifest, target: &str, outputs: &BTreeMap<PathBuf, PathBuf>, processed: &BTreeSet<&Path>, overrides: &Overrides, summary: &mut Summary, reporter: &mut Reporter) {
+  let output_root = manifest.output_root.clone();
+  let stale = manifest.update(target, outputs, processed);
+  if !overrides.prune {
+    if !stale.is_empty() {
+      info!("{} stale output(s) in {:?}, pass --prune to delete them", stale.len(), output_root);
+    }
+  } else if overrides.write == WriteMode::Write {
+    for path in stale {
+      info!("Deleting stale {:?}", output_root.join(&path));
+      match manifest.remove(&path) {
+        Ok(()) => summary.pruned += 1,
+        Err(error) => reporter.report(Diagnostic::error("io", format!("failed to delete: {}", error)).at(output_root.join(path), None)),
+      }
+    }
   } else {
-    value.parse::<i32>().unwrap()
+    for path in stale.into_iter().map(|it| output_root.join(it)).filter(|it| it.exists()) {
+      reporter.report(Diagnostic::error("stale", "generated file is stale, run generate --prune".to_owned()).at(path, None));
+      summary.outdated += 1;
+    }
   }
-}
-
-lazy_static! {
-  static ref TYPES_IN_GENERIC_REGEX: Regex = Regex::new(r"\.?<([\w\s,.?]+)>").unwrap();
-
-  static ref TYPE_REGEX: Regex = Regex::new(r"new (?:Type|Enum)CodecInfo\((.+?),\s*(false|true)\)").unwrap();
-  static ref COLLECTION_REGEX: Regex = Regex::new(r"new CollectionCodecInfo\((.+?),\s*(false|true)(?:,\s*\d+)?\)").unwrap();
-  static ref MAP_REGEX: Regex = Regex::new(r"new MapCodecInfo\((.+?),\s*(.+?),\s*(false|true)\)").unwrap();
-
-  static ref COLLECTION_REVERSE_REGEX: Regex = Regex::new(r"List<(.+?)(\?)?>").unwrap();
-
-  static ref REGEX_1: Regex = Regex::new(r"\bBoolean\b").unwrap();
-  static ref REGEX_2: Regex = Regex::new(r"\bByte\b").unwrap();
-  static ref REGEX_3: Regex = Regex::new(r"\bShort\b").unwrap();
-  static ref REGEX_4: Regex = Regex::new(r"\b[Ii]nt\b").unwrap();
-  static ref REGEX_5: Regex = Regex::new(r"\bLong\b").unwrap();
-  static ref REGEX_6: Regex = Regex::new(r"\bFloat\b").unwrap();
-  static ref REGEX_7: Regex = Regex::new(r"\b(Number|Double)\b").unwrap();
-  static ref REGEX_8: Regex = Regex::new(r"\bTanks3DSResource\b").unwrap();
-  static ref REGEX_9: Regex = Regex::new(r"\bDate\b").unwrap();
-}
 
-fn codec_to_type(codec: &str, is_constructor: bool) -> String {
-  let codec = TYPE_REGEX.replace_all(&codec, |captures: &regex::Captures| {
-    let inner = captures.get(1).unwrap().as_str();
-    let optional = captures.get(2).unwrap().as_str();
-    format!("{}{}", inner, if optional == "true" { "?" } else { "" })
-  });
-  let codec = COLLECTION_REGEX.replace_all(&codec, |captures: &regex::Captures| {
-    let inner = captures.get(1).unwrap().as_str();
-    let optional = captures.get(2).unwrap().as_str();
-    format!("List<{}>{}", inner, if optional == "true" { "?" } else { "" })
-  });
-  let codec = MAP_REGEX.replace_all(&codec, |captures: &regex::Captures| {
-    let key = captures.get(1).unwrap().as_str();
-    let value = captures.get(2).unwrap().as_str();
-    let optional = captures.get(3).unwrap().as_str();
-    format!("Map<{}, {}>{}", key, value, if optional == "true" { "?" } else { "" })
-  });
-  let codec = REGEX_1.replace_all(&codec, "bool");
-  let codec = REGEX_2.replace_all(&codec, "i8");
-  let codec = REGEX_3.replace_all(&codec, "i16");
-  let codec = REGEX_4.replace_all(&codec, "i32");
-  let codec = REGEX_5.replace_all(&codec, "i64");
-  let codec = REGEX_6.replace_all(&codec, "f32");
-  let codec = REGEX_7.replace_all(&codec, "f64");
-  let codec = REGEX_8.replace_all(&codec, "Object3DResource");
-  let codec = REGEX_9.replace_all(&codec, "Instant");
-
-  if !is_constructor {
-    // Convert CC to Model.Constructor references
-    // TODO: This does not support wrapped types, only TypeCodecInfo
-    let model_name = MODEL_TYPES.lock().unwrap().get(&codec.to_string()).cloned();
-    if let Some(model_name) = &model_name {
-      return format!("{}.Constructor", model_name);
+  if overrides.write == WriteMode::Write {
+    if let Err(error) = manifest.save() {
+      reporter.report(Diagnostic::warning("io", format!("failed to write the manifest: {}", error)).at(output_root.join(MANIFEST_FILE), None));
     }
   }
-
-  codec.to_string()
 }
 
-fn convert_to_id(high: i32, low: i32) -> i64 {
-  ((u32::from_ne_bytes(i32::to_ne_bytes(high)) as i64) << 32) | (u32::from_ne_bytes(i32::to_ne_bytes(low)) as i64)
-}
+/// Reports an `outdated` error if the file on disk is missing or differs from `contents`
+fn compare_file(path: &Path, contents: &str, print_diff: bool, summary: &mut Summary, reporter: &mut Reporter) {
+  let existing = match fs::read_to_string(path) {
+    Ok(existing) => Some(existing),
+    Err(error) if error.kind() == ErrorKind::NotFound => None,
+    Err(error) => {
+      reporter.report(Diagnostic::error("io", format!("failed to read: {}", error)).at(path.to_path_buf(), None));
+      summary.outdated += 1;
+      return;
+    }
+  };
+  if existing.as_deref() == Some(contents) {
+    summary.up_to_date += 1;
+    return;
+  }
 
-fn convert_from_id(id: i64) -> (i32, i32) {
-  ((id >> 32) as i32, (id & 0xffffffff) as i32)
+  summary.outdated += 1;
+  let message = if existing.is_some() { "generated file is out of date" } else { "generated file is missing" };
+  reporter.report(Diagnostic::error("outdated", message.to_owned()).at(path.to_path_buf(), None));
+  if print_diff {
+    let old_header = if existing.is_some() { format!("a/{}", path.display()) } else { "/dev/null".to_owned() };
+    let diff = TextDiff::from_lines(existing.as_deref().unwrap_or(""), contents);
+    // Written to stderr like the human diagnostics, stdout is reserved for `--message-format` output
+    eprint!("{}", diff.unified_diff().header(&old_header, &format!("b/{}", path.display())));
+  }
 }
 
-fn convert_path_to_definition(path: &Path) -> PathBuf {
-  warn!("convert path: {:?}", path);
-  let relative_to_source_root = path.components().skip(2).collect::<PathBuf>();
-  if relative_to_source_root.components().nth(0).unwrap().as_os_str() == "_codec" {
-    relative_to_source_root.components().skip(1).collect::<PathBuf>()
-  } else {
-    relative_to_source_root
+/// Prints the reported diagnostics and exits if there are errors
+fn exit_on_errors(reporter: &mut Reporter) {
+  if reporter.errors() > 0 {
+    reporter.finish();
+    std::process::exit(1);
   }
 }
 
-fn get_types_from_generic(value: &str) -> Vec<String> {
-  let captures = match TYPES_IN_GENERIC_REGEX.captures(value) {
-    Some(value) => value,
-    None => return vec![value.trim().trim_end_matches('?').to_owned()]
-  };
+fn write_files(output_root: &Path, files: &[GeneratedFile], mode: WriteMode, summary: &mut Summary, reporter: &mut Reporter) {
+  for file in files {
+    let output_path = output_root.join(&file.path);
+    if mode != WriteMode::Write {
+      compare_file(&output_path, &file.contents, mode == WriteMode::Diff, summary, reporter);
+      continue;
+    }
+
+    if fs::read_to_string(&output_path).is_ok_and(|it| it == file.contents) {
+      debug!("{:?} is unchanged", output_path);
+      summary.unchanged += 1;
+      continue;
+    }
 
-  let inner = captures.get(1).unwrap().as_str();
-  inner.split(',').map(|it| it.trim().trim_end_matches('?').to_owned()).collect_vec()
+    info!("Writing {:?}", output_path);
+    let result = match output_path.parent() {
+      Some(parent) => fs::create_dir_all(parent).and_then(|_| fs::write(&output_path, &file.contents)),
+      None => fs::write(&output_path, &file.contents),
+    };
+    match result {
+      Ok(()) => summary.written += 1,
+      Err(error) => {
+        reporter.report(Diagnostic::error("io", format!("failed to write: {}", error)).at(output_path, None));
+        summary.unwritten += 1;
+      }
+    }
+  }
 }
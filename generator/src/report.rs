@@ -0,0 +1,231 @@
+//! Output of diagnostics in the format selected with `--message-format`.
+//!
+//! Logs go to stderr, so that the JSON and SARIF output on stdout can be piped into other tools.
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, MAIN_SEPARATOR_STR};
+
+use clap::ValueEnum;
+use protolang_parser::diagnostic::{Diagnostic, Severity};
+use protolang_parser::span::Span;
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+  /// One line per diagnostic on stderr
+  #[default]
+  Human,
+  /// One JSON object per diagnostic and line on stdout
+  Json,
+  /// A SARIF 2.1.0 log on stdout, written when the command finishes
+  Sarif,
+}
+
+pub struct Reporter {
+  format: MessageFormat,
+  errors: usize,
+  warnings: usize,
+  /// Collected for [MessageFormat::Sarif] only
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl Reporter {
+  pub fn new(format: MessageFormat) -> Reporter {
+    Reporter { format, errors: 0, warnings: 0, diagnostics: Vec::new() }
+  }
+
+  pub fn report(&mut self, diagnostic: Diagnostic) {
+    match diagnostic.severity {
+      Severity::Error => self.errors += 1,
+      Severity::Warning => self.warnings += 1,
+    }
+    match self.format {
+      MessageFormat::Human => eprintln!("{}", diagnostic),
+      MessageFormat::Json => println!("{}", serde_json::to_string(&diagnostic).unwrap()),
+      MessageFormat::Sarif => self.diagnostics.push(diagnostic),
+    }
+  }
+
+  pub fn extend(&mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+    for diagnostic in diagnostics {
+      self.report(diagnostic);
+    }
+  }
+
+  /// Starts over for another run of `generate --watch`
+  pub fn restart(&mut self) {
+    self.errors = 0;
+    self.warnings = 0;
+    self.diagnostics.clear();
+  }
+
+  pub fn errors(&self) -> usize {
+    self.errors
+  }
+
+  pub fn warnings(&self) -> usize {
+    self.warnings
+  }
+
+  /// Prints the summary line or the SARIF log, call it before exiting
+  pub fn finish(&mut self) {
+    match self.format {
+      MessageFormat::Human => {
+        if self.errors > 0 || self.warnings > 0 {
+          eprintln!("{} error(s), {} warning(s)", self.errors, self.warnings);
+        }
+      }
+      MessageFormat::Json => {}
+      MessageFormat::Sarif => println!("{}", serde_json::to_string_pretty(&sarif(&self.diagnostics)).unwrap()),
+    }
+  }
+}
+
+/// Schema files per outcome, counted once per target, and the output files of a `generate` run
+#[derive(Debug, Default)]
+pub struct Summary {
+  pub generated: usize,
+  /// Up to date according to the cache, see `protolang_codegen::cache`
+  pub cached: usize,
+  /// In modules skipped for the target
+  pub skipped: usize,
+  /// Failed to load or to generate
+  pub failed: usize,
+  pub written: usize,
+  /// Output files that already had the same contents and were not touched
+  pub unchanged: usize,
+  /// Output files that could not be written
+  pub unwritten: usize,
+  /// Stale outputs of previous runs that were deleted, see `generate --prune`
+  pub pruned: usize,
+  /// Outputs were compared with the files on disk instead of written, see `generate --check`
+  pub check: bool,
+  pub up_to_date: usize,
+  /// Output files that are missing on disk or differ, and stale ones with `--prune`
+  pub outdated: usize,
+}
+
+impl Summary {
+  pub fn is_success(&self) -> bool {
+    self.failed == 0 && self.unwritten == 0 && self.outdated == 0
+  }
+}
+
+impl Display for Summary {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{} generated, ", self.generated)?;
+    if self.cached > 0 {
+      write!(f, "{} cached, ", self.cached)?;
+    }
+    write!(f, "{} skipped, {} failed", self.skipped, self.failed)?;
+    if self.check {
+      return write!(f, ", {} file(s) up to date, {} out of date", self.up_to_date, self.outdated);
+    }
+    write!(f, ", {} file(s) written, {} unchanged", self.written, self.unchanged)?;
+    if self.pruned > 0 {
+      write!(f, ", {} pruned", self.pruned)?;
+    }
+    if self.unwritten > 0 {
+      write!(f, ", {} could not be written", self.unwritten)?;
+    }
+    Ok(())
+  }
+}
+
+/// SARIF log with a single run, see https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+pub fn sarif(diagnostics: &[Diagnostic]) -> Value {
+  let rules = diagnostics.iter().map(|it| it.code.as_str()).collect::<BTreeSet<_>>();
+  let results = diagnostics.iter().map(sarif_result).collect::<Vec<_>>();
+  json!({
+    "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+    "version": "2.1.0",
+    "runs": [{
+      "tool": {
+        "driver": {
+          "name": env!("CARGO_PKG_NAME"),
+          "version": env!("CARGO_PKG_VERSION"),
+          "rules": rules.into_iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+        }
+      },
+      "results": results,
+    }]
+  })
+}
+
+fn sarif_result(diagnostic: &Diagnostic) -> Value {
+  let mut result = json!({
+    "ruleId": diagnostic.code,
+    "level": match diagnostic.severity {
+      Severity::Error => "error",
+      Severity::Warning => "warning",
+    },
+    "message": { "text": diagnostic.message },
+  });
+
+  if let Some(path) = &diagnostic.path {
+    let mut location = json!({ "artifactLocation": { "uri": uri(path) } });
+    if let Some(span) = diagnostic.span {
+      location["region"] = region(span);
+    }
+    result["locations"] = json!([{ "physicalLocation": location }]);
+  }
+
+  if let Some(fix) = &diagnostic.fix {
+    let mut sarif_fix = json!({ "description": { "text": fix.message } });
+    if let (Some(path), Some(span), Some(replacement)) = (&diagnostic.path, diagnostic.span, &fix.replacement) {
+      sarif_fix["artifactChanges"] = json!([{
+        "artifactLocation": { "uri": uri(path) },
+        "replacements": [{ "deletedRegion": region(span), "insertedContent": { "text": replacement } }],
+      }]);
+    }
+    result["fixes"] = json!([sarif_fix]);
+  }
+  result
+}
+
+fn uri(path: &Path) -> String {
+  path.to_string_lossy().replace(MAIN_SEPARATOR_STR, "/")
+}
+
+/// 1-based, the end column is exclusive. Spans end at their last character on the same line.
+fn region(span: Span) -> Value {
+  json!({
+    "startLine": span.line + 1,
+    "startColumn": span.column + 1,
+    "endColumn": span.column + span.end.saturating_sub(span.start) + 2,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use protolang_parser::diagnostic::Diagnostic;
+  use protolang_parser::span::Span;
+  use serde_json::json;
+
+  use super::sarif;
+
+  #[test]
+  fn sarif_log() {
+    let span = Span { start: 20, end: 22, line: 2, column: 4 };
+    let diagnostics = [
+      Diagnostic::error("unresolved-type", "unknown type Teem".to_owned()).at(PathBuf::from("schema/Tank.proto"), Some(span)).fix("did you mean Team?".to_owned(), Some("Team".to_owned())),
+      Diagnostic::warning("missing-docs", "type Tank has no doc comment".to_owned()),
+    ];
+    let log = sarif(&diagnostics);
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(log["runs"][0]["tool"]["driver"]["rules"], json!([{ "id": "missing-docs" }, { "id": "unresolved-type" }]));
+
+    let results = &log["runs"][0]["results"];
+    assert_eq!(results[0]["level"], "error");
+    let location = &results[0]["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "schema/Tank.proto");
+    assert_eq!(location["region"], json!({ "startLine": 3, "startColumn": 5, "endColumn": 8 }));
+    let change = &results[0]["fixes"][0]["artifactChanges"][0];
+    assert_eq!(change["replacements"][0]["insertedContent"]["text"], "Team");
+    assert_eq!(results[1]["level"], "warning");
+    assert!(results[1].get("locations").is_none());
+  }
+}
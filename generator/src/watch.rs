@@ -0,0 +1,88 @@
+//! Filesystem notifications for `generate --watch`, debounced so that saving several files at
+//! once, or an editor writing a file in several steps, triggers a single run.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use protolang_parser::workspace::MODULE_DESCRIPTOR;
+use tracing::{debug, warn};
+
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+pub struct Watcher {
+  // Stops watching when dropped
+  _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+  events: Receiver<DebounceEventResult>,
+}
+
+impl Watcher {
+  /// Watches directory roots recursively. For a single file root, its directory is watched, since
+  /// editors often replace a file instead of writing to it.
+  pub fn new(roots: &[PathBuf]) -> Result<Watcher, notify::Error> {
+    let (sender, events) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, None, sender)?;
+    for root in roots {
+      if root.is_file() {
+        let dir = root.parent().filter(|it| !it.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        debouncer.watch(dir, RecursiveMode::NonRecursive)?;
+      } else {
+        debouncer.watch(root, RecursiveMode::Recursive)?;
+      }
+    }
+    Ok(Watcher { _debouncer: debouncer, events })
+  }
+
+  /// Blocks until schema files or module descriptors change, returns the changed paths
+  pub fn next_change(&self) -> Option<BTreeSet<PathBuf>> {
+    for result in &self.events {
+      let events = match result {
+        Ok(events) => events,
+        Err(errors) => {
+          for error in errors {
+            warn!("Watch error: {}", error);
+          }
+          continue;
+        }
+      };
+
+      // Reading the files, which every run does, is reported as access
+      let changed = events.into_iter()
+        .filter(|it| !matches!(it.kind, EventKind::Access(_)))
+        .flat_map(|it| it.event.paths)
+        .filter(|it| is_schema_path(it))
+        .collect::<BTreeSet<_>>();
+      if !changed.is_empty() {
+        return Some(changed);
+      }
+      debug!("Ignoring changes to other files");
+    }
+    None
+  }
+}
+
+/// `.proto` files and module descriptors. Paths without an extension are ignored, most of them
+/// are unrelated files, and deleting a directory also reports the schema files in it.
+fn is_schema_path(path: &Path) -> bool {
+  path.extension().is_some_and(|it| it == "proto") || path.file_name().is_some_and(|it| it == MODULE_DESCRIPTOR)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use super::is_schema_path;
+
+  #[test]
+  fn schema_paths() {
+    assert!(is_schema_path(Path::new("schema/battle/Tank.proto")));
+    assert!(is_schema_path(Path::new("schema/battle/module.yaml")));
+    assert!(!is_schema_path(Path::new("schema/battle/notes.yaml")));
+    assert!(!is_schema_path(Path::new("schema/battle/Tank.proto.swp")));
+    assert!(!is_schema_path(Path::new("schema/battle/LICENSE")));
+    assert!(!is_schema_path(Path::new("schema/battle")));
+  }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+fn generator(dir: &Path, args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_protolang-generator"))
+    .current_dir(dir)
+    .args(args)
+    .output()
+    .unwrap()
+}
+
+/// Writes `(path, content)` pairs under `dir`, creating the directories in between
+fn write_tree(dir: &Path, files: &[(&str, &str)]) {
+  for (path, content) in files {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+  }
+}
+
+fn write_schema(dir: &Path) {
+  write_tree(dir, &[
+    ("schema/module.yaml", ""),
+    ("schema/battle/CliTeam.proto", "enum CliTeam : i32 {\n  RED = 0;\n}\n"),
+    ("schema/battle/CliType.proto", "type CliType {\n  team: CliTeam = 1;\n}\n"),
+  ]);
+}
+
+const GENERATE: [&str; 7] = ["generate", "schema", "-o", "out", "-t", "kotlin", "--no-cache"];
+
+#[test]
+fn check_sarif() {
+  let dir = tempfile::tempdir().unwrap();
+  write_schema(dir.path());
+  fs::write(dir.path().join("schema/battle/CliType.proto"), "type CliType {\n  team: CliTeem = 1;\n}\n").unwrap();
+
+  let output = generator(dir.path(), &["--message-format", "sarif", "check", "schema"]);
+  assert_eq!(output.status.code(), Some(2));
+  let log: Value = serde_json::from_slice(&output.stdout).unwrap();
+  let result = &log["runs"][0]["results"][0];
+  assert_eq!(result["ruleId"], "unresolved-type");
+  assert_eq!(result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "schema/battle/CliType.proto");
+  assert_eq!(result["locations"][0]["physicalLocation"]["region"]["startLine"], 2);
+  assert_eq!(result["fixes"][0]["artifactChanges"][0]["replacements"][0]["insertedContent"]["text"], "CliTeam");
+}
+
+#[test]
+fn generate_check_and_diff() {
+  let dir = tempfile::tempdir().unwrap();
+  write_schema(dir.path());
+  assert!(generator(dir.path(), &GENERATE).status.success());
+  let check = [&GENERATE[..], &["--check"]].concat();
+  assert!(generator(dir.path(), &check).status.success());
+
+  let output_path = dir.path().join("out/battle/CliTeam.generated.kt");
+  let contents = fs::read_to_string(&output_path).unwrap();
+  fs::write(&output_path, contents.replace("RED", "GREEN")).unwrap();
+  let output = generator(dir.path(), &check);
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8_lossy(&output.stderr).contains("error[outdated]: out/battle/CliTeam.generated.kt: generated file is out of date"));
+
+  // Only diagnostics go to stdout, the diff goes to stderr
+  let diff = [&["--message-format", "json"][..], &GENERATE, &["--diff"]].concat();
+  let output = generator(dir.path(), &diff);
+  assert_eq!(output.status.code(), Some(1));
+  let diagnostics = String::from_utf8(output.stdout).unwrap().lines().map(|it| serde_json::from_str::<Value>(it).unwrap()).collect::<Vec<_>>();
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0]["code"], "outdated");
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains("--- a/out/battle/CliTeam.generated.kt\n+++ b/out/battle/CliTeam.generated.kt\n"), "{}", stderr);
+  assert!(stderr.contains("\n-  GREEN(0),\n+  RED(0),\n"), "{}", stderr);
+  assert_eq!(fs::read_to_string(&output_path).unwrap(), contents.replace("RED", "GREEN"));
+}
+
+#[test]
+fn generate_orphan() {
+  let dir = tempfile::tempdir().unwrap();
+  write_schema(dir.path());
+  fs::rename(dir.path().join("schema/module.yaml"), dir.path().join("schema/battle/module.yaml")).unwrap();
+  fs::write(dir.path().join("schema/CliOrphan.proto"), "enum CliOrphan : i32 {\n  NONE = 0;\n}\n").unwrap();
+
+  // The orphan fails on its own, the files of the module are still generated
+  let output = generator(dir.path(), &GENERATE);
+  assert_eq!(output.status.code(), Some(1));
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(stderr.contains("error[orphan]: schema/CliOrphan.proto"), "{}", stderr);
+  assert!(stderr.contains("2 generated, 0 skipped, 1 failed"), "{}", stderr);
+  assert!(dir.path().join("out/battle/CliTeam.generated.kt").exists());
+  assert!(dir.path().join("out/battle/CliType.generated.kt").exists());
+  assert!(!dir.path().join("out/CliOrphan.generated.kt").exists());
+}
+
+#[test]
+fn generate_prune() {
+  let dir = tempfile::tempdir().unwrap();
+  write_schema(dir.path());
+  assert!(generator(dir.path(), &GENERATE).status.success());
+  fs::write(dir.path().join("out/battle/Handwritten.kt"), "").unwrap();
+  fs::remove_file(dir.path().join("schema/battle/CliType.proto")).unwrap();
+
+  // Without --prune, stale outputs are kept
+  assert!(generator(dir.path(), &GENERATE).status.success());
+  assert!(dir.path().join("out/battle/CliType.generated.kt").exists());
+
+  let check = [&GENERATE[..], &["--prune", "--check"]].concat();
+  let output = generator(dir.path(), &check);
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8_lossy(&output.stderr).contains("error[stale]: out/battle/CliType.generated.kt"));
+  assert!(dir.path().join("out/battle/CliType.generated.kt").exists());
+
+  let prune = [&GENERATE[..], &["--prune"]].concat();
+  assert!(generator(dir.path(), &prune).status.success());
+  assert!(!dir.path().join("out/battle/CliType.generated.kt").exists());
+  assert!(dir.path().join("out/battle/CliTeam.generated.kt").exists());
+  assert!(dir.path().join("out/battle/Handwritten.kt").exists());
+}
+
+#[test]
+fn generate_watch() {
+  let dir = tempfile::tempdir().unwrap();
+  write_schema(dir.path());
+  let mut child = Command::new(env!("CARGO_BIN_EXE_protolang-generator"))
+    .current_dir(dir.path())
+    .args([&GENERATE[..], &["--watch"]].concat())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+
+  let (sender, lines) = mpsc::channel();
+  let stderr = child.stderr.take().unwrap();
+  thread::spawn(move || {
+    for line in BufReader::new(stderr).lines() {
+      if sender.send(line.unwrap()).is_err() {
+        break;
+      }
+    }
+  });
+  let wait_for = |prefix: &str| loop {
+    let line = lines.recv_timeout(Duration::from_secs(10)).unwrap();
+    if line.starts_with(prefix) {
+      break line;
+    }
+  };
+  wait_for("Watching");
+
+  // Unrelated files are ignored, saving several schema files at once triggers a single run
+  fs::write(dir.path().join("schema/NOTES"), "").unwrap();
+  fs::write(dir.path().join("schema/battle/CliTeam.proto"), "enum CliTeam : i32 {\n  RED = 0;\n  BLUE = 1;\n}\n").unwrap();
+  fs::write(dir.path().join("schema/battle/CliType.proto"), "type CliType {\n  team: CliTeam? = 1;\n}\n").unwrap();
+  let changed = wait_for("Changed");
+  assert!(!changed.contains("NOTES"), "{}", changed);
+  wait_for("Finished");
+  thread::sleep(Duration::from_secs(1));
+  let more = lines.try_iter().filter(|it| it.starts_with("Changed")).collect::<Vec<_>>();
+  child.kill().unwrap();
+  child.wait().unwrap();
+
+  assert!(more.is_empty(), "{:?}", more);
+  assert!(fs::read_to_string(dir.path().join("out/battle/CliTeam.generated.kt")).unwrap().contains("BLUE"));
+}
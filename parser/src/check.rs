@@ -0,0 +1,296 @@
+//! Validation passes over a loaded [Workspace] that go beyond parsing and lowering: unresolved
+//! type references and the optional lints enabled in the project configuration.
+
+use std::collections::HashSet;
+
+use heck::{ToLowerCamelCase, ToShoutySnakeCase, ToUpperCamelCase};
+
+use crate::diagnostic::Diagnostic;
+use crate::span::{Positioned, Span};
+use crate::visit::{self, Visit};
+use crate::workspace::Workspace;
+use crate::{
+  ClientMethodDeclaration, CommentLit, EnumDeclaration, FieldDeclaration, Identifier, IncludeDeclaration, InterfaceDeclaration,
+  ModelDeclaration, ParamDeclaration, ServerMethodDeclaration, TemplateDeclaration, TemplateModelDeclaration, Type,
+  TypeDeclaration, VariantDeclaration,
+};
+
+/// Types every target supports
+pub const PRIMITIVE_TYPES: [&str; 10] = ["bool", "i8", "i16", "i32", "i64", "f32", "f64", "String", "List", "Map"];
+
+/// Lint name and description
+pub const LINTS: [(&str, &str); 3] = [
+  ("missing-docs", "declarations without a doc comment"),
+  ("naming", "declarations not in PascalCase, fields and parameters not in camelCase, enum variants not in SCREAMING_SNAKE_CASE"),
+  ("unused-definition", "types, enums and interfaces that nothing refers to"),
+];
+
+/// Type references that are neither declared in the workspace, nor [PRIMITIVE_TYPES] or `builtins`
+pub fn unresolved_references(workspace: &Workspace, builtins: &HashSet<String>) -> Vec<Diagnostic> {
+  let mut diagnostics = Vec::new();
+  for file in &workspace.files {
+    let mut references = References::default();
+    references.visit_program(&file.ast);
+    for (name, span) in references.0 {
+      if workspace.symbols.get(&name).is_some() || PRIMITIVE_TYPES.contains(&name.as_str()) || builtins.contains(&name) {
+        continue;
+      }
+      let mut diagnostic = Diagnostic::error("unresolved-type", format!("unknown type {}", name));
+      let candidates = workspace.symbols.iter().map(|it| it.name.as_str()).chain(PRIMITIVE_TYPES).chain(builtins.iter().map(String::as_str));
+      if let Some(candidate) = closest(&name, candidates) {
+        diagnostic = diagnostic.fix(format!("did you mean {}?", candidate), Some(candidate.to_owned()));
+      }
+      diagnostics.push(diagnostic.at(workspace.path(file), Some(span.locate(&file.content))));
+    }
+  }
+  diagnostics
+}
+
+/// Runs the given [LINTS], files in read-only roots are not linted. Unknown lint names are
+/// reported as errors.
+pub fn lint(workspace: &Workspace, lints: &[String]) -> Vec<Diagnostic> {
+  let mut diagnostics = Vec::new();
+  for lint in lints {
+    if !LINTS.iter().any(|(name, _)| name == lint) {
+      let names = LINTS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+      diagnostics.push(Diagnostic::error("unknown-lint", format!("unknown lint '{}', available lints: {}", lint, names)));
+    }
+  }
+  let enabled = |name: &str| lints.iter().any(|it| it == name);
+
+  let mut used = HashSet::new();
+  if enabled("unused-definition") {
+    for file in &workspace.files {
+      let mut references = References::default();
+      references.visit_program(&file.ast);
+      used.extend(references.0.into_iter().map(|(name, _)| name));
+    }
+  }
+
+  for file in workspace.files.iter().filter(|it| !workspace.roots[it.root].read_only) {
+    let mut declarations = Declarations::default();
+    declarations.visit_program(&file.ast);
+    let warning = |code: &str, message: String, span: Span| Diagnostic::warning(code, message).at(workspace.path(file), Some(span.locate(&file.content)));
+
+    for declaration in &declarations.0 {
+      let Declaration { kind, name, span, documented } = declaration;
+      if enabled("missing-docs") && !documented && matches!(kind, Kind::Model | Kind::Type | Kind::Enum | Kind::Interface | Kind::Template) {
+        let diagnostic = warning("missing-docs", format!("{} {} has no doc comment", kind.as_str(), name), *span);
+        diagnostics.push(diagnostic.fix("add a `///` comment above the declaration".to_owned(), None));
+      }
+
+      if enabled("naming") {
+        let (valid, case, renamed) = match kind {
+          Kind::Model | Kind::Type | Kind::Enum | Kind::Interface | Kind::Template => (is_pascal_case(name), "PascalCase", name.to_upper_camel_case()),
+          Kind::Field | Kind::Param | Kind::Method => (is_camel_case(name), "camelCase", name.to_lower_camel_case()),
+          Kind::Variant => (is_screaming_snake_case(name), "SCREAMING_SNAKE_CASE", name.to_shouty_snake_case()),
+        };
+        if !valid {
+          let diagnostic = warning("naming", format!("{} {} should be in {}", kind.as_str(), name, case), *span);
+          // Not applied mechanically, the references to the declaration would have to be renamed too
+          diagnostics.push(diagnostic.fix(format!("rename to {} and update its references", renamed), None));
+        }
+      }
+
+      if enabled("unused-definition") && matches!(kind, Kind::Type | Kind::Enum | Kind::Interface) && !used.contains(name) {
+        diagnostics.push(warning("unused-definition", format!("{} {} is never used", kind.as_str(), name), *span));
+      }
+    }
+  }
+  diagnostics
+}
+
+/// Names of the referenced types, included interfaces and template models
+#[derive(Default)]
+struct References(Vec<(String, Span)>);
+
+impl References {
+  fn push(&mut self, name: &Positioned<Identifier>) {
+    self.0.push((name.value.0.clone(), name.span));
+  }
+}
+
+impl Visit for References {
+  fn visit_include(&mut self, node: &IncludeDeclaration) {
+    self.push(&node.name);
+  }
+
+  fn visit_template_model(&mut self, node: &TemplateModelDeclaration) {
+    self.push(&node.name);
+  }
+
+  fn visit_type(&mut self, node: &Type) {
+    match node {
+      Type::Ident { ty, .. } => self.push(ty),
+      Type::Generic { ty, .. } => {
+        self.push(ty);
+        visit::walk_type(self, node);
+      }
+      // `TankModel.Constructor` only refers to the model
+      Type::Nested { ty, .. } => self.visit_type(ty),
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  Model,
+  Type,
+  Enum,
+  Interface,
+  Template,
+  Field,
+  Param,
+  Method,
+  Variant,
+}
+
+impl Kind {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Kind::Model => "model",
+      Kind::Type => "type",
+      Kind::Enum => "enum",
+      Kind::Interface => "interface",
+      Kind::Template => "template",
+      Kind::Field => "field",
+      Kind::Param => "parameter",
+      Kind::Method => "method",
+      Kind::Variant => "variant",
+    }
+  }
+}
+
+struct Declaration {
+  kind: Kind,
+  name: String,
+  span: Span,
+  documented: bool,
+}
+
+#[derive(Default)]
+struct Declarations(Vec<Declaration>);
+
+impl Declarations {
+  fn push(&mut self, kind: Kind, name: &Positioned<Identifier>, comments: &[CommentLit]) {
+    self.0.push(Declaration { kind, name: name.value.0.clone(), span: name.span, documented: !comments.is_empty() });
+  }
+}
+
+impl Visit for Declarations {
+  fn visit_model(&mut self, node: &ModelDeclaration) {
+    self.push(Kind::Model, &node.name, &node.comments);
+    visit::walk_model(self, node);
+  }
+
+  fn visit_interface(&mut self, node: &InterfaceDeclaration) {
+    self.push(Kind::Interface, &node.name, &node.comments);
+    visit::walk_interface(self, node);
+  }
+
+  fn visit_type_declaration(&mut self, node: &TypeDeclaration) {
+    self.push(Kind::Type, &node.name, &node.comments);
+    visit::walk_type_declaration(self, node);
+  }
+
+  fn visit_enum(&mut self, node: &EnumDeclaration) {
+    self.push(Kind::Enum, &node.name, &node.comments);
+    visit::walk_enum(self, node);
+  }
+
+  fn visit_variant(&mut self, node: &VariantDeclaration) {
+    self.push(Kind::Variant, &node.name, &node.comments);
+  }
+
+  fn visit_template(&mut self, node: &TemplateDeclaration) {
+    self.push(Kind::Template, &node.name, &node.comments);
+  }
+
+  fn visit_server_method(&mut self, node: &ServerMethodDeclaration) {
+    self.push(Kind::Method, &node.name, &node.comments);
+    visit::walk_server_method(self, node);
+  }
+
+  fn visit_client_method(&mut self, node: &ClientMethodDeclaration) {
+    self.push(Kind::Method, &node.name, &node.comments);
+    visit::walk_client_method(self, node);
+  }
+
+  fn visit_field(&mut self, node: &FieldDeclaration) {
+    self.push(Kind::Field, &node.name, &node.comments);
+  }
+
+  fn visit_param(&mut self, node: &ParamDeclaration) {
+    self.push(Kind::Param, &node.name, &[]);
+  }
+}
+
+/// Most similar name within a third of the length of `name`, for typos like `Tnak`
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+  let limit = (name.chars().count() / 3).max(1);
+  candidates
+    .map(|candidate| (strsim::damerau_levenshtein(name, candidate), candidate))
+    .filter(|(distance, _)| *distance <= limit)
+    .min()
+    .map(|(_, candidate)| candidate)
+}
+
+fn is_pascal_case(name: &str) -> bool {
+  name.starts_with(|char: char| char.is_ascii_uppercase()) && name.chars().all(|char| char.is_ascii_alphanumeric())
+}
+
+fn is_camel_case(name: &str) -> bool {
+  name.starts_with(|char: char| char.is_ascii_lowercase()) && name.chars().all(|char| char.is_ascii_alphanumeric())
+}
+
+fn is_screaming_snake_case(name: &str) -> bool {
+  name.starts_with(|char: char| char.is_ascii_uppercase()) && name.chars().all(|char| char.is_ascii_uppercase() || char.is_ascii_digit() || char == '_')
+}
+
+#[cfg(test)]
+mod tests {
+  use itertools::Itertools;
+
+  use super::*;
+  use crate::diagnostic::Severity;
+  use crate::workspace::WorkspaceRoot;
+  use crate::write_tree;
+
+  #[test]
+  fn check() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("Broken.proto", "type CheckTestBroken {\n  a i32 = 1;\n}\n"),
+      ("Truncated.proto", "type CheckTestTruncated {\n"),
+      ("CheckTestTeam.proto", "/// Team\nenum CheckTestTeam : i32 {\n  Red = 0;\n}\n"),
+      ("CheckTestType.proto", "type CheckTestType {\n  team: CheckTestTeam = 1;\n  items: List<CheckTestMissing?> = 2;\n  resource: Resource = 3;\n  other: CheckTestTeem = 4;\n}\n"),
+    ]);
+
+    let (workspace, errors) = Workspace::load_partial(vec![WorkspaceRoot::new(root)]);
+    let syntax = Diagnostic::from(&errors[0]);
+    assert_eq!(syntax.span.map(|it| (it.line, it.column)), Some((1, 4)));
+    let errors = errors.iter().map(|it| it.to_string()).collect_vec();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("Broken.proto\": unrecognized token"));
+    assert!(errors[1].contains("Truncated.proto\": unexpected end of file"));
+    assert_eq!(workspace.files.len(), 2);
+
+    let diagnostics = unresolved_references(&workspace, &HashSet::from(["Resource".to_owned()]));
+    assert_eq!(diagnostics.iter().map(|it| it.to_string()).collect_vec(), vec![
+      format!("error[unresolved-type]: {}:3:15: unknown type CheckTestMissing", root.join("CheckTestType.proto").display()),
+      format!("error[unresolved-type]: {}:5:10: unknown type CheckTestTeem (did you mean CheckTestTeam?)", root.join("CheckTestType.proto").display()),
+    ]);
+    assert_eq!(diagnostics[1].fix.as_ref().unwrap().replacement.as_deref(), Some("CheckTestTeam"));
+
+    let diagnostics = lint(&workspace, &["naming".to_owned(), "missing-docs".to_owned(), "unused".to_owned()]);
+    assert_eq!(diagnostics[1].fix.as_ref().unwrap().replacement, None);
+    let diagnostics = diagnostics.iter().map(|it| (it.severity, it.code.as_str(), it.message.as_str())).collect_vec();
+    assert_eq!(diagnostics, vec![
+      (Severity::Error, "unknown-lint", "unknown lint 'unused', available lints: missing-docs, naming, unused-definition"),
+      (Severity::Warning, "naming", "variant Red should be in SCREAMING_SNAKE_CASE"),
+      (Severity::Warning, "missing-docs", "type CheckTestType has no doc comment"),
+    ]);
+  }
+}
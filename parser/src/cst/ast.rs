@@ -0,0 +1,396 @@
+//! Typed read-only view over [SyntaxNode]s. Accessors return [None] for parts that are missing
+//! in invalid input instead of failing.
+
+use itertools::Itertools;
+
+use super::{SyntaxKind, SyntaxNode, SyntaxToken};
+
+pub trait AstNode<'a>: Sized {
+  fn cast(node: &'a SyntaxNode) -> Option<Self>;
+  fn syntax(&self) -> &'a SyntaxNode;
+}
+
+macro_rules! ast_node {
+  ($name:ident, $($kind:ident)|+) => {
+    #[derive(Debug, Clone, Copy)]
+    pub struct $name<'a>(&'a SyntaxNode);
+
+    impl<'a> AstNode<'a> for $name<'a> {
+      fn cast(node: &'a SyntaxNode) -> Option<Self> {
+        if matches!(node.kind, $(SyntaxKind::$kind)|+) { Some($name(node)) } else { None }
+      }
+
+      fn syntax(&self) -> &'a SyntaxNode {
+        self.0
+      }
+    }
+  };
+}
+
+ast_node!(SourceFile, SourceFile);
+ast_node!(Meta, Meta);
+ast_node!(Model, Model);
+ast_node!(TypeDef, TypeDef);
+ast_node!(Enum, Enum);
+ast_node!(Interface, Interface);
+ast_node!(Template, Template);
+ast_node!(TemplateModel, TemplateModel);
+ast_node!(TemplateValue, TemplateValue);
+ast_node!(Entity, Entity);
+ast_node!(Constructor, Constructor);
+ast_node!(Field, Field);
+ast_node!(Variant, Variant);
+ast_node!(Method, ClientMethod | ServerMethod);
+ast_node!(Include, Include);
+ast_node!(IncludeOverride, IncludeOverride);
+ast_node!(Param, Param);
+ast_node!(TypeRef, TypeRef);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Item<'a> {
+  Meta(Meta<'a>),
+  Model(Model<'a>),
+  Type(TypeDef<'a>),
+  Enum(Enum<'a>),
+  Interface(Interface<'a>),
+  Template(Template<'a>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ModelItem<'a> {
+  Meta(Meta<'a>),
+  Entity(Entity<'a>),
+  Constructor(Constructor<'a>),
+  Method(Method<'a>),
+  Include(Include<'a>),
+}
+
+fn children<'a, T: AstNode<'a> + 'a>(node: &'a SyntaxNode) -> impl Iterator<Item = T> + 'a {
+  node.child_nodes().filter_map(T::cast)
+}
+
+fn ident(node: &SyntaxNode) -> Option<&str> {
+  node.child_token(SyntaxKind::Ident).map(|it| it.text.as_str())
+}
+
+fn number(node: &SyntaxNode) -> Option<i64> {
+  node.child_token(SyntaxKind::Number).and_then(|it| it.text.parse().ok())
+}
+
+/// Text of `///` comments above the node, without the slashes
+fn doc_comments(node: &SyntaxNode) -> Vec<&str> {
+  node.leading_trivia().into_iter()
+    .filter(|it| it.kind == SyntaxKind::DocComment)
+    .map(|it| it.text[3..].trim())
+    .collect()
+}
+
+impl<'a> SourceFile<'a> {
+  pub fn items(&self) -> impl Iterator<Item = Item<'a>> + 'a {
+    self.0.child_nodes().filter_map(|node| match node.kind {
+      SyntaxKind::Meta => Some(Item::Meta(Meta(node))),
+      SyntaxKind::Model => Some(Item::Model(Model(node))),
+      SyntaxKind::TypeDef => Some(Item::Type(TypeDef(node))),
+      SyntaxKind::Enum => Some(Item::Enum(Enum(node))),
+      SyntaxKind::Interface => Some(Item::Interface(Interface(node))),
+      SyntaxKind::Template => Some(Item::Template(Template(node))),
+      _ => None
+    })
+  }
+}
+
+impl<'a> Meta<'a> {
+  pub fn key(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn value(&self) -> Option<&'a str> {
+    self.0.child_token(SyntaxKind::String).map(|it| it.text.trim_matches('"'))
+  }
+}
+
+impl<'a> Model<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn id(&self) -> Option<i64> {
+    number(self.0)
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+
+  pub fn items(&self) -> impl Iterator<Item = ModelItem<'a>> + 'a {
+    self.0.child_nodes().filter_map(|node| match node.kind {
+      SyntaxKind::Meta => Some(ModelItem::Meta(Meta(node))),
+      SyntaxKind::Entity => Some(ModelItem::Entity(Entity(node))),
+      SyntaxKind::Constructor => Some(ModelItem::Constructor(Constructor(node))),
+      SyntaxKind::ClientMethod | SyntaxKind::ServerMethod => Some(ModelItem::Method(Method(node))),
+      SyntaxKind::Include => Some(ModelItem::Include(Include(node))),
+      _ => None
+    })
+  }
+
+  pub fn meta(&self) -> impl Iterator<Item = Meta<'a>> + 'a {
+    children(self.0)
+  }
+
+  pub fn methods(&self) -> impl Iterator<Item = Method<'a>> + 'a {
+    children(self.0)
+  }
+
+  pub fn constructor(&self) -> Option<Constructor<'a>> {
+    children(self.0).next()
+  }
+}
+
+impl<'a> TypeDef<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+
+  pub fn meta(&self) -> impl Iterator<Item = Meta<'a>> + 'a {
+    children(self.0)
+  }
+
+  pub fn fields(&self) -> impl Iterator<Item = Field<'a>> + 'a {
+    children(self.0)
+  }
+}
+
+impl<'a> Enum<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn repr(&self) -> Option<&'a str> {
+    self.0.child_tokens().filter(|it| it.kind == SyntaxKind::Ident).nth(1).map(|it| it.text.as_str())
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+
+  pub fn meta(&self) -> impl Iterator<Item = Meta<'a>> + 'a {
+    children(self.0)
+  }
+
+  pub fn variants(&self) -> impl Iterator<Item = Variant<'a>> + 'a {
+    children(self.0)
+  }
+}
+
+impl<'a> Interface<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+
+  pub fn meta(&self) -> impl Iterator<Item = Meta<'a>> + 'a {
+    children(self.0)
+  }
+
+  pub fn methods(&self) -> impl Iterator<Item = Method<'a>> + 'a {
+    children(self.0)
+  }
+}
+
+impl<'a> Template<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+
+  pub fn meta(&self) -> impl Iterator<Item = Meta<'a>> + 'a {
+    children(self.0)
+  }
+
+  pub fn models(&self) -> impl Iterator<Item = TemplateModel<'a>> + 'a {
+    children(self.0)
+  }
+}
+
+impl<'a> TemplateModel<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn values(&self) -> impl Iterator<Item = TemplateValue<'a>> + 'a {
+    children(self.0)
+  }
+}
+
+impl<'a> TemplateValue<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  /// Number, string or identifier token after `=`
+  pub fn value(&self) -> Option<&'a SyntaxToken> {
+    self.0.child_tokens()
+      .skip_while(|it| it.kind != SyntaxKind::Eq)
+      .find(|it| matches!(it.kind, SyntaxKind::Number | SyntaxKind::String | SyntaxKind::Ident))
+  }
+}
+
+impl<'a> Entity<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn is_required(&self) -> bool {
+    self.0.child_token(SyntaxKind::RequiredKw).is_some()
+  }
+}
+
+impl<'a> Constructor<'a> {
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+
+  pub fn meta(&self) -> impl Iterator<Item = Meta<'a>> + 'a {
+    children(self.0)
+  }
+
+  pub fn fields(&self) -> impl Iterator<Item = Field<'a>> + 'a {
+    children(self.0)
+  }
+}
+
+impl<'a> Field<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn kind(&self) -> Option<TypeRef<'a>> {
+    children(self.0).next()
+  }
+
+  pub fn position(&self) -> Option<i64> {
+    number(self.0)
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+}
+
+impl<'a> Variant<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn value(&self) -> Option<i64> {
+    number(self.0)
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+}
+
+impl<'a> Method<'a> {
+  pub fn is_client(&self) -> bool {
+    self.0.kind == SyntaxKind::ClientMethod
+  }
+
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn id(&self) -> Option<i64> {
+    number(self.0)
+  }
+
+  pub fn params(&self) -> impl Iterator<Item = Param<'a>> + 'a {
+    self.0.child_node(SyntaxKind::ParamList).into_iter().flat_map(children)
+  }
+
+  pub fn doc_comments(&self) -> Vec<&'a str> {
+    doc_comments(self.0)
+  }
+}
+
+impl<'a> Include<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn offset(&self) -> Option<i64> {
+    number(self.0)
+  }
+
+  pub fn overrides(&self) -> impl Iterator<Item = IncludeOverride<'a>> + 'a {
+    children(self.0)
+  }
+}
+
+impl<'a> IncludeOverride<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn id(&self) -> Option<i64> {
+    number(self.0)
+  }
+}
+
+impl<'a> Param<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn kind(&self) -> Option<TypeRef<'a>> {
+    children(self.0).next()
+  }
+}
+
+impl<'a> TypeRef<'a> {
+  pub fn name(&self) -> Option<&'a str> {
+    ident(self.0)
+  }
+
+  pub fn is_nullable(&self) -> bool {
+    self.0.child_token(SyntaxKind::Question).is_some()
+  }
+
+  pub fn params(&self) -> impl Iterator<Item = TypeRef<'a>> + 'a {
+    self.0.child_node(SyntaxKind::GenericArgs).into_iter().flat_map(children)
+  }
+
+  pub fn has_generic_args(&self) -> bool {
+    self.0.child_node(SyntaxKind::GenericArgs).is_some()
+  }
+
+  /// Nested type after `.`
+  pub fn inner(&self) -> Option<TypeRef<'a>> {
+    children(self.0).next()
+  }
+
+  /// Same representation as [crate::type_to_hl], e.g. `Map<String, List<i32>?>?`
+  pub fn to_hl(&self) -> String {
+    let mut builder = self.name().unwrap_or_default().to_owned();
+    if self.has_generic_args() {
+      builder.push_str(&format!("<{}>", self.params().map(|it| it.to_hl()).join(", ")));
+    }
+    if self.is_nullable() {
+      builder.push('?');
+    }
+    if let Some(inner) = self.inner() {
+      builder.push_str(&format!(".{}", inner.to_hl()));
+    }
+    builder
+  }
+}
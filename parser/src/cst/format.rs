@@ -0,0 +1,360 @@
+//! Canonical formatting of `.proto` files, built on the lossless tree so that plain comments,
+//! entities and top-level meta survive unlike with `target::protolang`.
+//!
+//! Rules:
+//! - two space indentation, one item per line, empty blocks are printed as `{}`;
+//! - meta items are moved to the top of their block, keeping their relative order, together with
+//!   the comments above and after them. The comments at the top of a file that a blank line
+//!   separates from the first item stay at the top;
+//! - a blank line separates sections (different item kinds) and top-level declarations, other
+//!   blank lines are kept but collapsed to one;
+//! - single spaces around `=` and after `,` and `:`, no spaces inside generics and parameter lists
+//!   (`Map<String, List<i32>?>?`), `enum Name : repr` keeps spaces around the colon.
+
+use super::{parse, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+
+const INDENT: &str = "  ";
+
+/// Formats the whole file. Files with syntax errors are not touched, the errors are returned
+/// instead.
+pub fn format(input: &str) -> Result<String, Vec<String>> {
+  let parse = parse(input);
+  if !parse.errors.is_empty() {
+    return Err(parse.errors);
+  }
+
+  let mut printer = Printer { out: String::new(), previous: None };
+  printer.block(&parse.root.children, 0, true);
+  Ok(printer.out)
+}
+
+/// Item of a block together with the comments around it
+struct Unit<'a> {
+  /// Floating comments above the item, with a flag if they are preceded by a blank line
+  comments: Vec<(&'a SyntaxToken, bool)>,
+  item: Option<&'a SyntaxNode>,
+  /// Blank line between the last floating comment (or the previous unit) and the item
+  item_blank: bool,
+  trailing: Option<&'a SyntaxToken>,
+}
+
+impl<'a> Unit<'a> {
+  fn blank_before(&self) -> bool {
+    match self.comments.first() {
+      Some((_, blank)) => *blank,
+      None => self.item_blank,
+    }
+  }
+
+  fn kind(&self) -> Option<SyntaxKind> {
+    self.item.map(|it| it.kind)
+  }
+
+  /// Splits off the comments that a blank line separates from the item, as a unit of their own
+  fn take_header(&mut self) -> Option<Unit<'a>> {
+    let end = if self.item_blank { self.comments.len() } else { self.comments.iter().rposition(|(_, blank)| *blank)? };
+    if end == 0 || self.item.is_none() {
+      return None;
+    }
+
+    let comments = self.comments.drain(..end).collect();
+    Some(Unit { comments, item: None, item_blank: false, trailing: None })
+  }
+}
+
+struct Printer {
+  out: String,
+  /// Last significant token printed on the current line
+  previous: Option<SyntaxKind>,
+}
+
+impl Printer {
+  fn block(&mut self, children: &[SyntaxElement], depth: usize, top_level: bool) {
+    let indent = INDENT.repeat(depth);
+    let (units, open_trailing) = collect_units(children, top_level);
+
+    if !top_level {
+      if units.is_empty() && open_trailing.is_none() {
+        self.out.push_str("{}");
+        return;
+      }
+
+      self.out.push('{');
+      if let Some(comment) = open_trailing {
+        self.out.push_str(&format!(" {}", comment.text.trim_end()));
+      }
+      self.out.push('\n');
+    }
+
+    // Stable partition: meta first, then everything else. The file header stays at the top and
+    // floating comments at the end stay there.
+    let mut units = units;
+    let header = if top_level { units.first_mut().and_then(Unit::take_header) } else { None };
+    let (meta, rest): (Vec<_>, Vec<_>) = units.into_iter().partition(|it| it.kind() == Some(SyntaxKind::Meta));
+    let units = header.into_iter().chain(meta).chain(rest).collect::<Vec<_>>();
+
+    let mut previous: Option<Option<SyntaxKind>> = None;
+    for unit in &units {
+      if let Some(previous) = previous {
+        let section_changed = unit.item.is_some() && previous != unit.kind();
+        let top_level_item = top_level && unit.item.is_some() && unit.kind() != Some(SyntaxKind::Meta);
+        if section_changed || top_level_item || unit.blank_before() {
+          self.out.push('\n');
+        }
+      }
+      previous = Some(unit.kind());
+
+      for (index, (comment, blank)) in unit.comments.iter().enumerate() {
+        if index > 0 && *blank {
+          self.out.push('\n');
+        }
+        self.out.push_str(&format!("{}{}\n", indent, comment.text.trim_end()));
+      }
+
+      if let Some(item) = unit.item {
+        if !unit.comments.is_empty() && unit.item_blank {
+          self.out.push('\n');
+        }
+        self.item(item, depth);
+        if let Some(comment) = unit.trailing {
+          self.out.push_str(&format!(" {}", comment.text.trim_end()));
+        }
+        self.out.push('\n');
+      }
+    }
+
+    if !top_level {
+      self.out.push_str(&INDENT.repeat(depth.saturating_sub(1)));
+      self.out.push('}');
+    }
+  }
+
+  fn item(&mut self, node: &SyntaxNode, depth: usize) {
+    let indent = INDENT.repeat(depth);
+
+    // Comments attached to the item by the parser
+    let mut start = 0;
+    for child in &node.children {
+      match child {
+        SyntaxElement::Token(token) if token.kind == SyntaxKind::Whitespace => {}
+        SyntaxElement::Token(token) if token.kind.is_trivia() => {
+          self.out.push_str(&format!("{}{}\n", indent, token.text.trim_end()));
+        }
+        _ => break,
+      }
+      start += 1;
+    }
+
+    self.out.push_str(&indent);
+    self.previous = None;
+
+    let children = &node.children[start..];
+    let mut index = 0;
+    while index < children.len() {
+      match &children[index] {
+        SyntaxElement::Token(token) if token.kind == SyntaxKind::BraceOpen => {
+          let close = children.iter().rposition(|it| it.kind() == SyntaxKind::BraceClose).unwrap_or(children.len());
+          self.out.push(' ');
+          self.block(&children[index + 1..close], depth + 1, false);
+          index = close + 1;
+          continue;
+        }
+        SyntaxElement::Token(token) => self.token(token, node.kind, depth),
+        SyntaxElement::Node(child) => self.inline(child, depth),
+      }
+      index += 1;
+    }
+  }
+
+  /// Prints a node that is a part of a single line (type references, parameter lists)
+  fn inline(&mut self, node: &SyntaxNode, depth: usize) {
+    for child in &node.children {
+      match child {
+        SyntaxElement::Token(token) => self.token(token, node.kind, depth),
+        SyntaxElement::Node(child) => self.inline(child, depth),
+      }
+    }
+  }
+
+  fn token(&mut self, token: &SyntaxToken, parent: SyntaxKind, depth: usize) {
+    match token.kind {
+      SyntaxKind::Whitespace => {}
+      // Comment in the middle of a declaration, continue on the next line
+      SyntaxKind::Comment | SyntaxKind::DocComment => {
+        self.out.push_str(&format!(" {}\n{}", token.text.trim_end(), INDENT.repeat(depth + 1)));
+        self.previous = None;
+      }
+      kind => {
+        if let Some(previous) = self.previous {
+          if needs_space(previous, kind, parent) {
+            self.out.push(' ');
+          }
+        }
+        self.out.push_str(&token.text);
+        self.previous = Some(kind);
+      }
+    }
+  }
+}
+
+fn needs_space(previous: SyntaxKind, next: SyntaxKind, parent: SyntaxKind) -> bool {
+  if next == SyntaxKind::Colon {
+    return parent == SyntaxKind::Enum;
+  }
+  if matches!(previous, SyntaxKind::ParenOpen | SyntaxKind::Lt | SyntaxKind::Dot) {
+    return false;
+  }
+  !matches!(
+    next,
+    SyntaxKind::Semi | SyntaxKind::Comma | SyntaxKind::ParenOpen | SyntaxKind::ParenClose |
+    SyntaxKind::Lt | SyntaxKind::Gt | SyntaxKind::Question | SyntaxKind::Dot
+  )
+}
+
+/// Groups block children into items with their comments. Also returns a comment placed on the
+/// same line as the opening brace.
+fn collect_units(children: &[SyntaxElement], top_level: bool) -> (Vec<Unit<'_>>, Option<&SyntaxToken>) {
+  let mut units: Vec<Unit> = Vec::new();
+  let mut comments = Vec::new();
+  let mut open_trailing = None;
+  let mut newlines = 0;
+
+  for child in children {
+    match child {
+      SyntaxElement::Token(token) if token.kind == SyntaxKind::Whitespace => {
+        newlines += token.text.matches('\n').count();
+      }
+      SyntaxElement::Token(token) if token.kind.is_trivia() => {
+        let last = units.last_mut().filter(|it| it.trailing.is_none());
+        match last {
+          Some(unit) if newlines == 0 && comments.is_empty() => unit.trailing = Some(token),
+          None if !top_level && newlines == 0 && comments.is_empty() && open_trailing.is_none() => open_trailing = Some(token),
+          _ => comments.push((token, newlines >= 2)),
+        }
+        newlines = 0;
+      }
+      SyntaxElement::Node(node) => {
+        units.push(Unit {
+          comments: std::mem::take(&mut comments),
+          item: Some(node),
+          item_blank: newlines >= 2,
+          trailing: None,
+        });
+        newlines = 0;
+      }
+      SyntaxElement::Token(_) => {}
+    }
+  }
+
+  if !comments.is_empty() {
+    units.push(Unit { comments, item: None, item_blank: false, trailing: None });
+  }
+
+  (units, open_trailing)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Also checks that formatting the expected output again changes nothing
+  fn assert_formatted(source: &str, expected: &str) {
+    let formatted = format(source).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(format(&formatted).unwrap(), formatted);
+  }
+
+  #[test]
+  fn format_source() {
+    let source = r#"// File header
+
+meta package = "battle";
+model   SusModel = 1 { // opener
+  client  JKL ( param1 : i8 ,param2:Map < String , List<i32>? >? ) = 3 ; // trailing
+  meta client_name="x";
+      required entity SusEntity;
+  /// Ctor
+  constructor {
+  a : i32 = 1;
+
+
+  b: Foo . Bar = 2;
+  // dangling
+  }
+  include Notifications = 10 { notify = 1; }
+}
+meta other = "y";
+enum E:i32 {  A = 0; B = 1; }
+interface I {}
+"#;
+    let expected = r#"// File header
+
+meta package = "battle";
+meta other = "y";
+
+model SusModel = 1 { // opener
+  meta client_name = "x";
+
+  client JKL(param1: i8, param2: Map<String, List<i32>?>?) = 3; // trailing
+
+  required entity SusEntity;
+
+  /// Ctor
+  constructor {
+    a: i32 = 1;
+
+    b: Foo.Bar = 2;
+    // dangling
+  }
+
+  include Notifications = 10 {
+    notify = 1;
+  }
+}
+
+enum E : i32 {
+  A = 0;
+  B = 1;
+}
+
+interface I {}
+"#;
+    assert_formatted(source, expected);
+    assert!(format("model X = {").is_err());
+  }
+  #[test]
+  fn format_moved_meta_keeps_comments() {
+    // The floating comment above the meta and both trailing comments move with their items
+    assert_formatted(
+      "model M = 1 {\n  client a() = 1; // after a\n\n  // about the meta\n  meta client_name = \"x\"; // trailing meta\n  /// doc b\n  server b() = 2;\n}\n",
+      "model M = 1 {\n  // about the meta\n  meta client_name = \"x\"; // trailing meta\n\n  client a() = 1; // after a\n\n  /// doc b\n  server b() = 2;\n}\n",
+    );
+    assert_formatted(
+      "type T {\n  a: i32 = 1; // a\n  // about meta\n\n  meta client_name = \"T\";\n  b: i32 = 2;\n}\n",
+      "type T {\n  // about meta\n\n  meta client_name = \"T\";\n\n  a: i32 = 1; // a\n  b: i32 = 2;\n}\n",
+    );
+  }
+
+  #[test]
+  fn format_moved_meta_keeps_blank_lines() {
+    // Blank lines between meta items are kept, collapsed to one, and the comment at the end of the
+    // block stays there
+    assert_formatted(
+      "model M = 1 {\n  client a() = 1;\n\n\n  meta a = \"1\";\n  // floating\n\n  meta b = \"2\"; // b\n\n  // last\n}\n",
+      "model M = 1 {\n  meta a = \"1\";\n  // floating\n\n  meta b = \"2\"; // b\n\n  client a() = 1;\n\n  // last\n}\n",
+    );
+  }
+
+  #[test]
+  fn format_moved_meta_keeps_file_header() {
+    assert_formatted(
+      "// header\n\n\nmodel M = 1 {}\n// between\nmeta package = \"x\"; // package\n\n// end\n",
+      "// header\n\n// between\nmeta package = \"x\"; // package\n\nmodel M = 1 {}\n\n// end\n",
+    );
+    // Only the comments separated from the first item by a blank line are the header
+    assert_formatted(
+      "// license\n\n// about M\nmodel M = 1 {}\nmeta package = \"x\";\n",
+      "// license\n\nmeta package = \"x\";\n\n// about M\nmodel M = 1 {}\n",
+    );
+  }
+}
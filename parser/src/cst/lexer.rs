@@ -0,0 +1,53 @@
+use super::{SyntaxKind, SyntaxToken};
+
+/// Splits the input into tokens without dropping anything, concatenating the text of all
+/// returned tokens gives back the input.
+pub fn lex(input: &str) -> Vec<SyntaxToken> {
+  let mut tokens = Vec::new();
+  let mut rest = input;
+
+  while let Some(ch) = rest.chars().next() {
+    let (kind, len) = match ch {
+      ch if ch.is_whitespace() => (SyntaxKind::Whitespace, take_while(rest, char::is_whitespace)),
+      '/' if rest.starts_with("///") => (SyntaxKind::DocComment, take_while(rest, |it| it != '\n')),
+      '/' if rest.starts_with("//") => (SyntaxKind::Comment, take_while(rest, |it| it != '\n')),
+      '"' => match rest[1..].find('"') {
+        Some(end) => (SyntaxKind::String, end + 2),
+        None => (SyntaxKind::ErrorToken, rest.len())
+      },
+      '0'..='9' => (SyntaxKind::Number, take_while(rest, |it| it.is_ascii_digit())),
+      ch if ch.is_ascii_alphabetic() || ch == '_' => {
+        let len = take_while(rest, |it| it.is_ascii_alphanumeric() || it == '_');
+        // Same rule as [crate::tokenizer]: keywords must be followed by whitespace,
+        // so `type: i32` is still a field named "type"
+        let is_keyword_position = rest[len..].starts_with(char::is_whitespace);
+        match SyntaxKind::from_keyword(&rest[..len]) {
+          Some(kind) if is_keyword_position => (kind, len),
+          _ => (SyntaxKind::Ident, len)
+        }
+      }
+      '{' => (SyntaxKind::BraceOpen, 1),
+      '}' => (SyntaxKind::BraceClose, 1),
+      '(' => (SyntaxKind::ParenOpen, 1),
+      ')' => (SyntaxKind::ParenClose, 1),
+      '<' => (SyntaxKind::Lt, 1),
+      '>' => (SyntaxKind::Gt, 1),
+      '=' => (SyntaxKind::Eq, 1),
+      ':' => (SyntaxKind::Colon, 1),
+      ';' => (SyntaxKind::Semi, 1),
+      ',' => (SyntaxKind::Comma, 1),
+      '?' => (SyntaxKind::Question, 1),
+      '.' => (SyntaxKind::Dot, 1),
+      ch => (SyntaxKind::ErrorToken, ch.len_utf8()),
+    };
+
+    tokens.push(SyntaxToken::new(kind, &rest[..len]));
+    rest = &rest[len..];
+  }
+
+  tokens
+}
+
+fn take_while(input: &str, predicate: impl Fn(char) -> bool) -> usize {
+  input.find(|it| !predicate(it)).unwrap_or(input.len())
+}
@@ -0,0 +1,434 @@
+//! Lossless concrete syntax tree.
+//!
+//! Unlike [crate::tokenizer] and [crate::parse_program], this layer keeps every byte of the
+//! source, including whitespace, plain `//` comments and delimiters, so that tools can rewrite
+//! files without destroying hand formatting. `parse(text).root.to_string() == text` always holds,
+//! even for invalid input.
+
+pub mod ast;
+mod format;
+mod lexer;
+mod parser;
+
+use std::fmt::{self, Display, Formatter};
+
+pub use format::format;
+pub use lexer::lex;
+pub use parser::{parse, parse_model_item, Parse};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+  // Trivia
+  Whitespace,
+  Comment,
+  DocComment,
+
+  // Tokens
+  Ident,
+  Number,
+  String,
+  BraceOpen,
+  BraceClose,
+  ParenOpen,
+  ParenClose,
+  Lt,
+  Gt,
+  Eq,
+  Colon,
+  Semi,
+  Comma,
+  Question,
+  Dot,
+  /// Unrecognized character or unterminated string
+  ErrorToken,
+
+  // Keywords
+  MetaKw,
+  ModelKw,
+  TypeKw,
+  EnumKw,
+  EntityKw,
+  InterfaceKw,
+  IncludeKw,
+  TemplateKw,
+  ConstructorKw,
+  ServerKw,
+  ClientKw,
+  RequiredKw,
+  OptionalKw,
+
+  // Nodes
+  SourceFile,
+  Meta,
+  Model,
+  TypeDef,
+  Enum,
+  Interface,
+  Template,
+  TemplateModel,
+  TemplateValue,
+  Entity,
+  Constructor,
+  Field,
+  Variant,
+  ClientMethod,
+  ServerMethod,
+  Include,
+  IncludeOverride,
+  ParamList,
+  Param,
+  TypeRef,
+  GenericArgs,
+  /// Tokens that could not be parsed
+  Error,
+}
+
+impl SyntaxKind {
+  pub fn is_trivia(self) -> bool {
+    matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment | SyntaxKind::DocComment)
+  }
+
+  pub fn from_keyword(text: &str) -> Option<SyntaxKind> {
+    let kind = match text {
+      "meta" => SyntaxKind::MetaKw,
+      "model" => SyntaxKind::ModelKw,
+      "type" => SyntaxKind::TypeKw,
+      "enum" => SyntaxKind::EnumKw,
+      "entity" => SyntaxKind::EntityKw,
+      "interface" => SyntaxKind::InterfaceKw,
+      "include" => SyntaxKind::IncludeKw,
+      "template" => SyntaxKind::TemplateKw,
+      "constructor" => SyntaxKind::ConstructorKw,
+      "server" => SyntaxKind::ServerKw,
+      "client" => SyntaxKind::ClientKw,
+      "required" => SyntaxKind::RequiredKw,
+      "optional" => SyntaxKind::OptionalKw,
+      _ => return None
+    };
+    Some(kind)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+  pub kind: SyntaxKind,
+  pub text: String,
+}
+
+impl SyntaxToken {
+  pub fn new(kind: SyntaxKind, text: impl Into<String>) -> Self {
+    SyntaxToken { kind, text: text.into() }
+  }
+
+  pub fn set_text(&mut self, text: impl Into<String>) {
+    self.text = text.into();
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+  Node(SyntaxNode),
+  Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+  pub fn kind(&self) -> SyntaxKind {
+    match self {
+      SyntaxElement::Node(node) => node.kind,
+      SyntaxElement::Token(token) => token.kind,
+    }
+  }
+
+  pub fn as_node(&self) -> Option<&SyntaxNode> {
+    if let SyntaxElement::Node(node) = self { Some(node) } else { None }
+  }
+
+  pub fn as_token(&self) -> Option<&SyntaxToken> {
+    if let SyntaxElement::Token(token) = self { Some(token) } else { None }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode {
+  pub kind: SyntaxKind,
+  pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+  pub fn new(kind: SyntaxKind, children: Vec<SyntaxElement>) -> Self {
+    SyntaxNode { kind, children }
+  }
+
+  pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+    self.children.iter().filter_map(SyntaxElement::as_node)
+  }
+
+  pub fn child_tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+    self.children.iter().filter_map(SyntaxElement::as_token)
+  }
+
+  /// First direct child node of the given kind
+  pub fn child_node(&self, kind: SyntaxKind) -> Option<&SyntaxNode> {
+    self.child_nodes().find(|it| it.kind == kind)
+  }
+
+  /// First direct child token of the given kind
+  pub fn child_token(&self, kind: SyntaxKind) -> Option<&SyntaxToken> {
+    self.child_tokens().find(|it| it.kind == kind)
+  }
+
+  pub fn child_node_mut(&mut self, kind: SyntaxKind) -> Option<&mut SyntaxNode> {
+    self.children.iter_mut().find_map(|it| match it {
+      SyntaxElement::Node(node) if node.kind == kind => Some(node),
+      _ => None
+    })
+  }
+
+  pub fn child_token_mut(&mut self, kind: SyntaxKind) -> Option<&mut SyntaxToken> {
+    self.children.iter_mut().find_map(|it| match it {
+      SyntaxElement::Token(token) if token.kind == kind => Some(token),
+      _ => None
+    })
+  }
+
+  /// All tokens of the subtree in source order
+  pub fn tokens(&self) -> Vec<&SyntaxToken> {
+    let mut tokens = Vec::new();
+    self.collect_tokens(&mut tokens);
+    tokens
+  }
+
+  fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SyntaxToken>) {
+    for child in &self.children {
+      match child {
+        SyntaxElement::Node(node) => node.collect_tokens(tokens),
+        SyntaxElement::Token(token) => tokens.push(token),
+      }
+    }
+  }
+
+  /// Comments and whitespace before the first significant token of the node
+  pub fn leading_trivia(&self) -> Vec<&SyntaxToken> {
+    self.tokens().into_iter().take_while(|it| it.kind.is_trivia()).collect()
+  }
+
+  pub fn text_len(&self) -> usize {
+    self.tokens().iter().map(|it| it.text.len()).sum()
+  }
+
+  pub fn insert_child(&mut self, index: usize, element: SyntaxElement) {
+    self.children.insert(index, element);
+  }
+
+  pub fn remove_child(&mut self, index: usize) -> SyntaxElement {
+    self.children.remove(index)
+  }
+
+  /// Replaces a direct child. When both the old and the new child are nodes and the new one has
+  /// no leading trivia of its own, comments attached to the old node are moved to the new one.
+  pub fn replace_child(&mut self, index: usize, element: SyntaxElement) -> SyntaxElement {
+    let mut element = element;
+    if let (SyntaxElement::Node(old), SyntaxElement::Node(new)) = (&self.children[index], &mut element) {
+      if new.leading_trivia().is_empty() {
+        let trivia = old.leading_trivia().into_iter().cloned().collect::<Vec<_>>();
+        new.prepend_tokens(trivia);
+      }
+    }
+    std::mem::replace(&mut self.children[index], element)
+  }
+
+  fn prepend_tokens(&mut self, tokens: Vec<SyntaxToken>) {
+    let mut children = tokens.into_iter().map(SyntaxElement::Token).collect::<Vec<_>>();
+    children.append(&mut self.children);
+    self.children = children;
+  }
+
+  /// Inserts an item into a braced block (model, type, enum, interface, template, constructor)
+  /// right before the closing brace, on its own line and indented like the existing items. Every
+  /// line of the item is indented, not only the first one.
+  pub fn push_item(&mut self, mut item: SyntaxNode) {
+    let close = match self.children.iter().rposition(|it| it.kind() == SyntaxKind::BraceClose) {
+      Some(index) => index,
+      None => {
+        self.children.push(SyntaxElement::Node(item));
+        return;
+      }
+    };
+
+    let indent = self.item_indent();
+    item.indent(&indent);
+    let has_close_whitespace = close > 0 && self.children[close - 1].kind() == SyntaxKind::Whitespace;
+    let index = if has_close_whitespace { close - 1 } else { close };
+
+    let mut elements = vec![
+      SyntaxElement::Token(SyntaxToken::new(SyntaxKind::Whitespace, format!("\n{}", indent))),
+      SyntaxElement::Node(item),
+    ];
+    if !has_close_whitespace {
+      let close_indent = indent.strip_suffix("  ").unwrap_or_default();
+      elements.push(SyntaxElement::Token(SyntaxToken::new(SyntaxKind::Whitespace, format!("\n{}", close_indent))));
+    }
+    self.children.splice(index..index, elements);
+  }
+
+  /// Prepends `indent` to every line after the first one, blank lines stay empty
+  fn indent(&mut self, indent: &str) {
+    for child in &mut self.children {
+      match child {
+        SyntaxElement::Node(node) => node.indent(indent),
+        SyntaxElement::Token(token) if token.kind == SyntaxKind::Whitespace && token.text.contains('\n') => {
+          let lines = token.text.split('\n').collect::<Vec<_>>();
+          let mut text = lines[0].to_owned();
+          for (index, line) in lines.iter().enumerate().skip(1) {
+            text.push('\n');
+            if !line.is_empty() || index == lines.len() - 1 {
+              text.push_str(indent);
+            }
+            text.push_str(line);
+          }
+          token.text = text;
+        }
+        SyntaxElement::Token(_) => {}
+      }
+    }
+  }
+
+  /// Indentation of the first item in a block, or two spaces if the block is empty
+  fn item_indent(&self) -> String {
+    for (index, child) in self.children.iter().enumerate() {
+      let node = match child {
+        SyntaxElement::Node(node) => node,
+        SyntaxElement::Token(_) => continue,
+      };
+
+      // Whitespace right before the first significant token, inside the node or before it
+      let trivia = node.leading_trivia();
+      let whitespace = match trivia.last() {
+        Some(token) => Some(*token),
+        None => index.checked_sub(1).and_then(|index| self.children[index].as_token()),
+      };
+      if let Some(whitespace) = whitespace.filter(|it| it.kind == SyntaxKind::Whitespace && it.text.contains('\n')) {
+        return whitespace.text.rsplit('\n').next().unwrap_or_default().to_owned();
+      }
+    }
+    "  ".to_owned()
+  }
+
+  /// Indented tree dump, useful for debugging and tests
+  pub fn debug_tree(&self) -> String {
+    let mut builder = String::new();
+    self.write_debug_tree(&mut builder, 0);
+    builder
+  }
+
+  fn write_debug_tree(&self, builder: &mut String, depth: usize) {
+    builder.push_str(&format!("{}{:?}\n", "  ".repeat(depth), self.kind));
+    for child in &self.children {
+      match child {
+        SyntaxElement::Node(node) => node.write_debug_tree(builder, depth + 1),
+        SyntaxElement::Token(token) => builder.push_str(&format!("{}{:?} {:?}\n", "  ".repeat(depth + 1), token.kind, token.text)),
+      }
+    }
+  }
+}
+
+impl Display for SyntaxNode {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    for child in &self.children {
+      match child {
+        SyntaxElement::Node(node) => write!(f, "{}", node)?,
+        SyntaxElement::Token(token) => write!(f, "{}", token.text)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ast::{AstNode, Item, ModelItem, SourceFile};
+  use super::*;
+
+  const SOURCE: &str = r#"
+meta package = "battle";
+
+// Plain comment, kept by the CST only
+/// Example model
+model   SusModel = 213242343 {
+  meta client_name = "SusModel";
+  required entity SusEntity;
+
+  /// Comment for constructor
+  constructor {
+    CTORFIELD1 : i32 = 1;
+    field2: Map<String, List<i32>?>? = 2;
+  }
+
+  client JKL(param1: i8, param2: Foo.Bar) = 1011412; // trailing
+  include Notifications = 10 { notify = 1; }
+}
+
+enum SusEnum : i32 {
+  A = 0;
+}
+"#;
+
+  #[test]
+  fn round_trip() {
+    let result = parse(SOURCE);
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert_eq!(result.root.to_string(), SOURCE);
+
+    // Invalid input is preserved as well
+    let source = "model X = { client ?? foo( = 1; }\n}} \"unterminated";
+    let result = parse(source);
+    assert!(!result.errors.is_empty());
+    assert_eq!(result.root.to_string(), source);
+  }
+
+  #[test]
+  fn typed_view() {
+    let result = parse(SOURCE);
+    let file = SourceFile::cast(&result.root).unwrap();
+    let model = match file.items().nth(1) {
+      Some(Item::Model(model)) => model,
+      _ => unreachable!()
+    };
+    assert_eq!(model.name(), Some("SusModel"));
+    assert_eq!(model.id(), Some(213242343));
+    assert_eq!(model.doc_comments(), vec!["Example model"]);
+
+    let constructor = model.constructor().unwrap();
+    assert_eq!(constructor.doc_comments(), vec!["Comment for constructor"]);
+    let kinds = constructor.fields().map(|it| it.kind().unwrap().to_hl()).collect::<Vec<_>>();
+    assert_eq!(kinds, vec!["i32", "Map<String, List<i32>?>?"]);
+
+    let method = model.methods().next().unwrap();
+    assert!(method.is_client());
+    assert_eq!(method.params().map(|it| it.kind().unwrap().to_hl()).collect::<Vec<_>>(), vec!["i8", "Foo.Bar"]);
+
+    let include = model.items().find_map(|it| if let ModelItem::Include(include) = it { Some(include) } else { None }).unwrap();
+    assert_eq!(include.offset(), Some(10));
+    assert_eq!(include.overrides().map(|it| (it.name().unwrap(), it.id().unwrap())).collect::<Vec<_>>(), vec![("notify", 1)]);
+  }
+
+  #[test]
+  fn edit() {
+    let mut root = parse(SOURCE).root;
+    let model = root.child_node_mut(SyntaxKind::Model).unwrap();
+    model.child_token_mut(SyntaxKind::Number).unwrap().set_text("42");
+
+    let item = parse_model_item("/// Added\nserver added() = 5;").root;
+    model.push_item(item);
+
+    let index = model.children.iter().position(|it| it.kind() == SyntaxKind::Constructor).unwrap();
+    let replacement = parse_model_item("constructor {\n  }").root;
+    model.replace_child(index, SyntaxElement::Node(replacement));
+
+    let expected = SOURCE
+      .replace("213242343", "42")
+      .replace("  constructor {\n    CTORFIELD1 : i32 = 1;\n    field2: Map<String, List<i32>?>? = 2;\n  }", "  constructor {\n  }")
+      .replace("{ notify = 1; }\n}", "{ notify = 1; }\n  /// Added\n  server added() = 5;\n}");
+    assert_eq!(root.to_string(), expected);
+  }
+}
@@ -0,0 +1,438 @@
+use super::{lex, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+
+#[derive(Debug)]
+pub struct Parse {
+  pub root: SyntaxNode,
+  pub errors: Vec<String>,
+}
+
+/// Parses a whole file into a [SyntaxKind::SourceFile] node. Never fails, unexpected tokens are
+/// wrapped into [SyntaxKind::Error] nodes and reported in [Parse::errors].
+pub fn parse(input: &str) -> Parse {
+  let mut parser = Parser::new(lex(input));
+  parser.source_file();
+  parser.finish()
+}
+
+/// Parses a single model body item (meta, entity, constructor, method or include), for building
+/// nodes to insert with [SyntaxNode::push_item].
+pub fn parse_model_item(input: &str) -> Parse {
+  let mut parser = Parser::new(lex(input));
+  if parser.current().is_some() {
+    parser.model_item();
+  }
+  while parser.current().is_some() {
+    parser.error_item();
+  }
+
+  let Parse { root, mut errors } = parser.finish();
+  let mut nodes = root.children.into_iter().filter_map(|it| match it {
+    SyntaxElement::Node(node) => Some(node),
+    SyntaxElement::Token(_) => None,
+  });
+  let root = match (nodes.next(), nodes.next()) {
+    (Some(node), None) => node,
+    (node, _) => {
+      errors.push("expected a single model item".to_owned());
+      node.unwrap_or_else(|| SyntaxNode::new(SyntaxKind::Error, Vec::new()))
+    }
+  };
+
+  Parse { root, errors }
+}
+
+struct Parser {
+  tokens: Vec<SyntaxToken>,
+  position: usize,
+  stack: Vec<SyntaxNode>,
+  errors: Vec<String>,
+}
+
+impl Parser {
+  fn new(tokens: Vec<SyntaxToken>) -> Self {
+    Parser {
+      tokens,
+      position: 0,
+      stack: vec![SyntaxNode::new(SyntaxKind::SourceFile, Vec::new())],
+      errors: Vec::new(),
+    }
+  }
+
+  fn finish(mut self) -> Parse {
+    self.eat_trivia();
+    while self.stack.len() > 1 {
+      self.finish_node();
+    }
+
+    Parse {
+      root: self.stack.pop().unwrap(),
+      errors: self.errors,
+    }
+  }
+
+  fn significant_index(&self) -> usize {
+    let mut index = self.position;
+    while index < self.tokens.len() && self.tokens[index].kind.is_trivia() {
+      index += 1;
+    }
+    index
+  }
+
+  fn current(&self) -> Option<SyntaxKind> {
+    self.tokens.get(self.significant_index()).map(|it| it.kind)
+  }
+
+  fn at(&self, kind: SyntaxKind) -> bool {
+    self.current() == Some(kind)
+  }
+
+  fn push_token(&mut self) {
+    let token = self.tokens[self.position].clone();
+    self.position += 1;
+    self.stack.last_mut().unwrap().children.push(SyntaxElement::Token(token));
+  }
+
+  fn eat_trivia(&mut self) {
+    while self.position < self.tokens.len() && self.tokens[self.position].kind.is_trivia() {
+      self.push_token();
+    }
+  }
+
+  fn bump(&mut self) {
+    self.eat_trivia();
+    if self.position < self.tokens.len() {
+      self.push_token();
+    }
+  }
+
+  fn eat(&mut self, kind: SyntaxKind) -> bool {
+    if self.at(kind) {
+      self.bump();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, kind: SyntaxKind) -> bool {
+    if self.eat(kind) {
+      return true;
+    }
+
+    let found = self.tokens.get(self.significant_index()).map(|it| it.text.clone());
+    self.errors.push(format!("expected {:?}, found {:?}", kind, found.as_deref().unwrap_or("end of file")));
+    false
+  }
+
+  /// Starts a node, trivia before it goes to the parent
+  fn start(&mut self, kind: SyntaxKind) {
+    self.eat_trivia();
+    self.stack.push(SyntaxNode::new(kind, Vec::new()));
+  }
+
+  /// Starts an item node. Comments directly above the item (not separated by a blank line)
+  /// become a part of it, so they move together with the item when the tree is edited.
+  /// Trailing comments on the line of the previous item are left in the parent.
+  fn start_item(&mut self, kind: SyntaxKind) {
+    let significant = self.significant_index();
+    let mut split = significant;
+    let mut index = significant;
+    while index > self.position {
+      let token = &self.tokens[index - 1];
+      match token.kind {
+        SyntaxKind::Comment | SyntaxKind::DocComment => {
+          let starts_line = index == 1 || {
+            let previous = &self.tokens[index - 2];
+            previous.kind == SyntaxKind::Whitespace && previous.text.contains('\n')
+          };
+          if !starts_line {
+            break;
+          }
+          split = index - 1;
+        }
+        SyntaxKind::Whitespace if token.text.matches('\n').count() < 2 => {}
+        _ => break,
+      }
+      index -= 1;
+    }
+
+    while self.position < split {
+      self.push_token();
+    }
+    self.stack.push(SyntaxNode::new(kind, Vec::new()));
+  }
+
+  fn finish_node(&mut self) {
+    let node = self.stack.pop().unwrap();
+    self.stack.last_mut().unwrap().children.push(SyntaxElement::Node(node));
+  }
+
+  fn error_item(&mut self) {
+    let found = self.tokens.get(self.significant_index()).map(|it| it.text.clone()).unwrap_or_default();
+    self.errors.push(format!("unexpected {:?}", found));
+    self.start(SyntaxKind::Error);
+    self.bump();
+    self.finish_node();
+  }
+
+  fn block(&mut self, item: fn(&mut Parser)) {
+    if !self.expect(SyntaxKind::BraceOpen) {
+      return;
+    }
+
+    while let Some(kind) = self.current() {
+      if kind == SyntaxKind::BraceClose {
+        break;
+      }
+      item(self);
+    }
+
+    self.expect(SyntaxKind::BraceClose);
+  }
+
+  fn source_file(&mut self) {
+    while let Some(kind) = self.current() {
+      match kind {
+        SyntaxKind::MetaKw => self.meta(),
+        SyntaxKind::ModelKw => self.model(),
+        SyntaxKind::TypeKw => self.type_def(),
+        SyntaxKind::EnumKw => self.enum_def(),
+        SyntaxKind::InterfaceKw => self.interface(),
+        SyntaxKind::TemplateKw => self.template(),
+        _ => self.error_item(),
+      }
+    }
+  }
+
+  fn meta(&mut self) {
+    self.start_item(SyntaxKind::Meta);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    self.expect(SyntaxKind::Eq);
+    self.expect(SyntaxKind::String);
+    self.expect(SyntaxKind::Semi);
+    self.finish_node();
+  }
+
+  fn model(&mut self) {
+    self.start_item(SyntaxKind::Model);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    self.expect(SyntaxKind::Eq);
+    self.expect(SyntaxKind::Number);
+    self.block(Parser::model_item);
+    self.finish_node();
+  }
+
+  fn model_item(&mut self) {
+    match self.current() {
+      Some(SyntaxKind::MetaKw) => self.meta(),
+      Some(SyntaxKind::RequiredKw | SyntaxKind::OptionalKw | SyntaxKind::EntityKw) => self.entity(),
+      Some(SyntaxKind::ConstructorKw) => self.constructor(),
+      Some(SyntaxKind::ClientKw) => self.method(SyntaxKind::ClientMethod),
+      Some(SyntaxKind::ServerKw) => self.method(SyntaxKind::ServerMethod),
+      Some(SyntaxKind::IncludeKw) => self.include(),
+      _ => self.error_item(),
+    }
+  }
+
+  fn entity(&mut self) {
+    self.start_item(SyntaxKind::Entity);
+    if !self.eat(SyntaxKind::RequiredKw) {
+      self.eat(SyntaxKind::OptionalKw);
+    }
+    self.expect(SyntaxKind::EntityKw);
+    self.expect(SyntaxKind::Ident);
+    self.expect(SyntaxKind::Semi);
+    self.finish_node();
+  }
+
+  fn constructor(&mut self) {
+    self.start_item(SyntaxKind::Constructor);
+    self.bump();
+    self.block(Parser::field_item);
+    self.finish_node();
+  }
+
+  fn field_item(&mut self) {
+    match self.current() {
+      Some(SyntaxKind::MetaKw) => self.meta(),
+      Some(SyntaxKind::Ident) => {
+        self.start_item(SyntaxKind::Field);
+        self.bump();
+        self.expect(SyntaxKind::Colon);
+        self.type_ref();
+        self.expect(SyntaxKind::Eq);
+        self.expect(SyntaxKind::Number);
+        self.expect(SyntaxKind::Semi);
+        self.finish_node();
+      }
+      _ => self.error_item(),
+    }
+  }
+
+  fn method(&mut self, kind: SyntaxKind) {
+    self.start_item(kind);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    self.param_list();
+    self.expect(SyntaxKind::Eq);
+    self.expect(SyntaxKind::Number);
+    self.expect(SyntaxKind::Semi);
+    self.finish_node();
+  }
+
+  fn param_list(&mut self) {
+    self.start(SyntaxKind::ParamList);
+    if self.expect(SyntaxKind::ParenOpen) {
+      while let Some(kind) = self.current() {
+        match kind {
+          SyntaxKind::ParenClose => break,
+          SyntaxKind::Ident => {
+            self.start(SyntaxKind::Param);
+            self.bump();
+            self.expect(SyntaxKind::Colon);
+            self.type_ref();
+            self.finish_node();
+
+            if !self.eat(SyntaxKind::Comma) {
+              break;
+            }
+          }
+          _ => break,
+        }
+      }
+      self.expect(SyntaxKind::ParenClose);
+    }
+    self.finish_node();
+  }
+
+  fn type_ref(&mut self) {
+    self.start(SyntaxKind::TypeRef);
+    self.expect(SyntaxKind::Ident);
+    if self.at(SyntaxKind::Lt) {
+      self.generic_args();
+    }
+    self.eat(SyntaxKind::Question);
+    if self.eat(SyntaxKind::Dot) {
+      self.type_ref();
+    }
+    self.finish_node();
+  }
+
+  fn generic_args(&mut self) {
+    self.start(SyntaxKind::GenericArgs);
+    self.bump();
+    while self.at(SyntaxKind::Ident) {
+      self.type_ref();
+      if !self.eat(SyntaxKind::Comma) {
+        break;
+      }
+    }
+    self.expect(SyntaxKind::Gt);
+    self.finish_node();
+  }
+
+  fn include(&mut self) {
+    self.start_item(SyntaxKind::Include);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    if self.eat(SyntaxKind::Eq) {
+      self.expect(SyntaxKind::Number);
+    }
+    if self.at(SyntaxKind::BraceOpen) {
+      self.block(|parser| match parser.current() {
+        Some(SyntaxKind::Ident) => {
+          parser.start_item(SyntaxKind::IncludeOverride);
+          parser.bump();
+          parser.expect(SyntaxKind::Eq);
+          parser.expect(SyntaxKind::Number);
+          parser.expect(SyntaxKind::Semi);
+          parser.finish_node();
+        }
+        _ => parser.error_item(),
+      });
+    } else {
+      self.expect(SyntaxKind::Semi);
+    }
+    self.finish_node();
+  }
+
+  fn type_def(&mut self) {
+    self.start_item(SyntaxKind::TypeDef);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    self.block(Parser::field_item);
+    self.finish_node();
+  }
+
+  fn enum_def(&mut self) {
+    self.start_item(SyntaxKind::Enum);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    self.expect(SyntaxKind::Colon);
+    self.expect(SyntaxKind::Ident);
+    self.block(|parser| match parser.current() {
+      Some(SyntaxKind::MetaKw) => parser.meta(),
+      Some(SyntaxKind::Ident) => {
+        parser.start_item(SyntaxKind::Variant);
+        parser.bump();
+        parser.expect(SyntaxKind::Eq);
+        parser.expect(SyntaxKind::Number);
+        parser.expect(SyntaxKind::Semi);
+        parser.finish_node();
+      }
+      _ => parser.error_item(),
+    });
+    self.finish_node();
+  }
+
+  fn interface(&mut self) {
+    self.start_item(SyntaxKind::Interface);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    self.block(|parser| match parser.current() {
+      Some(SyntaxKind::MetaKw) => parser.meta(),
+      Some(SyntaxKind::ClientKw) => parser.method(SyntaxKind::ClientMethod),
+      Some(SyntaxKind::ServerKw) => parser.method(SyntaxKind::ServerMethod),
+      _ => parser.error_item(),
+    });
+    self.finish_node();
+  }
+
+  fn template(&mut self) {
+    self.start_item(SyntaxKind::Template);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    self.block(|parser| match parser.current() {
+      Some(SyntaxKind::MetaKw) => parser.meta(),
+      Some(SyntaxKind::ModelKw) => parser.template_model(),
+      _ => parser.error_item(),
+    });
+    self.finish_node();
+  }
+
+  fn template_model(&mut self) {
+    self.start_item(SyntaxKind::TemplateModel);
+    self.bump();
+    self.expect(SyntaxKind::Ident);
+    if self.at(SyntaxKind::BraceOpen) {
+      self.block(|parser| match parser.current() {
+        Some(SyntaxKind::Ident) => {
+          parser.start_item(SyntaxKind::TemplateValue);
+          parser.bump();
+          parser.expect(SyntaxKind::Eq);
+          if !parser.eat(SyntaxKind::Number) && !parser.eat(SyntaxKind::String) {
+            parser.expect(SyntaxKind::Ident);
+          }
+          parser.expect(SyntaxKind::Semi);
+          parser.finish_node();
+        }
+        _ => parser.error_item(),
+      });
+    } else {
+      self.expect(SyntaxKind::Semi);
+    }
+    self.finish_node();
+  }
+}
@@ -0,0 +1,117 @@
+//! Problems found in the schema tree, collected instead of stopping at the first one
+
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+use crate::module::ModuleError;
+use crate::span::Span;
+use crate::workspace::WorkspaceError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum Severity {
+  Warning,
+  Error,
+}
+
+impl Display for Severity {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Severity::Warning => write!(f, "warning"),
+      Severity::Error => write!(f, "error"),
+    }
+  }
+}
+
+/// Suggested change, shown to the user and passed on to editors and code review tools
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Fix {
+  pub message: String,
+  /// Replaces the text of [Diagnostic::span], if the fix can be applied mechanically
+  pub replacement: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diagnostic {
+  pub severity: Severity,
+  /// Stable kebab-case identifier, e.g. `syntax` or a lint name
+  pub code: String,
+  /// File the diagnostic is about, if any
+  pub path: Option<PathBuf>,
+  /// With 0-based line and column, `end` is the offset of the last character
+  pub span: Option<Span>,
+  /// Description without the location
+  pub message: String,
+  pub fix: Option<Fix>,
+}
+
+impl Diagnostic {
+  pub fn error(code: &str, message: String) -> Diagnostic {
+    Diagnostic { severity: Severity::Error, code: code.to_owned(), path: None, span: None, message, fix: None }
+  }
+
+  pub fn warning(code: &str, message: String) -> Diagnostic {
+    Diagnostic { severity: Severity::Warning, code: code.to_owned(), path: None, span: None, message, fix: None }
+  }
+
+  pub fn at(mut self, path: PathBuf, span: Option<Span>) -> Diagnostic {
+    self.path = Some(path);
+    self.span = span;
+    self
+  }
+
+  pub fn fix(mut self, message: String, replacement: Option<String>) -> Diagnostic {
+    self.fix = Some(Fix { message, replacement });
+    self
+  }
+}
+
+/// `error[syntax]: schema/Tank.proto:3:5: message`, lines and columns are 1-based
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}[{}]: ", self.severity, self.code)?;
+    if let Some(path) = &self.path {
+      write!(f, "{}", path.display())?;
+      if let Some(span) = self.span {
+        write!(f, ":{}:{}", span.line + 1, span.column + 1)?;
+      }
+      write!(f, ": ")?;
+    }
+    write!(f, "{}", self.message)?;
+    if let Some(fix) = &self.fix {
+      write!(f, " ({})", fix.message)?;
+    }
+    Ok(())
+  }
+}
+
+impl From<&WorkspaceError> for Diagnostic {
+  fn from(error: &WorkspaceError) -> Self {
+    let code = match error {
+      WorkspaceError::Io { .. } => "io",
+      WorkspaceError::Discovery(_) => "discovery",
+      WorkspaceError::Syntax { error, .. } => error.code(),
+      WorkspaceError::Descriptor { .. } => "module-descriptor",
+      WorkspaceError::Duplicate { .. } => "duplicate",
+    };
+    let span = match error {
+      WorkspaceError::Syntax { error, .. } => error.span(),
+      _ => None,
+    };
+    Diagnostic { path: error.path().map(|it| it.to_path_buf()), span, ..Diagnostic::error(code, error.message()) }
+  }
+}
+
+impl From<&ModuleError> for Diagnostic {
+  fn from(error: &ModuleError) -> Self {
+    let code = match error {
+      ModuleError::UnknownModule { .. } => "unknown-module",
+      ModuleError::Orphan(_) => "orphan",
+      ModuleError::NotExported { .. } => "not-exported",
+      ModuleError::UndeclaredDependency { .. } => "undeclared-dependency",
+    };
+    Diagnostic { path: error.path().map(|it| it.to_path_buf()), ..Diagnostic::error(code, error.message()) }
+  }
+}
@@ -0,0 +1,193 @@
+//! Input discovery shared by the schema loader, the formatter and the ActionScript importer.
+//!
+//! A file under the root is an input if, in order:
+//! - no path component is hidden (starts with `.`),
+//! - it is not ignored by a [IGNORE_FILE] in its directory or a parent directory below the root,
+//! - it does not match the exclude globs, [DEFAULT_EXCLUDE] followed by the configured ones,
+//! - it matches an include glob, if any are given,
+//! - it has the wanted extension.
+//!
+//! Globs and ignore files use the gitignore syntax and are relative to the root, so a later
+//! `!excluded/` exclude re-includes the default exclusion.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+
+pub const IGNORE_FILE: &str = ".protolangignore";
+pub const DEFAULT_EXCLUDE: [&str; 1] = ["excluded/"];
+
+#[derive(Debug)]
+pub enum DiscoveryError {
+  /// Include or exclude glob that does not parse
+  Glob {
+    glob: String,
+    error: ignore::Error,
+  },
+  /// Unreadable directory or ignore file, the error includes the path
+  Walk(ignore::Error),
+}
+
+impl Display for DiscoveryError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      DiscoveryError::Glob { glob, error } => write!(f, "invalid glob '{}': {}", glob, error),
+      DiscoveryError::Walk(error) => write!(f, "{}", error),
+    }
+  }
+}
+
+impl Error for DiscoveryError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      DiscoveryError::Glob { error, .. } => Some(error),
+      DiscoveryError::Walk(error) => Some(error),
+    }
+  }
+}
+
+/// Gitignore-style glob list, relative to a root
+#[derive(Debug, Clone)]
+pub struct Globs(Gitignore);
+
+impl Globs {
+  pub fn new(root: &Path, globs: &[String]) -> Result<Globs, DiscoveryError> {
+    let mut builder = GitignoreBuilder::new(root);
+    for glob in globs {
+      builder.add_line(None, glob).map_err(|error| DiscoveryError::Glob { glob: glob.clone(), error })?;
+    }
+    builder.build().map(Globs).map_err(|error| DiscoveryError::Glob { glob: globs.join(", "), error })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  /// Whether the last glob matching `path` or one of its parent directories is not a negation
+  pub fn is_match(&self, path: &Path, is_dir: bool) -> bool {
+    self.0.matched_path_or_any_parents(path, is_dir).is_ignore()
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Discovery {
+  pub extension: String,
+  pub include: Vec<String>,
+  /// Applied after [DEFAULT_EXCLUDE]
+  pub exclude: Vec<String>,
+  /// Whether [IGNORE_FILE] files are read
+  pub ignore_files: bool,
+  /// Files returned regardless of the extension and the include globs, e.g. module descriptors
+  pub file_names: Vec<String>,
+}
+
+impl Discovery {
+  pub fn new(extension: &str) -> Discovery {
+    Discovery {
+      extension: extension.to_owned(),
+      include: Vec::new(),
+      exclude: Vec::new(),
+      ignore_files: true,
+      file_names: Vec::new(),
+    }
+  }
+
+  pub fn include(mut self, include: impl IntoIterator<Item = String>) -> Discovery {
+    self.include.extend(include);
+    self
+  }
+
+  pub fn exclude(mut self, exclude: impl IntoIterator<Item = String>) -> Discovery {
+    self.exclude.extend(exclude);
+    self
+  }
+
+  pub fn ignore_files(mut self, ignore_files: bool) -> Discovery {
+    self.ignore_files = ignore_files;
+    self
+  }
+
+  pub fn file_name(mut self, file_name: &str) -> Discovery {
+    self.file_names.push(file_name.to_owned());
+    self
+  }
+
+  /// Paths of the inputs under `root`, relative to it and sorted
+  pub fn walk(&self, root: &Path) -> Result<Vec<PathBuf>, DiscoveryError> {
+    let exclude = DEFAULT_EXCLUDE.iter().map(|it| (*it).to_owned()).chain(self.exclude.iter().cloned()).collect::<Vec<_>>();
+    let exclude = Globs::new(root, &exclude)?;
+    let include = Globs::new(root, &self.include)?;
+
+    let mut builder = WalkBuilder::new(root);
+    builder
+      .standard_filters(false)
+      .hidden(true)
+      .sort_by_file_name(|a, b| a.cmp(b));
+    if self.ignore_files {
+      builder.add_custom_ignore_filename(IGNORE_FILE);
+    }
+    // Excluded directories are not descended into
+    let filter_root = root.to_path_buf();
+    builder.filter_entry(move |entry| {
+      let path = entry.path();
+      path == filter_root || !exclude.is_match(path, entry.file_type().is_some_and(|it| it.is_dir()))
+    });
+
+    let mut paths = Vec::new();
+    for entry in builder.build() {
+      let entry = entry.map_err(DiscoveryError::Walk)?;
+      if !entry.file_type().is_some_and(|it| it.is_file()) {
+        continue;
+      }
+
+      let path = entry.path();
+      let relative_path = path.strip_prefix(root).unwrap();
+      let file_name = relative_path.file_name().unwrap_or_default();
+      if self.file_names.iter().any(|it| file_name == it.as_str()) {
+        paths.push(relative_path.to_path_buf());
+        continue;
+      }
+
+      if path.extension().is_none_or(|it| it != self.extension.as_str()) {
+        continue;
+      }
+      if !include.is_empty() && !include.is_match(path, false) {
+        continue;
+      }
+      paths.push(relative_path.to_path_buf());
+    }
+    Ok(paths)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use itertools::Itertools;
+
+  use super::*;
+  use crate::write_tree;
+
+  #[test]
+  fn discovery() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let files = [
+      "module.yaml", "Team.proto", "notes.txt", ".cache/Cached.proto", "excluded/Old.proto",
+      "battle/Tank.proto", "battle/TankTest.proto", "battle/ignored/Hull.proto", "lobby/Chat.proto",
+    ];
+    write_tree(root, &files.map(|it| (it, "")));
+    write_tree(&root.join("battle"), &[(IGNORE_FILE, "ignored/\n")]);
+
+    let walk = |discovery: Discovery| discovery.walk(root).unwrap().iter().map(|it| it.to_string_lossy().replace('\\', "/")).collect_vec();
+    assert_eq!(walk(Discovery::new("proto")), vec!["Team.proto", "battle/Tank.proto", "battle/TankTest.proto", "lobby/Chat.proto"]);
+    assert_eq!(walk(Discovery::new("proto").ignore_files(false)), vec!["Team.proto", "battle/Tank.proto", "battle/TankTest.proto", "battle/ignored/Hull.proto", "lobby/Chat.proto"]);
+    assert_eq!(walk(Discovery::new("proto").exclude(["*Test.proto".to_owned(), "!excluded/".to_owned()])), vec!["Team.proto", "battle/Tank.proto", "excluded/Old.proto", "lobby/Chat.proto"]);
+    assert_eq!(walk(Discovery::new("proto").include(["battle/".to_owned()]).file_name("module.yaml")), vec!["battle/Tank.proto", "battle/TankTest.proto", "module.yaml"]);
+
+    let result = Discovery::new("proto").exclude(["{battle".to_owned()]).walk(root);
+    assert!(result.is_err());
+  }
+}
@@ -1,4 +1,5 @@
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Model {
   pub name: String,
   pub id: i64,
@@ -6,11 +7,14 @@ pub struct Model {
   // pub entities: ModelItem,
   pub client_methods: Vec<ClientMethod>,
   pub server_methods: Vec<ServerMethod>,
+  /// Names of the included interfaces, whose methods are already merged into the methods above
+  pub includes: Vec<String>,
   pub meta: Vec<Meta>,
   pub comments: Vec<String>
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModelConstructor {
   pub fields: Vec<Field>,
   pub meta: Vec<Meta>,
@@ -18,6 +22,7 @@ pub struct ModelConstructor {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Field {
   pub name: String,
   pub kind: String,
@@ -26,7 +31,8 @@ pub struct Field {
   pub comments: Vec<String>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClientMethod {
   pub name: String,
   pub id: i64,
@@ -34,7 +40,8 @@ pub struct ClientMethod {
   pub comments: Vec<String>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ServerMethod {
   pub name: String,
   pub id: i64,
@@ -42,14 +49,61 @@ pub struct ServerMethod {
   pub comments: Vec<String>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Param {
   pub name: String,
   pub kind: String,
   pub codec: String
 }
 
+/// Method set that models can include, see [crate::IncludeDeclaration]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interface {
+  pub name: String,
+  pub client_methods: Vec<ClientMethod>,
+  pub server_methods: Vec<ServerMethod>,
+  pub meta: Vec<Meta>,
+  pub comments: Vec<String>
+}
+
+/// Game object template, see [crate::TemplateDeclaration]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Template {
+  pub name: String,
+  pub models: Vec<TemplateModel>,
+  pub meta: Vec<Meta>,
+  pub comments: Vec<String>
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TemplateModel {
+  pub name: String,
+  /// Fixed constructor values by field name
+  pub values: Vec<TemplateValue>,
+  pub comments: Vec<String>
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TemplateValue {
+  pub name: String,
+  pub value: Value
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+  Number(i64),
+  String(String),
+  Ident(String)
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Type {
   pub name: String,
   pub fields: Vec<Field>,
@@ -58,6 +112,7 @@ pub struct Type {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Enum {
   pub name: String,
   pub repr: String,
@@ -67,6 +122,7 @@ pub struct Enum {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variant {
   pub name: String,
   pub value: i64,
@@ -74,7 +130,35 @@ pub struct Variant {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Meta {
   pub key: String,
   pub value: String
 }
+
+/// Lowered top-level item of a file, see [crate::ProgramItem]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Definition {
+  Meta(Meta),
+  Model(Model),
+  Type(Type),
+  Enum(Enum),
+  Interface(Interface),
+  Template(Template)
+}
+
+impl Definition {
+  /// Declared name, or the key for meta
+  pub fn name(&self) -> &str {
+    match self {
+      Definition::Meta(meta) => &meta.key,
+      Definition::Model(model) => &model.name,
+      Definition::Type(type_def) => &type_def.name,
+      Definition::Enum(enum_def) => &enum_def.name,
+      Definition::Interface(interface) => &interface.name,
+      Definition::Template(template) => &template.name,
+    }
+  }
+}
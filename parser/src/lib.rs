@@ -1,32 +1,77 @@
 pub mod span;
 pub mod hl;
+pub mod cst;
+pub mod visit;
+pub mod check;
+pub mod diagnostic;
+pub mod discovery;
+pub mod module;
+pub mod workspace;
 
 use std::{iter, slice::Iter};
-use std::collections::HashSet;
-use std::sync::Mutex;
+use std::collections::{HashMap, HashSet};
 
 use itertools::{Itertools, MultiPeek, PeekingNext};
-use once_cell::sync::Lazy;
 use span::{Positioned, Span};
-use tracing::{error, trace, warn};
+use tracing::trace;
 use crate::hl::Meta;
 
-pub static ENUM_TYPES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
-
 #[derive(Debug)]
 pub struct SyntaxError {
+  code: &'static str,
   message: String,
+  span: Option<Span>,
 }
 
 impl SyntaxError {
   fn new(message: String) -> Self {
     SyntaxError {
-      message
+      code: "syntax",
+      message,
+      span: None,
+    }
+  }
+
+  fn at(span: Span, message: String) -> Self {
+    SyntaxError {
+      code: "syntax",
+      message,
+      span: Some(span),
     }
   }
+
+  fn with_code(mut self, code: &'static str) -> Self {
+    self.code = code;
+    self
+  }
+
+  /// Diagnostic code, `syntax` unless the error is about a more specific rule
+  pub fn code(&self) -> &'static str {
+    self.code
+  }
+
+  /// Location of the offending token, see [Span::locate]
+  pub fn span(&self) -> Option<Span> {
+    self.span
+  }
+
+  /// Recomputes the line and column of the span from the parsed source
+  pub fn locate(mut self, content: &str) -> Self {
+    self.span = self.span.map(|it| it.locate(content));
+    self
+  }
+}
+
+impl std::fmt::Display for SyntaxError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.message)
+  }
 }
 
+impl std::error::Error for SyntaxError {}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Delimiter {
   BraceOpen,
   BraceClose,
@@ -35,17 +80,22 @@ pub enum Delimiter {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Comment {
   LineDoc(String)
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
   Meta,
   Model,
   Type,
   Enum,
   Entity,
+  Interface,
+  Include,
+  Template,
   Constructor,
   Server,
   Client,
@@ -99,7 +149,7 @@ impl<'a, T: Iterator<Item = (usize, char)>> PeekNum<'a, T> for MultiPeek<T> {
 
   fn consume_num(&mut self, n: usize) {
     for _ in 0..n {
-      if let Some(_) = self.next() {} else {
+      if self.next().is_none() {
         break; // Break if the iterator ends before peeking n characters
       }
     }
@@ -144,7 +194,7 @@ pub fn tokenizer(input: &str) -> Result<Vec<Positioned<Token>>, SyntaxError> {
 
     if ch == '/' && iter.peek_num("//".len()) == "//" {
       let mut comment = String::new();
-      while let Some((_, ch)) = iter.next() {
+      for (_, ch) in iter.by_ref() {
         comment.push(ch);
         column += 1;
         if ch == '\n' {
@@ -159,7 +209,7 @@ pub fn tokenizer(input: &str) -> Result<Vec<Positioned<Token>>, SyntaxError> {
     }
 
     if ch == '/' && iter.peek_num("/".len()) == "/" {
-      while let Some((_, ch)) = iter.next() {
+      for (_, ch) in iter.by_ref() {
         column += 1;
         if ch == '\n' {
           line += 0;
@@ -181,6 +231,10 @@ pub fn tokenizer(input: &str) -> Result<Vec<Positioned<Token>>, SyntaxError> {
         let span = Span { start: pos, end: pos + "odel".len(), line, column };
         tokens.push(Positioned::new(Token::Model, span))
       }
+      't' if iter.peek_num("emplate ".len()) == "emplate " => {
+        iter.consume_num("emplate".len());
+        tokens.push(Positioned::new(Token::Template, Span { start: pos, end: pos + "emplate".len(), line, column }));
+      }
       't' if iter.peek_num("ype ".len()) == "ype " => {
         iter.consume_num("ype".len());
         let span = Span { start: pos, end: pos + "ype".len(), line, column };
@@ -195,6 +249,14 @@ pub fn tokenizer(input: &str) -> Result<Vec<Positioned<Token>>, SyntaxError> {
         iter.consume_num("ntity".len());
         tokens.push(Positioned::new(Token::Entity, Span { start: pos, end: pos + "ntity".len(), line, column }));
       }
+      'i' if iter.peek_num("nterface ".len()) == "nterface " => {
+        iter.consume_num("nterface".len());
+        tokens.push(Positioned::new(Token::Interface, Span { start: pos, end: pos + "nterface".len(), line, column }));
+      }
+      'i' if iter.peek_num("nclude ".len()) == "nclude " => {
+        iter.consume_num("nclude".len());
+        tokens.push(Positioned::new(Token::Include, Span { start: pos, end: pos + "nclude".len(), line, column }));
+      }
       'c' if iter.peek_num("onstructor ".len()) == "onstructor " => {
         iter.consume_num("onstructor".len());
         tokens.push(Positioned::new(Token::Constructor, Span { start: pos, end: pos + "onstructor".len(), line, column }));
@@ -246,11 +308,10 @@ pub fn tokenizer(input: &str) -> Result<Vec<Positioned<Token>>, SyntaxError> {
             iter.by_ref().peeking_next(|(_, s)| s.is_ascii_digit()).map(|(_, c)| c)
           }))
           .collect::<String>();
+        let span = Span { start: pos, end: pos + s.len() - 1, line, column };
         let n: i64 = s
           .parse()
-          .unwrap();
-
-        let span = Span { start: pos, end: pos + s.len() - 1, line, column };
+          .map_err(|_| SyntaxError::at(span, format!("number {} is out of range", s)))?;
         tokens.push(Positioned::new(Token::Number(n), span))
       }
       ch if ch.is_ascii_alphabetic() || ch == '_' => {
@@ -263,7 +324,7 @@ pub fn tokenizer(input: &str) -> Result<Vec<Positioned<Token>>, SyntaxError> {
         let span = Span { start: pos, end: pos + s.len() - 1, line, column };
         tokens.push(Positioned::new(Token::Ident(s), span))
       }
-      _ => return Err(SyntaxError::new(format!("unrecognized character {} (position: {}, {}:{})", ch, pos, line, column))),
+      _ => return Err(SyntaxError::at(Span { start: pos, end: pos, line, column }, format!("unrecognized character {}", ch))),
     }
 
     if !ch.is_ascii_control() {
@@ -280,40 +341,86 @@ pub fn tokenizer(input: &str) -> Result<Vec<Positioned<Token>>, SyntaxError> {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
   pub body: Vec<ProgramItem>,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProgramItem {
   Meta(MetaDeclaration),
   Model(ModelDeclaration),
   Type(TypeDeclaration),
   Enum(EnumDeclaration),
+  Interface(InterfaceDeclaration),
+  Template(TemplateDeclaration),
+}
+
+impl ProgramItem {
+  /// Span of the declared name, or of the key for meta
+  pub fn name_span(&self) -> Span {
+    match self {
+      ProgramItem::Meta(meta) => meta.key.span,
+      ProgramItem::Model(model) => model.name.span,
+      ProgramItem::Type(type_def) => type_def.name.span,
+      ProgramItem::Enum(enum_def) => enum_def.name.span,
+      ProgramItem::Interface(interface) => interface.name.span,
+      ProgramItem::Template(template) => template.name.span,
+    }
+  }
+
+  /// Declared name, or the key for meta
+  pub fn name(&self) -> &str {
+    match self {
+      ProgramItem::Meta(meta) => &meta.key.value.0,
+      ProgramItem::Model(model) => &model.name.value.0,
+      ProgramItem::Type(type_def) => &type_def.name.value.0,
+      ProgramItem::Enum(enum_def) => &enum_def.name.value.0,
+      ProgramItem::Interface(interface) => &interface.name.value.0,
+      ProgramItem::Template(template) => &template.name.value.0,
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CommentLit(pub String);
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Identifier(pub String);
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringLit(pub String);
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NumberLit(pub i64);
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BooleanLit(pub bool);
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValueLit {
+  Number(i64),
+  String(String),
+  /// Enum variants and `true`/`false`
+  Ident(String),
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MetaDeclaration {
   pub key: Positioned<Identifier>,
   pub value: Positioned<StringLit>,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModelDeclaration {
   pub name: Positioned<Identifier>,
   pub id: Positioned<NumberLit>,
@@ -323,14 +430,50 @@ pub struct ModelDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ModelItem {
   Entity(EntityDeclaration),
   Constructor(ConstructorDeclaration),
   ServerMethod(ServerMethodDeclaration),
   ClientMethod(ClientMethodDeclaration),
+  Include(IncludeDeclaration),
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterfaceDeclaration {
+  pub name: Positioned<Identifier>,
+  pub body: Vec<InterfaceItem>,
+  pub meta: Vec<MetaDeclaration>,
+  pub comments: Vec<CommentLit>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InterfaceItem {
+  ServerMethod(ServerMethodDeclaration),
+  ClientMethod(ClientMethodDeclaration),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IncludeDeclaration {
+  pub name: Positioned<Identifier>,
+  /// Added to the ID of every included method
+  pub offset: Option<Positioned<NumberLit>>,
+  pub overrides: Vec<IncludeOverrideDeclaration>,
+  pub comments: Vec<CommentLit>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IncludeOverrideDeclaration {
+  pub name: Positioned<Identifier>,
+  pub id: Positioned<NumberLit>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeDeclaration {
   pub name: Positioned<Identifier>,
   pub fields: Vec<FieldDeclaration>,
@@ -339,6 +482,7 @@ pub struct TypeDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumDeclaration {
   pub name: Positioned<Identifier>,
   pub repr: Positioned<Identifier>,
@@ -347,7 +491,33 @@ pub struct EnumDeclaration {
   pub comments: Vec<CommentLit>,
 }
 
+/// Game object template, a fixed set of models with optional constructor values
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TemplateDeclaration {
+  pub name: Positioned<Identifier>,
+  pub models: Vec<TemplateModelDeclaration>,
+  pub meta: Vec<MetaDeclaration>,
+  pub comments: Vec<CommentLit>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TemplateModelDeclaration {
+  pub name: Positioned<Identifier>,
+  pub values: Vec<TemplateValueDeclaration>,
+  pub comments: Vec<CommentLit>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TemplateValueDeclaration {
+  pub name: Positioned<Identifier>,
+  pub value: Positioned<ValueLit>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EntityDeclaration {
   pub name: Positioned<Identifier>,
   pub required: Option<Positioned<Token>>,
@@ -355,6 +525,7 @@ pub struct EntityDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstructorDeclaration {
   pub fields: Vec<FieldDeclaration>,
   pub meta: Vec<MetaDeclaration>,
@@ -362,6 +533,7 @@ pub struct ConstructorDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ServerMethodDeclaration {
   pub name: Positioned<Identifier>,
   pub params: Vec<ParamDeclaration>,
@@ -370,6 +542,7 @@ pub struct ServerMethodDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClientMethodDeclaration {
   pub name: Positioned<Identifier>,
   pub params: Vec<ParamDeclaration>,
@@ -378,6 +551,7 @@ pub struct ClientMethodDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldDeclaration {
   pub name: Positioned<Identifier>,
   pub kind: Type,
@@ -386,6 +560,7 @@ pub struct FieldDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariantDeclaration {
   pub name: Positioned<Identifier>,
   pub value: Positioned<NumberLit>,
@@ -393,12 +568,14 @@ pub struct VariantDeclaration {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParamDeclaration {
   pub name: Positioned<Identifier>,
   pub kind: Type,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
   Ident {
     ty: Positioned<Identifier>,
@@ -422,29 +599,35 @@ pub fn parse_program(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<P
     match &token.value {
       Token::Comment(comment) => {
         trace!("comment {:?}", comment);
-        match comment {
-          Comment::LineDoc(body) => comments.push(CommentLit(body.to_owned())),
-          _ => {}
-        }
+        let Comment::LineDoc(body) = comment;
+        comments.push(CommentLit(body.to_owned()));
         input.next();
       }
       Token::Meta => {
-        body.push(ProgramItem::Meta(parse_meta(input).unwrap()));
+        body.push(ProgramItem::Meta(parse_meta(input)?));
         comments.clear();
       }
       Token::Model => {
-        body.push(ProgramItem::Model(parse_model(input, &comments).unwrap()));
+        body.push(ProgramItem::Model(parse_model(input, &comments)?));
         comments.clear();
       }
       Token::Type => {
-        body.push(ProgramItem::Type(parse_type(input, &comments).unwrap()));
+        body.push(ProgramItem::Type(parse_type(input, &comments)?));
         comments.clear();
       }
       Token::Enum => {
-        body.push(ProgramItem::Enum(parse_enum(input, &comments).unwrap()));
+        body.push(ProgramItem::Enum(parse_enum(input, &comments)?));
+        comments.clear();
+      }
+      Token::Interface => {
+        body.push(ProgramItem::Interface(parse_interface(input, &comments)?));
+        comments.clear();
+      }
+      Token::Template => {
+        body.push(ProgramItem::Template(parse_template(input, &comments)?));
         comments.clear();
       }
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}", token))),
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}", token))),
     }
   }
 
@@ -453,35 +636,43 @@ pub fn parse_program(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<P
   })
 }
 
+fn next_token<'a>(input: &mut MultiPeek<Iter<'a, Positioned<Token>>>) -> Result<&'a Positioned<Token>, SyntaxError> {
+  input.next().ok_or_else(|| SyntaxError::new("unexpected end of file".to_owned()))
+}
+
+fn peek_token<'a>(input: &mut MultiPeek<Iter<'a, Positioned<Token>>>) -> Result<&'a Positioned<Token>, SyntaxError> {
+  input.peek().copied().ok_or_else(|| SyntaxError::new("unexpected end of file".to_owned()))
+}
+
 pub fn parse_meta(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<MetaDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Meta => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Meta", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Meta", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let key = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Eq => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Eq", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Eq", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let value = match &token.value {
     Token::String(value) => token.span.wrap(StringLit(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected String", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected String", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Semi => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Semi", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi", token)))
   };
 
   Ok(MetaDeclaration {
@@ -492,30 +683,30 @@ pub fn parse_meta(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<Meta
 
 macro_rules! consume_token {
   ($input:expr, $token:pat) => {{
-    let token = $input.next().unwrap();
+    let token = next_token($input)?;
     match &token.value {
       $token => token,
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected {}", token, stringify!($token))))
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected {}", token, stringify!($token))))
     }
   }};
 }
 
 macro_rules! consume_ident {
   ($input:expr) => {{
-    let token = $input.next().unwrap();
+    let token = next_token($input)?;
     match &token.value {
       Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected {}", token, stringify!(Token::Ident))))
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected {}", token, stringify!(Token::Ident))))
     }
   }};
 }
 
 macro_rules! consume_number {
   ($input:expr) => {{
-    let token = $input.next().unwrap();
+    let token = next_token($input)?;
     match &token.value {
       Token::Number(value) => token.span.wrap(NumberLit(value.to_owned())),
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected {}", token, stringify!(Token::Number))))
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected {}", token, stringify!(Token::Number))))
     }
   }};
 }
@@ -535,34 +726,36 @@ pub fn parse_model(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[C
     match &token.value {
       Token::Comment(comment) => {
         trace!("comment {:?}", comment);
-        match comment {
-          Comment::LineDoc(body) => item_comments.push(CommentLit(body.to_owned())),
-          _ => {}
-        }
+        let Comment::LineDoc(body) = comment;
+        item_comments.push(CommentLit(body.to_owned()));
         input.next();
       }
       Token::Meta => {
-        meta.push(parse_meta(input).unwrap());
+        meta.push(parse_meta(input)?);
         item_comments.clear();
       }
       Token::Required | Token::Entity => {
-        body.push(ModelItem::Entity(parse_entity(input, &item_comments).unwrap()));
+        body.push(ModelItem::Entity(parse_entity(input, &item_comments)?));
         item_comments.clear();
       }
       Token::Constructor => {
-        body.push(ModelItem::Constructor(parse_constructor(input, &item_comments).unwrap()));
+        body.push(ModelItem::Constructor(parse_constructor(input, &item_comments)?));
         item_comments.clear();
       }
       Token::Server => {
-        body.push(ModelItem::ServerMethod(parse_server_method(input, &item_comments).unwrap()));
+        body.push(ModelItem::ServerMethod(parse_server_method(input, &item_comments)?));
         item_comments.clear();
       }
       Token::Client => {
-        body.push(ModelItem::ClientMethod(parse_client_method(input, &item_comments).unwrap()));
+        body.push(ModelItem::ClientMethod(parse_client_method(input, &item_comments)?));
+        item_comments.clear();
+      }
+      Token::Include => {
+        body.push(ModelItem::Include(parse_include(input, &item_comments)?));
         item_comments.clear();
       }
       Token::Delimiter(Delimiter::BraceClose) => break,
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}", token))),
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}", token))),
     }
   }
 
@@ -577,30 +770,200 @@ pub fn parse_model(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[C
   })
 }
 
+pub fn parse_interface(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<InterfaceDeclaration, SyntaxError> {
+  consume_token!(input, Token::Interface);
+  let name = consume_ident!(input);
+  consume_token!(input, Token::Delimiter(Delimiter::BraceOpen));
+
+  let mut meta = Vec::new();
+  let mut body = Vec::new();
+  let mut item_comments = Vec::new();
+  while let Some(token) = input.peek() {
+    trace!("body: {:?}", token.value);
+    match &token.value {
+      Token::Comment(Comment::LineDoc(body)) => {
+        trace!("comment {:?}", body);
+        item_comments.push(CommentLit(body.to_owned()));
+        input.next();
+      }
+      Token::Meta => {
+        meta.push(parse_meta(input)?);
+        item_comments.clear();
+      }
+      Token::Server => {
+        body.push(InterfaceItem::ServerMethod(parse_server_method(input, &item_comments)?));
+        item_comments.clear();
+      }
+      Token::Client => {
+        body.push(InterfaceItem::ClientMethod(parse_client_method(input, &item_comments)?));
+        item_comments.clear();
+      }
+      Token::Delimiter(Delimiter::BraceClose) => break,
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}", token))),
+    }
+  }
+
+  consume_token!(input, Token::Delimiter(Delimiter::BraceClose));
+
+  Ok(InterfaceDeclaration {
+    name,
+    body,
+    meta,
+    comments: comments.to_vec(),
+  })
+}
+
+/// Parses `include Name [= offset] [{ method = id; ... }]`, the trailing semicolon
+/// is only required without an override block.
+pub fn parse_include(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<IncludeDeclaration, SyntaxError> {
+  consume_token!(input, Token::Include);
+  let name = consume_ident!(input);
+
+  let mut offset = None;
+  if let Some(Positioned { value: Token::Eq, .. }) = input.peek() {
+    input.next();
+    offset = Some(consume_number!(input));
+  }
+  input.reset_peek();
+
+  let mut overrides = Vec::new();
+  let token = next_token(input)?;
+  match &token.value {
+    Token::Semi => {}
+    Token::Delimiter(Delimiter::BraceOpen) => {
+      while let Some(token) = input.peek() {
+        match &token.value {
+          Token::Ident(_) => {
+            input.reset_peek();
+            let name = consume_ident!(input);
+            consume_token!(input, Token::Eq);
+            let id = consume_number!(input);
+            consume_token!(input, Token::Semi);
+            overrides.push(IncludeOverrideDeclaration { name, id });
+          }
+          Token::Delimiter(Delimiter::BraceClose) => break,
+          _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident or BraceClose", token))),
+        }
+      }
+      consume_token!(input, Token::Delimiter(Delimiter::BraceClose));
+    }
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi or BraceOpen", token)))
+  }
+
+  Ok(IncludeDeclaration {
+    name,
+    offset,
+    overrides,
+    comments: comments.to_vec(),
+  })
+}
+
+pub fn parse_template(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<TemplateDeclaration, SyntaxError> {
+  consume_token!(input, Token::Template);
+  let name = consume_ident!(input);
+  consume_token!(input, Token::Delimiter(Delimiter::BraceOpen));
+
+  let mut meta = Vec::new();
+  let mut models = Vec::new();
+  let mut item_comments = Vec::new();
+  while let Some(token) = input.peek() {
+    match &token.value {
+      Token::Comment(Comment::LineDoc(body)) => {
+        trace!("comment {:?}", body);
+        item_comments.push(CommentLit(body.to_owned()));
+        input.next();
+      }
+      Token::Meta => {
+        meta.push(parse_meta(input)?);
+        item_comments.clear();
+      }
+      Token::Model => {
+        models.push(parse_template_model(input, &item_comments)?);
+        item_comments.clear();
+      }
+      Token::Delimiter(Delimiter::BraceClose) => break,
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}", token))),
+    }
+  }
+
+  consume_token!(input, Token::Delimiter(Delimiter::BraceClose));
+
+  Ok(TemplateDeclaration {
+    name,
+    models,
+    meta,
+    comments: comments.to_vec(),
+  })
+}
+
+/// Parses `model Name;` or `model Name { field = value; ... }` inside a template.
+pub fn parse_template_model(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<TemplateModelDeclaration, SyntaxError> {
+  consume_token!(input, Token::Model);
+  let name = consume_ident!(input);
+
+  let mut values = Vec::new();
+  let token = next_token(input)?;
+  match &token.value {
+    Token::Semi => {}
+    Token::Delimiter(Delimiter::BraceOpen) => {
+      while let Some(token) = input.peek() {
+        match &token.value {
+          Token::Ident(_) => {
+            input.reset_peek();
+            let name = consume_ident!(input);
+            consume_token!(input, Token::Eq);
+
+            let token = next_token(input)?;
+            let value = match &token.value {
+              Token::Number(value) => token.span.wrap(ValueLit::Number(*value)),
+              Token::String(value) => token.span.wrap(ValueLit::String(value.to_owned())),
+              Token::Ident(value) => token.span.wrap(ValueLit::Ident(value.to_owned())),
+              _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Number, String or Ident", token)))
+            };
+            consume_token!(input, Token::Semi);
+
+            values.push(TemplateValueDeclaration { name, value });
+          }
+          Token::Delimiter(Delimiter::BraceClose) => break,
+          _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident or BraceClose", token))),
+        }
+      }
+      consume_token!(input, Token::Delimiter(Delimiter::BraceClose));
+    }
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi or BraceOpen", token)))
+  }
+
+  Ok(TemplateModelDeclaration {
+    name,
+    values,
+    comments: comments.to_vec(),
+  })
+}
+
 pub fn parse_entity(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<EntityDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let required = match &token.value {
     Token::Required => Some(token.to_owned()),
     Token::Entity => None,
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Required or Entity", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Required or Entity", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Entity => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Entity", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Entity", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Semi => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Semi", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi", token)))
   };
 
   Ok(EntityDeclaration {
@@ -611,16 +974,16 @@ pub fn parse_entity(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[
 }
 
 pub fn parse_constructor(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<ConstructorDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Constructor => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Constructor", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Constructor", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::BraceOpen) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected BraceOpen", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected BraceOpen", token)))
   };
 
   let mut meta = Vec::new();
@@ -630,30 +993,28 @@ pub fn parse_constructor(input: &mut MultiPeek<Iter<Positioned<Token>>>, comment
     match &token.value {
       Token::Comment(comment) => {
         trace!("comment {:?}", comment);
-        match comment {
-          Comment::LineDoc(body) => field_comments.push(CommentLit(body.to_owned())),
-          _ => {}
-        }
+        let Comment::LineDoc(body) = comment;
+        field_comments.push(CommentLit(body.to_owned()));
         input.next();
       }
       Token::Meta => {
-        meta.push(parse_meta(input).unwrap());
+        meta.push(parse_meta(input)?);
         field_comments.clear();
       }
       Token::Ident(_) => {
-        fields.push(parse_field(input, &field_comments).unwrap());
+        fields.push(parse_field(input, &field_comments)?);
         field_comments.clear();
       }
       Token::Delimiter(Delimiter::BraceClose) => break,
       _ => {}
-      // _ => return Err(SyntaxError::new(format!("unrecognized token {:?}", token))),
+      // _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}", token))),
     }
   }
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::BraceClose) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected BraceClose", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected BraceClose", token)))
   };
 
   Ok(ConstructorDeclaration {
@@ -664,36 +1025,36 @@ pub fn parse_constructor(input: &mut MultiPeek<Iter<Positioned<Token>>>, comment
 }
 
 pub fn parse_field(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<FieldDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Colon => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Colon", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Colon", token)))
   };
 
-  let kind = parse_type_2(input).unwrap();
+  let kind = parse_type_2(input)?;
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Eq => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Eq", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Eq", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let position = match &token.value {
     Token::Number(value) => token.span.wrap(NumberLit(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Number", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Number", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Semi => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Semi", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi", token)))
   };
 
   Ok(FieldDeclaration {
@@ -705,28 +1066,28 @@ pub fn parse_field(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[C
 }
 
 pub fn parse_variant(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<VariantDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Eq => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Eq", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Eq", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let value = match &token.value {
     Token::Number(value) => token.span.wrap(NumberLit(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Number", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Number", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Semi => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Semi", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi", token)))
   };
 
   Ok(VariantDeclaration {
@@ -737,19 +1098,19 @@ pub fn parse_variant(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &
 }
 
 pub fn parse_param(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<ParamDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Colon => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Colon", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Colon", token)))
   };
 
-  let kind = parse_type_2(input).unwrap();
+  let kind = parse_type_2(input)?;
 
   Ok(ParamDeclaration {
     name,
@@ -758,22 +1119,22 @@ pub fn parse_param(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<Par
 }
 
 pub fn parse_type(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<TypeDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Type => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Type", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Type", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::BraceOpen) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected BraceOpen", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected BraceOpen", token)))
   };
 
   let mut meta = Vec::new();
@@ -783,30 +1144,28 @@ pub fn parse_type(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[Co
     match &token.value {
       Token::Comment(comment) => {
         trace!("comment {:?}", comment);
-        match comment {
-          Comment::LineDoc(body) => field_comments.push(CommentLit(body.to_owned())),
-          _ => {}
-        }
+        let Comment::LineDoc(body) = comment;
+        field_comments.push(CommentLit(body.to_owned()));
         input.next();
       }
       Token::Meta => {
-        meta.push(parse_meta(input).unwrap());
+        meta.push(parse_meta(input)?);
         field_comments.clear();
       }
       Token::Ident(_) => {
-        fields.push(parse_field(input, &field_comments).unwrap());
+        fields.push(parse_field(input, &field_comments)?);
         field_comments.clear();
       }
       Token::Delimiter(Delimiter::BraceClose) => break,
       _ => {}
-      // _ => return Err(SyntaxError::new(format!("unrecognized token {:?}", token))),
+      // _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}", token))),
     }
   }
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::BraceClose) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected BraceClose", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected BraceClose", token)))
   };
 
   Ok(TypeDeclaration {
@@ -818,34 +1177,34 @@ pub fn parse_type(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[Co
 }
 
 pub fn parse_enum(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<EnumDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Enum => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Enum", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Enum", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Colon => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Colon", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Colon", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let repr = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::BraceOpen) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected BraceOpen", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected BraceOpen", token)))
   };
 
   let mut meta = Vec::new();
@@ -855,30 +1214,28 @@ pub fn parse_enum(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[Co
     match &token.value {
       Token::Comment(comment) => {
         trace!("comment {:?}", comment);
-        match comment {
-          Comment::LineDoc(body) => field_comments.push(CommentLit(body.to_owned())),
-          _ => {}
-        }
+        let Comment::LineDoc(body) = comment;
+        field_comments.push(CommentLit(body.to_owned()));
         input.next();
       }
       Token::Meta => {
-        meta.push(parse_meta(input).unwrap());
+        meta.push(parse_meta(input)?);
         field_comments.clear();
       }
       Token::Ident(_) => {
-        variants.push(parse_variant(input, &field_comments).unwrap());
+        variants.push(parse_variant(input, &field_comments)?);
         field_comments.clear();
       }
       Token::Delimiter(Delimiter::BraceClose) => break,
       _ => {}
-      // _ => return Err(SyntaxError::new(format!("unrecognized token {:?}", token))),
+      // _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}", token))),
     }
   }
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::BraceClose) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected BraceClose", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected BraceClose", token)))
   };
 
   Ok(EnumDeclaration {
@@ -891,73 +1248,72 @@ pub fn parse_enum(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[Co
 }
 
 pub fn parse_server_method(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<ServerMethodDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Server => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Server", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Server", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::ParenOpen) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected ParenOpen", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected ParenOpen", token)))
   };
 
   let mut params = Vec::new();
   while let Some(token) = input.peek() {
     match &token.value {
       Token::Ident(_) => {
-        params.push(parse_param(input).unwrap());
+        params.push(parse_param(input)?);
 
-        let token = input.peek().unwrap();
+        let token = peek_token(input)?;
         match &token.value {
           Token::Comma => {
             input.next();
 
-            let token = input.peek().unwrap();
-            match &token.value {
-              Token::Delimiter(Delimiter::ParenClose) => return Err(SyntaxError::new(format!("unexpected token {:?}", token))),
-              _ => {}
+            let token = peek_token(input)?;
+            if let Token::Delimiter(Delimiter::ParenClose) = &token.value {
+              return Err(SyntaxError::at(token.span, format!("unexpected token {:?}", token)));
             }
           }
           Token::Delimiter(Delimiter::ParenClose) => {}
-          _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Comma or ParenClose", token))),
+          _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Comma or ParenClose", token))),
         }
         input.reset_peek();
       }
       Token::Delimiter(Delimiter::ParenClose) => break,
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident or ParenClose", token))),
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident or ParenClose", token))),
     }
   }
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::ParenClose) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected ParenClose", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected ParenClose", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Eq => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Eq", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Eq", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let id = match &token.value {
     Token::Number(value) => token.span.wrap(NumberLit(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Number", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Number", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Semi => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Semi", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi", token)))
   };
 
   Ok(ServerMethodDeclaration {
@@ -969,73 +1325,72 @@ pub fn parse_server_method(input: &mut MultiPeek<Iter<Positioned<Token>>>, comme
 }
 
 pub fn parse_client_method(input: &mut MultiPeek<Iter<Positioned<Token>>>, comments: &[CommentLit]) -> Result<ClientMethodDeclaration, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Client => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Client", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Client", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let name = match &token.value {
     Token::Ident(value) => token.span.wrap(Identifier(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::ParenOpen) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected ParenOpen", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected ParenOpen", token)))
   };
 
   let mut params = Vec::new();
   while let Some(token) = input.peek() {
     match &token.value {
       Token::Ident(_) => {
-        params.push(parse_param(input).unwrap());
+        params.push(parse_param(input)?);
 
-        let token = input.peek().unwrap();
+        let token = peek_token(input)?;
         match &token.value {
           Token::Comma => {
             input.next();
 
-            let token = input.peek().unwrap();
-            match &token.value {
-              Token::Delimiter(Delimiter::ParenClose) => return Err(SyntaxError::new(format!("unexpected token {:?}", token))),
-              _ => {}
+            let token = peek_token(input)?;
+            if let Token::Delimiter(Delimiter::ParenClose) = &token.value {
+              return Err(SyntaxError::at(token.span, format!("unexpected token {:?}", token)));
             }
           }
           Token::Delimiter(Delimiter::ParenClose) => {}
-          _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Comma or ParenClose", token))),
+          _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Comma or ParenClose", token))),
         }
         input.reset_peek();
       }
       Token::Delimiter(Delimiter::ParenClose) => break,
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident or ParenClose", token))),
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident or ParenClose", token))),
     }
   }
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Delimiter(Delimiter::ParenClose) => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected ParenClose", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected ParenClose", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Eq => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Eq", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Eq", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   let id = match &token.value {
     Token::Number(value) => token.span.wrap(NumberLit(value.to_owned())),
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Number", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Number", token)))
   };
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Semi => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Semi", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Semi", token)))
   };
 
   Ok(ClientMethodDeclaration {
@@ -1056,7 +1411,7 @@ pub fn parse_type_2(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<Ty
     match &token.value {
       Token::Question => {
         if nullable_token.is_some() {
-          return Err(SyntaxError::new(format!("unexpected token {:?}, nullable is already present", token)));
+          return Err(SyntaxError::at(token.span, format!("unexpected token {:?}, nullable is already present", token)));
         }
 
         nullable_token = Some(token.wrap(token.value.to_owned()));
@@ -1065,7 +1420,7 @@ pub fn parse_type_2(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<Ty
       }
       Token::Ident(ident) => {
         if current_ident.is_some() {
-          return Err(SyntaxError::new(format!("unexpected token {:?}, identifier is already present", token)));
+          return Err(SyntaxError::at(token.span, format!("unexpected token {:?}, identifier is already present", token)));
         }
 
         current_ident = Some(token.wrap(Identifier(ident.to_owned())));
@@ -1074,26 +1429,26 @@ pub fn parse_type_2(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<Ty
       }
       Token::Dot => {
         if current_nested_type.is_some() {
-          return Err(SyntaxError::new(format!("unexpected token {:?}, nested type is already present", token)));
+          return Err(SyntaxError::at(token.span, format!("unexpected token {:?}, nested type is already present", token)));
         }
         if current_ident.is_none() {
-          return Err(SyntaxError::new(format!("unexpected token {:?}, expected type identifier", token)));
+          return Err(SyntaxError::at(token.span, format!("unexpected token {:?}, expected type identifier", token)));
         }
 
         input.next();
-        current_nested_type = Some(parse_type_2(input).unwrap());
+        current_nested_type = Some(parse_type_2(input)?);
         trace!("PARSED NESTED TYPE IDENT: {:?}", current_ident);
       }
       Token::Lt => {
         if current_generic.is_some() {
-          return Err(SyntaxError::new(format!("unexpected token {:?}, generic is already present", token)));
+          return Err(SyntaxError::at(token.span, format!("unexpected token {:?}, generic is already present", token)));
         }
         if current_ident.is_none() {
-          return Err(SyntaxError::new(format!("unexpected token {:?}, expected type identifier", token)));
+          return Err(SyntaxError::at(token.span, format!("unexpected token {:?}, expected type identifier", token)));
         }
 
         trace!("PARSE GENERIC ENTER");
-        let params = parse_type_2_generic_params(input).unwrap();
+        let params = parse_type_2_generic_params(input)?;
         trace!("PARSE GENERIC {:?}", params);
 
         current_generic = Some(params);
@@ -1130,18 +1485,18 @@ pub fn parse_type_2(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<Ty
           return Ok(ty);
         }
 
-        return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Question, Ident, or Lt", token)));
+        return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Question, Ident, or Lt", token)));
       }
     }
   }
-  return Err(SyntaxError::new(format!("unexpected eof")));
+  Err(SyntaxError::new("unexpected eof".to_owned()))
 }
 
 pub fn parse_type_2_generic_params(input: &mut MultiPeek<Iter<Positioned<Token>>>) -> Result<Vec<Type>, SyntaxError> {
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Lt => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Lt", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Lt", token)))
   };
 
   let mut params = Vec::new();
@@ -1149,33 +1504,32 @@ pub fn parse_type_2_generic_params(input: &mut MultiPeek<Iter<Positioned<Token>>
     match &token.value {
       Token::Ident(_) => {
         input.reset_peek();
-        params.push(parse_type_2(input).unwrap());
+        params.push(parse_type_2(input)?);
 
-        let token = input.peek().unwrap();
+        let token = peek_token(input)?;
         match &token.value {
           Token::Comma => {
             input.next();
 
-            let token = input.peek().unwrap();
-            match &token.value {
-              Token::Gt => return Err(SyntaxError::new(format!("unexpected token {:?}", token))),
-              _ => {}
+            let token = peek_token(input)?;
+            if let Token::Gt = &token.value {
+              return Err(SyntaxError::at(token.span, format!("unexpected token {:?}", token)));
             }
           }
           Token::Gt => {}
-          _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Comma or Gt", token))),
+          _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Comma or Gt", token))),
         }
         input.reset_peek();
       }
       Token::Gt => break,
-      _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Ident or Gt", token))),
+      _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Ident or Gt", token))),
     }
   }
 
-  let token = input.next().unwrap();
+  let token = next_token(input)?;
   match &token.value {
     Token::Gt => {}
-    _ => return Err(SyntaxError::new(format!("unrecognized token {:?}, expected Gt", token)))
+    _ => return Err(SyntaxError::at(token.span, format!("unrecognized token {:?}, expected Gt", token)))
   };
 
   Ok(params)
@@ -1187,7 +1541,7 @@ pub fn type_to_hl(kind: &Type) -> String {
       format!("{}{}", ty.value.0.to_owned(), if nullable.is_some() { "?" } else { "" })
     }
     Type::Generic { ty, nullable, params } => {
-      let params = params.iter().map(|it| type_to_hl(it)).join(", ");
+      let params = params.iter().map(type_to_hl).join(", ");
       format!("{}<{}>{}", ty.value.0.to_owned(), params, if nullable.is_some() { "?" } else { "" })
     }
     Type::Nested { ty, inner } => {
@@ -1196,102 +1550,239 @@ pub fn type_to_hl(kind: &Type) -> String {
   }
 }
 
-pub fn type_to_hl_codec(kind: &Type) -> String {
-  match kind {
+/// Declarations that lowering a single definition depends on: codecs depend on enum names and
+/// models depend on included interfaces
+#[derive(Debug, Default)]
+pub struct Declarations {
+  pub enums: HashSet<String>,
+  /// Interface name -> lowered interface
+  pub interfaces: HashMap<String, hl::Interface>,
+}
+
+impl Declarations {
+  /// Collects the enums and interfaces of all programs. Interfaces that cannot be lowered are
+  /// left out, their errors are reported when the interface itself is lowered.
+  pub fn collect<'a>(programs: impl IntoIterator<Item = &'a Program> + Clone) -> Declarations {
+    let mut declarations = Declarations::default();
+    for item in programs.clone().into_iter().flat_map(|it| &it.body) {
+      if let ProgramItem::Enum(enum_def) = item {
+        declarations.enums.insert(enum_def.name.value.0.to_owned());
+      }
+    }
+    for item in programs.into_iter().flat_map(|it| &it.body) {
+      if let ProgramItem::Interface(interface) = item {
+        let Ok(definition) = interface_to_definition(interface, &declarations) else { continue };
+        trace!("registered interface {}", definition.name);
+        declarations.interfaces.insert(definition.name.clone(), definition);
+      }
+    }
+    declarations
+  }
+}
+
+/// Only `List<T>` and `Map<K, V>` generics and one level of nesting have a codec
+pub fn type_to_hl_codec(kind: &Type, declarations: &Declarations) -> Result<String, SyntaxError> {
+  let unsupported = |ty: &Positioned<Identifier>| SyntaxError::at(ty.span, format!("generic type {} has no codec", ty.value.0));
+  Ok(match kind {
     Type::Ident { ty, nullable } => {
       let name = ty.value.0.to_owned();
-      let info = if ENUM_TYPES.lock().unwrap().contains(&name) { "EnumCodecInfo" } else { "TypeCodecInfo" };
+      let info = if declarations.enums.contains(&name) { "EnumCodecInfo" } else { "TypeCodecInfo" };
       format!("new {}({},{})", info, name, if nullable.is_some() { "true" } else { "false" })
     }
     Type::Generic { ty, nullable, params } => {
       let main = ty.value.0.to_owned();
-      match main.as_str() {
-        "List" => format!("new CollectionCodecInfo({},{},1)", type_to_hl_codec(&params[0]), if nullable.is_some() { "true" } else { "false" }),
-        "Map" => format!("new MapCodecInfo({},{},{})", type_to_hl_codec(&params[0]), type_to_hl_codec(&params[1]), if nullable.is_some() { "true" } else { "false" }),
-        _ => todo!()
+      match (main.as_str(), params.as_slice()) {
+        ("List", [item]) => format!("new CollectionCodecInfo({},{},1)", type_to_hl_codec(item, declarations)?, if nullable.is_some() { "true" } else { "false" }),
+        ("Map", [key, value]) => format!("new MapCodecInfo({},{},{})", type_to_hl_codec(key, declarations)?, type_to_hl_codec(value, declarations)?, if nullable.is_some() { "true" } else { "false" }),
+        _ => return Err(unsupported(ty))
       }
     }
     Type::Nested { ty, inner } => {
       let base = match &**ty {
-        Type::Ident { ty, nullable } => {
+        Type::Ident { ty, .. } => {
           ty.value.0.to_owned()
         }
-        _ => todo!()
+        _ => return Err(SyntaxError::new(format!("nested type {} has no codec", type_to_hl(kind))))
       };
       match &**inner {
         Type::Ident { ty, nullable } => {
           let name = ty.value.0.to_owned();
-          let info = if ENUM_TYPES.lock().unwrap().contains(&name) { "EnumCodecInfo" } else { "TypeCodecInfo" };
+          let info = if declarations.enums.contains(&name) { "EnumCodecInfo" } else { "TypeCodecInfo" };
           format!("new {}({}.{},{})", info, base, name, if nullable.is_some() { "true" } else { "false" })
         }
         Type::Generic { ty, nullable, params } => {
           let main = ty.value.0.to_owned();
-          match main.as_str() {
-            "List" => format!("new CollectionCodecInfo({}.{},{},1)", type_to_hl_codec(&params[0]), base, if nullable.is_some() { "true" } else { "false" }),
-            "Map" => format!("new MapCodecInfo({}.{},{},{})", type_to_hl_codec(&params[0]), base, type_to_hl_codec(&params[1]), if nullable.is_some() { "true" } else { "false" }),
-            _ => todo!()
+          match (main.as_str(), params.as_slice()) {
+            ("List", [item]) => format!("new CollectionCodecInfo({}.{},{},1)", type_to_hl_codec(item, declarations)?, base, if nullable.is_some() { "true" } else { "false" }),
+            ("Map", [key, value]) => format!("new MapCodecInfo({}.{},{},{})", type_to_hl_codec(key, declarations)?, base, type_to_hl_codec(value, declarations)?, if nullable.is_some() { "true" } else { "false" }),
+            _ => return Err(unsupported(ty))
           }
         }
-        _ => todo!()
+        Type::Nested { .. } => return Err(SyntaxError::new(format!("nested type {} has no codec", type_to_hl(kind))))
       }
     }
-  }
+  })
 }
 
-pub fn model_to_definition(input: &ModelDeclaration) -> Result<hl::Model, SyntaxError> {
+pub fn model_to_definition(input: &ModelDeclaration, declarations: &Declarations) -> Result<hl::Model, SyntaxError> {
   let constructor = input.body.iter().filter_map(|item| if let ModelItem::Constructor(value) = item { Some(value) } else { None }).next();
   let client_methods = input.body.iter().filter_map(|item| if let ModelItem::ClientMethod(value) = item { Some(value) } else { None });
   let server_methods = input.body.iter().filter_map(|item| if let ModelItem::ServerMethod(value) = item { Some(value) } else { None });
+  let includes = input.body.iter().filter_map(|item| if let ModelItem::Include(value) = item { Some(value) } else { None });
 
-  Ok(hl::Model {
-    name: input.name.value.0.to_owned(),
-    id: input.id.value.0,
-    constructor: constructor.map(|it| hl::ModelConstructor {
-      fields: it.fields.iter().map(|it| hl::Field {
-        name: it.name.value.0.to_owned(),
-        kind: type_to_hl(&it.kind),
-        codec: type_to_hl_codec(&it.kind),
-        position: it.position.value.0 as usize,
-        comments: convert_comments(&it.comments),
-      }).collect_vec(),
+  let constructor = match constructor {
+    Some(it) => Some(hl::ModelConstructor {
+      fields: it.fields.iter().map(|it| field_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
       meta: convert_meta(&it.meta),
       comments: convert_comments(&it.comments),
     }),
-    client_methods: client_methods.map(|it| hl::ClientMethod {
-      name: it.name.value.0.to_owned(),
-      id: it.id.value.0,
-      params: it.params.iter().map(|it| hl::Param {
-        name: it.name.value.0.to_owned(),
-        kind: type_to_hl(&it.kind),
-        codec: type_to_hl_codec(&it.kind),
-      }).collect_vec(),
-      comments: convert_comments(&it.comments),
-    }).collect_vec(),
-    server_methods: server_methods.map(|it| hl::ServerMethod {
-      name: it.name.value.0.to_owned(),
-      id: it.id.value.0,
-      params: it.params.iter().map(|it| hl::Param {
-        name: it.name.value.0.to_owned(),
-        kind: type_to_hl(&it.kind),
-        codec: type_to_hl_codec(&it.kind),
-      }).collect_vec(),
-      comments: convert_comments(&it.comments),
-    }).collect_vec(),
+    None => None,
+  };
+
+  // Span of the ID of every method, or of the include that added it, in the order of the model methods
+  let mut client_spans = client_methods.clone().map(|it| it.id.span).collect::<Vec<_>>();
+  let mut server_spans = server_methods.clone().map(|it| it.id.span).collect::<Vec<_>>();
+  let mut model = hl::Model {
+    name: input.name.value.0.to_owned(),
+    id: input.id.value.0,
+    constructor,
+    client_methods: client_methods.map(|it| client_method_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
+    server_methods: server_methods.map(|it| server_method_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
+    includes: Vec::new(),
     meta: convert_meta(&input.meta),
     comments: convert_comments(&input.comments),
+  };
+
+  for include in includes {
+    let interface = match declarations.interfaces.get(&include.name.value.0).cloned() {
+      Some(interface) => interface,
+      None => return Err(SyntaxError::at(include.name.span, format!("model {} includes unknown interface {}", model.name, include.name.value.0)))
+    };
+
+    include_interface(&mut model, include, interface)?;
+    model.includes.push(include.name.value.0.to_owned());
+
+    let span = |name: &str| include.overrides.iter().find(|it| it.name.value.0 == name).map_or(include.name.span, |it| it.id.span);
+    client_spans.extend(model.client_methods[client_spans.len()..].iter().map(|it| span(&it.name)));
+    server_spans.extend(model.server_methods[server_spans.len()..].iter().map(|it| span(&it.name)));
+  }
+
+  validate_model(&model, &client_spans, &server_spans)?;
+  Ok(model)
+}
+
+pub fn interface_to_definition(input: &InterfaceDeclaration, declarations: &Declarations) -> Result<hl::Interface, SyntaxError> {
+  let client_methods = input.body.iter().filter_map(|item| if let InterfaceItem::ClientMethod(value) = item { Some(value) } else { None });
+  let server_methods = input.body.iter().filter_map(|item| if let InterfaceItem::ServerMethod(value) = item { Some(value) } else { None });
+
+  Ok(hl::Interface {
+    name: input.name.value.0.to_owned(),
+    client_methods: client_methods.map(|it| client_method_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
+    server_methods: server_methods.map(|it| server_method_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
+    meta: convert_meta(&input.meta),
+    comments: convert_comments(&input.comments),
+  })
+}
+
+fn client_method_to_definition(input: &ClientMethodDeclaration, declarations: &Declarations) -> Result<hl::ClientMethod, SyntaxError> {
+  Ok(hl::ClientMethod {
+    name: input.name.value.0.to_owned(),
+    id: input.id.value.0,
+    params: input.params.iter().map(|it| param_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
+    comments: convert_comments(&input.comments),
   })
 }
 
-pub fn type_to_definition(input: &TypeDeclaration) -> Result<hl::Type, SyntaxError> {
+fn server_method_to_definition(input: &ServerMethodDeclaration, declarations: &Declarations) -> Result<hl::ServerMethod, SyntaxError> {
+  Ok(hl::ServerMethod {
+    name: input.name.value.0.to_owned(),
+    id: input.id.value.0,
+    params: input.params.iter().map(|it| param_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
+    comments: convert_comments(&input.comments),
+  })
+}
+
+fn param_to_definition(input: &ParamDeclaration, declarations: &Declarations) -> Result<hl::Param, SyntaxError> {
+  Ok(hl::Param {
+    name: input.name.value.0.to_owned(),
+    kind: type_to_hl(&input.kind),
+    codec: type_to_hl_codec(&input.kind, declarations)?,
+  })
+}
+
+fn field_to_definition(input: &FieldDeclaration, declarations: &Declarations) -> Result<hl::Field, SyntaxError> {
+  Ok(hl::Field {
+    name: input.name.value.0.to_owned(),
+    kind: type_to_hl(&input.kind),
+    codec: type_to_hl_codec(&input.kind, declarations)?,
+    position: input.position.value.0 as usize,
+    comments: convert_comments(&input.comments),
+  })
+}
+
+/// Appends methods of an included interface to the model. The include offset is added
+/// to every method ID first, then overrides replace IDs of the named methods. An offset that
+/// overflows a method ID is an error.
+fn include_interface(model: &mut hl::Model, include: &IncludeDeclaration, mut interface: hl::Interface) -> Result<(), SyntaxError> {
+  if let Some(offset) = &include.offset {
+    let overflow = |name: &str| SyntaxError::at(offset.span, format!("offset {} overflows the ID of method {} of interface {} included by model {}", offset.value.0, name, interface.name, model.name));
+    for method in &mut interface.client_methods {
+      method.id = method.id.checked_add(offset.value.0).ok_or_else(|| overflow(&method.name))?;
+    }
+    for method in &mut interface.server_methods {
+      method.id = method.id.checked_add(offset.value.0).ok_or_else(|| overflow(&method.name))?;
+    }
+  }
+
+  for item in &include.overrides {
+    let name = &item.name.value.0;
+    let mut found = false;
+    for method in interface.client_methods.iter_mut().filter(|it| &it.name == name) {
+      method.id = item.id.value.0;
+      found = true;
+    }
+    for method in interface.server_methods.iter_mut().filter(|it| &it.name == name) {
+      method.id = item.id.value.0;
+      found = true;
+    }
+
+    if !found {
+      return Err(SyntaxError::at(item.name.span, format!("model {} overrides unknown method {} of interface {}", model.name, name, interface.name)));
+    }
+  }
+
+  model.client_methods.append(&mut interface.client_methods);
+  model.server_methods.append(&mut interface.server_methods);
+  Ok(())
+}
+
+/// Checks that client and server method IDs are unique within the model,
+/// including methods that come from included interfaces. The spans belong to the methods
+/// in the same order, a duplicate is reported at the span of the later method.
+pub fn validate_model(model: &hl::Model, client_spans: &[Span], server_spans: &[Span]) -> Result<(), SyntaxError> {
+  let mut ids = HashMap::new();
+  for (method, span) in model.client_methods.iter().zip(client_spans) {
+    if let Some(other) = ids.insert(method.id, &method.name) {
+      let message = format!("client method {} of model {} has the same ID {} as {}", method.name, model.name, method.id, other);
+      return Err(SyntaxError::at(*span, message).with_code("duplicate-method-id"));
+    }
+  }
+
+  let mut ids = HashMap::new();
+  for (method, span) in model.server_methods.iter().zip(server_spans) {
+    if let Some(other) = ids.insert(method.id, &method.name) {
+      let message = format!("server method {} of model {} has the same ID {} as {}", method.name, model.name, method.id, other);
+      return Err(SyntaxError::at(*span, message).with_code("duplicate-method-id"));
+    }
+  }
+
+  Ok(())
+}
+
+pub fn type_to_definition(input: &TypeDeclaration, declarations: &Declarations) -> Result<hl::Type, SyntaxError> {
   Ok(hl::Type {
     name: input.name.value.0.to_owned(),
-    fields: input.fields.iter().map(|it| hl::Field {
-      name: it.name.value.0.to_owned(),
-      kind: type_to_hl(&it.kind),
-      codec: type_to_hl_codec(&it.kind),
-      position: it.position.value.0 as usize,
-      comments: convert_comments(&it.comments),
-    }).collect_vec(),
+    fields: input.fields.iter().map(|it| field_to_definition(it, declarations)).collect::<Result<Vec<_>, _>>()?,
     meta: convert_meta(&input.meta),
     comments: convert_comments(&input.comments),
   })
@@ -1311,6 +1802,26 @@ pub fn enum_to_definition(input: &EnumDeclaration) -> Result<hl::Enum, SyntaxErr
   })
 }
 
+pub fn template_to_definition(input: &TemplateDeclaration) -> Result<hl::Template, SyntaxError> {
+  Ok(hl::Template {
+    name: input.name.value.0.to_owned(),
+    models: input.models.iter().map(|it| hl::TemplateModel {
+      name: it.name.value.0.to_owned(),
+      values: it.values.iter().map(|it| hl::TemplateValue {
+        name: it.name.value.0.to_owned(),
+        value: match &it.value.value {
+          ValueLit::Number(value) => hl::Value::Number(*value),
+          ValueLit::String(value) => hl::Value::String(value.to_owned()),
+          ValueLit::Ident(value) => hl::Value::Ident(value.to_owned()),
+        },
+      }).collect_vec(),
+      comments: convert_comments(&it.comments),
+    }).collect_vec(),
+    meta: convert_meta(&input.meta),
+    comments: convert_comments(&input.comments),
+  })
+}
+
 pub fn convert_comments(comments: &[CommentLit]) -> Vec<String> {
   comments.iter().map(|it| it.0[2..].trim().to_owned()).collect::<_>()
 }
@@ -1324,11 +1835,20 @@ pub fn convert_meta(meta: &[MetaDeclaration]) -> Vec<Meta> {
   }).collect::<_>()
 }
 
+/// Writes `(path, content)` pairs under `dir`, creating the directories in between
+#[cfg(test)]
+pub(crate) fn write_tree(dir: &std::path::Path, files: &[(&str, &str)]) {
+  for (path, content) in files {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use test_log::test;
   use tracing::{debug, info};
-  use std::fs;
 
   use super::*;
 
@@ -1396,10 +1916,138 @@ mod tests {
       ProgramItem::Model(model) => model,
       _ => todo!()
     };
-    let definition = model_to_definition(model);
+    let definition = model_to_definition(model, &Declarations::default());
     info!("{:?}", definition);
   }
 
+  #[test]
+  fn model_include() {
+    let source = r#"
+      /// Battle notifications
+      interface IncludeTestNotifications {
+        client showNotification(message: String) = 100;
+        client hideNotification() = 200;
+        server notificationShown() = 300;
+      }
+
+      model IncludeTestModel = 1 {
+        client ownMethod() = 1;
+        include IncludeTestNotifications = 1000 {
+          hideNotification = 2;
+        }
+      }
+
+      model IncludeTestConflictModel = 2 {
+        client ownMethod() = 100;
+        include IncludeTestNotifications;
+      }
+    "#;
+    let tokens = tokenizer(source).unwrap();
+    let mut iter = itertools::multipeek(&tokens);
+    let ast = parse_program(&mut iter).unwrap();
+
+    let declarations = Declarations::collect([&ast]);
+
+    let model = match &ast.body[1] {
+      ProgramItem::Model(model) => model,
+      _ => unreachable!()
+    };
+    let definition = model_to_definition(model, &declarations).unwrap();
+    assert_eq!(definition.client_methods.iter().map(|it| (it.name.as_str(), it.id)).collect_vec(), vec![
+      ("ownMethod", 1),
+      ("showNotification", 1100),
+      ("hideNotification", 2),
+    ]);
+    assert_eq!(definition.server_methods.iter().map(|it| (it.name.as_str(), it.id)).collect_vec(), vec![
+      ("notificationShown", 1300),
+    ]);
+
+    let model = match &ast.body[2] {
+      ProgramItem::Model(model) => model,
+      _ => unreachable!()
+    };
+    let error = model_to_definition(model, &declarations).unwrap_err().locate(source);
+    assert_eq!(error.to_string(), "client method showNotification of model IncludeTestConflictModel has the same ID 100 as ownMethod");
+    assert_eq!((error.code(), error.span().map(|it| it.line)), ("duplicate-method-id", Some(17)));
+  }
+
+  #[test]
+  fn number_out_of_range() {
+    let source = "model M = 1 {\n  client a() = 99999999999999999999;\n}\n";
+    let error = tokenizer(source).unwrap_err().locate(source);
+    assert_eq!(error.to_string(), "number 99999999999999999999 is out of range");
+    assert_eq!(error.span().map(|it| (it.line, it.column)), Some((1, 15)));
+  }
+
+  #[test]
+  fn model_include_overflow() {
+    let source = r#"
+      interface OverflowTestNotifications {
+        client show() = 9223372036854775807;
+      }
+
+      model OverflowTestModel = 1 {
+        include OverflowTestNotifications = 1;
+      }
+    "#;
+    let tokens = tokenizer(source).unwrap();
+    let mut iter = itertools::multipeek(&tokens);
+    let ast = parse_program(&mut iter).unwrap();
+    let declarations = Declarations::collect([&ast]);
+
+    let model = match &ast.body[1] {
+      ProgramItem::Model(model) => model,
+      _ => unreachable!()
+    };
+    let error = model_to_definition(model, &declarations).unwrap_err().locate(source);
+    assert_eq!(error.to_string(), "offset 1 overflows the ID of method show of interface OverflowTestNotifications included by model OverflowTestModel");
+    assert_eq!(error.span().map(|it| it.line), Some(6));
+  }
+
+  #[test]
+  fn template() {
+    let tokens = tokenizer(r#"
+      template TankObject {
+        model TankModel;
+        /// Health is fixed
+        model HealthModel {
+          maxHealth = 100;
+          name = "tank";
+          team = RED;
+        }
+      }
+    "#).unwrap();
+    let mut iter = itertools::multipeek(&tokens);
+    let ast = parse_program(&mut iter).unwrap();
+
+    let template = match &ast.body[0] {
+      ProgramItem::Template(template) => template,
+      _ => unreachable!()
+    };
+    let definition = template_to_definition(template).unwrap();
+    assert_eq!(definition.name, "TankObject");
+    assert_eq!(definition.models.iter().map(|it| it.name.as_str()).collect_vec(), vec!["TankModel", "HealthModel"]);
+    assert!(definition.models[0].values.is_empty());
+    assert_eq!(definition.models[1].comments, vec!["Health is fixed"]);
+    assert!(matches!(&definition.models[1].values[0].value, hl::Value::Number(100)));
+    assert!(matches!(&definition.models[1].values[1].value, hl::Value::String(value) if value == "tank"));
+    assert!(matches!(&definition.models[1].values[2].value, hl::Value::Ident(value) if value == "RED"));
+  }
+
+  #[test]
+  fn include_eof() {
+    let tokens = tokenizer("model IncludeEofModel = 1 {\n  include").unwrap();
+    let mut iter = itertools::multipeek(&tokens);
+    assert!(parse_program(&mut iter).is_err());
+  }
+
+  #[test]
+  fn template_eof() {
+    let tokens = tokenizer("template EofObject {\n  model HealthModel {\n    maxHealth =").unwrap();
+    let mut iter = itertools::multipeek(&tokens);
+    assert!(parse_program(&mut iter).is_err());
+  }
+
   #[test]
   fn type_to_string() {
     assert_eq!(type_to_hl(&Type::Ident { ty: Positioned::identity(Identifier("String".to_owned())), nullable: None }), "String");
@@ -1454,4 +2102,27 @@ mod tests {
       ],
     }), "TwoParam<A?, B>?");
   }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn serde() {
+    let tokens = tokenizer(r#"
+      model SerdeTestModel = 1 {
+        client show(items: List<String?>) = 1;
+      }
+    "#).unwrap();
+    let mut iter = itertools::multipeek(&tokens);
+    let ast = parse_program(&mut iter).unwrap();
+
+    let json = serde_json::to_string(&ast).unwrap();
+    let ast: Program = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&ast).unwrap(), json);
+
+    let definition = match &ast.body[0] {
+      ProgramItem::Model(model) => model_to_definition(model, &Declarations::default()).unwrap(),
+      _ => unreachable!()
+    };
+    let value = serde_json::to_value(&definition).unwrap();
+    assert_eq!(value["client_methods"][0]["params"][0]["kind"], "List<String?>");
+  }
 }
@@ -0,0 +1,256 @@
+//! Modules are directories with a `module.yaml`. Every source file belongs to the closest module
+//! up the tree.
+//!
+//! ```yaml
+//! # Every key is optional
+//! name: battle
+//! # Replaces the root package for the module definitions
+//! package: com.example.battle
+//! # Modules this module may reference, any module if absent
+//! depends_on: [common]
+//! # Definitions other modules may reference, all of them if absent
+//! exports: [BattleTeam, TankModel]
+//! targets:
+//!   actionscript:
+//!     skip: true
+//!   kotlin:
+//!     package: com.example.server.battle
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::hl::Definition;
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct ModuleDescriptor {
+  /// Directory path relative to the workspace root by default, `root` for the root itself
+  pub name: Option<String>,
+  pub package: Option<String>,
+  pub depends_on: Option<Vec<String>>,
+  pub exports: Option<Vec<String>>,
+  /// Target name -> settings
+  pub targets: BTreeMap<String, ModuleTargetSettings>,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct ModuleTargetSettings {
+  /// Overrides [ModuleDescriptor::package] for this target
+  pub package: Option<String>,
+  /// Generate nothing for this module
+  pub skip: bool,
+}
+
+impl ModuleDescriptor {
+  /// Parses the content of a `module.yaml`, an empty file has default settings
+  pub fn parse(content: &str) -> Result<ModuleDescriptor, DescriptorError> {
+    if content.trim().is_empty() {
+      return Ok(ModuleDescriptor::default());
+    }
+    parse_settings(content)
+  }
+}
+
+#[cfg(feature = "serde")]
+fn parse_settings(content: &str) -> Result<ModuleDescriptor, DescriptorError> {
+  serde_yaml::from_str(content).map_err(DescriptorError::Yaml)
+}
+
+#[cfg(not(feature = "serde"))]
+fn parse_settings(_content: &str) -> Result<ModuleDescriptor, DescriptorError> {
+  Err(DescriptorError::Unsupported)
+}
+
+/// Invalid `module.yaml`, see [ModuleDescriptor::parse]
+#[derive(Debug)]
+pub enum DescriptorError {
+  #[cfg(feature = "serde")]
+  Yaml(serde_yaml::Error),
+  /// Descriptor with settings, but the `serde` feature is disabled
+  Unsupported,
+}
+
+impl Display for DescriptorError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      #[cfg(feature = "serde")]
+      DescriptorError::Yaml(error) => write!(f, "{}", error),
+      DescriptorError::Unsupported => write!(f, "module settings need the serde feature of protolang-parser"),
+    }
+  }
+}
+
+impl Error for DescriptorError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      #[cfg(feature = "serde")]
+      DescriptorError::Yaml(error) => Some(error),
+      DescriptorError::Unsupported => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+  pub name: String,
+  /// Index into [crate::workspace::Workspace::roots]
+  pub root: usize,
+  /// Relative to its root
+  pub dir: PathBuf,
+  pub descriptor: ModuleDescriptor,
+}
+
+impl Module {
+  /// Root package of the module definitions for the target, if the module sets one
+  pub fn package(&self, target: &str) -> Option<&str> {
+    self.descriptor.targets.get(target).and_then(|it| it.package.as_deref()).or(self.descriptor.package.as_deref())
+  }
+
+  pub fn is_skipped(&self, target: &str) -> bool {
+    self.descriptor.targets.get(target).is_some_and(|it| it.skip)
+  }
+
+  pub fn exports(&self, name: &str) -> bool {
+    self.descriptor.exports.as_ref().is_none_or(|it| it.iter().any(|export| export == name))
+  }
+
+  pub fn may_depend_on(&self, module: &str) -> bool {
+    self.descriptor.depends_on.as_ref().is_none_or(|it| it.iter().any(|dependency| dependency == module))
+  }
+}
+
+#[derive(Debug)]
+pub enum ModuleError {
+  /// `depends_on` or `--module` names a module that does not exist
+  UnknownModule {
+    module: String,
+    referenced_by: Option<String>,
+  },
+  /// Source file outside of any module
+  Orphan(PathBuf),
+  /// Reference to a definition the other module does not export
+  NotExported {
+    path: PathBuf,
+    name: String,
+    module: String,
+  },
+  /// Reference to a module missing from `depends_on`
+  UndeclaredDependency {
+    path: PathBuf,
+    name: String,
+    module: String,
+  },
+}
+
+impl ModuleError {
+  /// File the error is about, if any
+  pub fn path(&self) -> Option<&Path> {
+    match self {
+      ModuleError::UnknownModule { .. } => None,
+      ModuleError::Orphan(path) | ModuleError::NotExported { path, .. } | ModuleError::UndeclaredDependency { path, .. } => Some(path),
+    }
+  }
+
+  /// Description without the path
+  pub fn message(&self) -> String {
+    match self {
+      ModuleError::UnknownModule { module, referenced_by: Some(referenced_by) } => format!("module '{}' depends on unknown module '{}'", referenced_by, module),
+      ModuleError::UnknownModule { module, referenced_by: None } => format!("unknown module '{}'", module),
+      ModuleError::Orphan(_) => "file is not inside any module, add a module.yaml to its directory or a parent directory".to_owned(),
+      ModuleError::NotExported { name, module, .. } => format!("{} is not exported by module '{}'", name, module),
+      ModuleError::UndeclaredDependency { name, module, .. } => format!("{} is declared in module '{}', which is not in depends_on", name, module),
+    }
+  }
+}
+
+impl Display for ModuleError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self.path() {
+      Some(path) => write!(f, "{:?}: {}", path, self.message()),
+      None => write!(f, "{}", self.message()),
+    }
+  }
+}
+
+impl Error for ModuleError {}
+
+/// Names a definition refers to, including builtin types that are not workspace symbols
+pub fn referenced_names(definition: &Definition) -> BTreeSet<&str> {
+  let mut kinds = Vec::new();
+  match definition {
+    Definition::Meta(_) | Definition::Enum(_) => {}
+    Definition::Model(model) => {
+      if let Some(constructor) = &model.constructor {
+        kinds.extend(constructor.fields.iter().map(|it| it.kind.as_str()));
+      }
+      kinds.extend(model.client_methods.iter().flat_map(|it| &it.params).map(|it| it.kind.as_str()));
+      kinds.extend(model.server_methods.iter().flat_map(|it| &it.params).map(|it| it.kind.as_str()));
+      kinds.extend(model.includes.iter().map(|it| it.as_str()));
+    }
+    Definition::Type(type_def) => kinds.extend(type_def.fields.iter().map(|it| it.kind.as_str())),
+    Definition::Interface(interface) => {
+      kinds.extend(interface.client_methods.iter().flat_map(|it| &it.params).map(|it| it.kind.as_str()));
+      kinds.extend(interface.server_methods.iter().flat_map(|it| &it.params).map(|it| it.kind.as_str()));
+    }
+    Definition::Template(template) => kinds.extend(template.models.iter().map(|it| it.name.as_str())),
+  }
+
+  // `Map<K, List<V>>?` -> `Map`, `K`, `List`, `V`; `TankModel.Constructor` -> `TankModel`
+  kinds.into_iter()
+    .flat_map(|kind| kind.split(|char: char| !(char.is_alphanumeric() || char == '_' || char == '.')))
+    .filter_map(|name| name.split('.').next())
+    .filter(|name| !name.is_empty())
+    .collect()
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+  use std::fs;
+
+  use itertools::Itertools;
+
+  use crate::workspace::{Workspace, WorkspaceError};
+  use crate::write_tree;
+
+  use super::*;
+
+  #[test]
+  fn modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("common/module.yaml", "exports: [ModulesTestTeam]\n"),
+      ("common/ModulesTestTeam.proto", "enum ModulesTestTeam : i32 {\n  RED = 0;\n}\n"),
+      ("common/ModulesTestInternal.proto", "type ModulesTestInternal {\n  value: i32 = 1;\n}\n"),
+      ("common/ModulesTestNotifications.proto", "interface ModulesTestNotifications {\n  client notify() = 1;\n}\n"),
+      ("battle/module.yaml", "name: fight\ndepends_on: [common]\n"),
+      ("battle/ModulesTestModel.proto", "model ModulesTestModel = 1 {\n  include ModulesTestNotifications;\n}\n"),
+      ("battle/ModulesTestType.proto", "type ModulesTestType {\n  team: ModulesTestTeam = 1;\n  internal: List<ModulesTestInternal>? = 2;\n}\n"),
+      ("lobby/module.yaml", "depends_on: [fight, shop]\n"),
+      ("lobby/ModulesTestLobby.proto", "type ModulesTestLobby {\n  team: ModulesTestTeam = 1;\n}\n"),
+      ("Orphan.proto", "enum ModulesTestOrphan : i32 {\n  A = 0;\n}\n"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+    assert_eq!(workspace.modules.keys().collect_vec(), vec!["common", "fight", "lobby"]);
+    assert_eq!(workspace.files.iter().map(|it| it.module.as_deref()).collect_vec(), vec![None, Some("fight"), Some("fight"), Some("common"), Some("common"), Some("common"), Some("lobby")]);
+    assert_eq!(workspace.with_dependencies(&["fight".to_owned()]).unwrap(), vec!["common", "fight"]);
+    assert!(matches!(workspace.with_dependencies(&["lobby".to_owned()]), Err(ModuleError::UnknownModule { module, .. }) if module == "shop"));
+
+    let errors = workspace.validate().iter().map(ToString::to_string).collect_vec();
+    assert_eq!(errors.len(), 5, "{:?}", errors);
+    assert!(errors[0].contains("'lobby' depends on unknown module 'shop'"));
+    assert!(errors[1].contains("Orphan.proto\": file is not inside any module"));
+    assert!(errors[2].contains("ModulesTestNotifications is not exported by module 'common'"));
+    assert!(errors[3].contains("ModulesTestInternal is not exported by module 'common'"));
+    assert!(errors[4].contains("ModulesTestTeam is declared in module 'common', which is not in depends_on"));
+
+    fs::write(root.join("lobby/module.yaml"), "unknown: 1\n").unwrap();
+    let result = Workspace::load(root);
+    assert!(matches!(result, Err(WorkspaceError::Descriptor { .. })));
+  }
+}
@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
   pub start: usize,
   pub end: usize,
@@ -30,9 +31,25 @@ impl Span {
   pub fn wrap<T>(self, value: T) -> Positioned<T> {
     Positioned { value, span: self }
   }
+
+  /// Recomputes the 0-based line and column from [Span::start], a character offset into `content`
+  pub fn locate(self, content: &str) -> Span {
+    let mut line = 0;
+    let mut column = 0;
+    for char in content.chars().take(self.start) {
+      if char == '\n' {
+        line += 1;
+        column = 0;
+      } else {
+        column += 1;
+      }
+    }
+    Span { line, column, ..self }
+  }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Positioned<T> {
   pub value: T,
   pub span: Span,
@@ -0,0 +1,1027 @@
+//! Traversal of the [Program] AST.
+//!
+//! [Visit] walks the tree by reference, [VisitMut] by mutable reference and [Fold] rebuilds it
+//! by value. Every method has a default implementation calling the matching `walk_*` / `fold_*`
+//! function, which visits the children, so an implementation only overrides the nodes it is
+//! interested in and calls the walk function to continue into children:
+//!
+//! ```
+//! use protolang_parser::ClientMethodDeclaration;
+//! use protolang_parser::visit::{self, Visit};
+//!
+//! struct ClientMethods(Vec<String>);
+//!
+//! impl Visit for ClientMethods {
+//!   fn visit_client_method(&mut self, node: &ClientMethodDeclaration) {
+//!     self.0.push(node.name.value.0.to_owned());
+//!     visit::walk_client_method(self, node);
+//!   }
+//! }
+//! ```
+
+use crate::span::Positioned;
+use crate::{
+  ClientMethodDeclaration, ConstructorDeclaration, EntityDeclaration, EnumDeclaration, FieldDeclaration, Identifier,
+  IncludeDeclaration, IncludeOverrideDeclaration, InterfaceDeclaration, InterfaceItem, MetaDeclaration, ModelDeclaration,
+  ModelItem, ParamDeclaration, Program, ProgramItem, ServerMethodDeclaration, TemplateDeclaration,
+  TemplateModelDeclaration, TemplateValueDeclaration, Type, TypeDeclaration, VariantDeclaration,
+};
+
+pub trait Visit {
+  fn visit_program(&mut self, node: &Program) {
+    walk_program(self, node);
+  }
+
+  fn visit_program_item(&mut self, node: &ProgramItem) {
+    walk_program_item(self, node);
+  }
+
+  fn visit_meta(&mut self, node: &MetaDeclaration) {
+    walk_meta(self, node);
+  }
+
+  fn visit_model(&mut self, node: &ModelDeclaration) {
+    walk_model(self, node);
+  }
+
+  fn visit_model_item(&mut self, node: &ModelItem) {
+    walk_model_item(self, node);
+  }
+
+  fn visit_entity(&mut self, node: &EntityDeclaration) {
+    walk_entity(self, node);
+  }
+
+  fn visit_constructor(&mut self, node: &ConstructorDeclaration) {
+    walk_constructor(self, node);
+  }
+
+  fn visit_server_method(&mut self, node: &ServerMethodDeclaration) {
+    walk_server_method(self, node);
+  }
+
+  fn visit_client_method(&mut self, node: &ClientMethodDeclaration) {
+    walk_client_method(self, node);
+  }
+
+  fn visit_include(&mut self, node: &IncludeDeclaration) {
+    walk_include(self, node);
+  }
+
+  fn visit_include_override(&mut self, node: &IncludeOverrideDeclaration) {
+    walk_include_override(self, node);
+  }
+
+  fn visit_interface(&mut self, node: &InterfaceDeclaration) {
+    walk_interface(self, node);
+  }
+
+  fn visit_interface_item(&mut self, node: &InterfaceItem) {
+    walk_interface_item(self, node);
+  }
+
+  fn visit_type_declaration(&mut self, node: &TypeDeclaration) {
+    walk_type_declaration(self, node);
+  }
+
+  fn visit_enum(&mut self, node: &EnumDeclaration) {
+    walk_enum(self, node);
+  }
+
+  fn visit_variant(&mut self, node: &VariantDeclaration) {
+    walk_variant(self, node);
+  }
+
+  fn visit_template(&mut self, node: &TemplateDeclaration) {
+    walk_template(self, node);
+  }
+
+  fn visit_template_model(&mut self, node: &TemplateModelDeclaration) {
+    walk_template_model(self, node);
+  }
+
+  fn visit_template_value(&mut self, node: &TemplateValueDeclaration) {
+    walk_template_value(self, node);
+  }
+
+  fn visit_field(&mut self, node: &FieldDeclaration) {
+    walk_field(self, node);
+  }
+
+  fn visit_param(&mut self, node: &ParamDeclaration) {
+    walk_param(self, node);
+  }
+
+  fn visit_type(&mut self, node: &Type) {
+    walk_type(self, node);
+  }
+
+  /// Called for the names of top-level declarations and the names that refer to them: types,
+  /// enum representations, included interfaces and template models. Not for meta keys.
+  fn visit_identifier(&mut self, _node: &Positioned<Identifier>) {}
+
+  /// Called for the names of members: entities, methods, fields, parameters, variants, template
+  /// values and include overrides
+  fn visit_member_name(&mut self, _node: &Positioned<Identifier>) {}
+}
+
+pub fn walk_program<V: Visit + ?Sized>(visitor: &mut V, node: &Program) {
+  for item in &node.body {
+    visitor.visit_program_item(item);
+  }
+}
+
+pub fn walk_program_item<V: Visit + ?Sized>(visitor: &mut V, node: &ProgramItem) {
+  match node {
+    ProgramItem::Meta(item) => visitor.visit_meta(item),
+    ProgramItem::Model(item) => visitor.visit_model(item),
+    ProgramItem::Type(item) => visitor.visit_type_declaration(item),
+    ProgramItem::Enum(item) => visitor.visit_enum(item),
+    ProgramItem::Interface(item) => visitor.visit_interface(item),
+    ProgramItem::Template(item) => visitor.visit_template(item),
+  }
+}
+
+pub fn walk_meta<V: Visit + ?Sized>(_visitor: &mut V, _node: &MetaDeclaration) {}
+
+pub fn walk_model<V: Visit + ?Sized>(visitor: &mut V, node: &ModelDeclaration) {
+  visitor.visit_identifier(&node.name);
+  for item in &node.meta {
+    visitor.visit_meta(item);
+  }
+  for item in &node.body {
+    visitor.visit_model_item(item);
+  }
+}
+
+pub fn walk_model_item<V: Visit + ?Sized>(visitor: &mut V, node: &ModelItem) {
+  match node {
+    ModelItem::Entity(item) => visitor.visit_entity(item),
+    ModelItem::Constructor(item) => visitor.visit_constructor(item),
+    ModelItem::ServerMethod(item) => visitor.visit_server_method(item),
+    ModelItem::ClientMethod(item) => visitor.visit_client_method(item),
+    ModelItem::Include(item) => visitor.visit_include(item),
+  }
+}
+
+pub fn walk_entity<V: Visit + ?Sized>(visitor: &mut V, node: &EntityDeclaration) {
+  visitor.visit_member_name(&node.name);
+}
+
+pub fn walk_constructor<V: Visit + ?Sized>(visitor: &mut V, node: &ConstructorDeclaration) {
+  for item in &node.meta {
+    visitor.visit_meta(item);
+  }
+  for field in &node.fields {
+    visitor.visit_field(field);
+  }
+}
+
+pub fn walk_server_method<V: Visit + ?Sized>(visitor: &mut V, node: &ServerMethodDeclaration) {
+  visitor.visit_member_name(&node.name);
+  for param in &node.params {
+    visitor.visit_param(param);
+  }
+}
+
+pub fn walk_client_method<V: Visit + ?Sized>(visitor: &mut V, node: &ClientMethodDeclaration) {
+  visitor.visit_member_name(&node.name);
+  for param in &node.params {
+    visitor.visit_param(param);
+  }
+}
+
+pub fn walk_include<V: Visit + ?Sized>(visitor: &mut V, node: &IncludeDeclaration) {
+  visitor.visit_identifier(&node.name);
+  for item in &node.overrides {
+    visitor.visit_include_override(item);
+  }
+}
+
+pub fn walk_include_override<V: Visit + ?Sized>(visitor: &mut V, node: &IncludeOverrideDeclaration) {
+  visitor.visit_member_name(&node.name);
+}
+
+pub fn walk_interface<V: Visit + ?Sized>(visitor: &mut V, node: &InterfaceDeclaration) {
+  visitor.visit_identifier(&node.name);
+  for item in &node.meta {
+    visitor.visit_meta(item);
+  }
+  for item in &node.body {
+    visitor.visit_interface_item(item);
+  }
+}
+
+pub fn walk_interface_item<V: Visit + ?Sized>(visitor: &mut V, node: &InterfaceItem) {
+  match node {
+    InterfaceItem::ServerMethod(item) => visitor.visit_server_method(item),
+    InterfaceItem::ClientMethod(item) => visitor.visit_client_method(item),
+  }
+}
+
+pub fn walk_type_declaration<V: Visit + ?Sized>(visitor: &mut V, node: &TypeDeclaration) {
+  visitor.visit_identifier(&node.name);
+  for item in &node.meta {
+    visitor.visit_meta(item);
+  }
+  for field in &node.fields {
+    visitor.visit_field(field);
+  }
+}
+
+pub fn walk_enum<V: Visit + ?Sized>(visitor: &mut V, node: &EnumDeclaration) {
+  visitor.visit_identifier(&node.name);
+  visitor.visit_identifier(&node.repr);
+  for item in &node.meta {
+    visitor.visit_meta(item);
+  }
+  for variant in &node.variants {
+    visitor.visit_variant(variant);
+  }
+}
+
+pub fn walk_variant<V: Visit + ?Sized>(visitor: &mut V, node: &VariantDeclaration) {
+  visitor.visit_member_name(&node.name);
+}
+
+pub fn walk_template<V: Visit + ?Sized>(visitor: &mut V, node: &TemplateDeclaration) {
+  visitor.visit_identifier(&node.name);
+  for item in &node.meta {
+    visitor.visit_meta(item);
+  }
+  for model in &node.models {
+    visitor.visit_template_model(model);
+  }
+}
+
+pub fn walk_template_model<V: Visit + ?Sized>(visitor: &mut V, node: &TemplateModelDeclaration) {
+  visitor.visit_identifier(&node.name);
+  for value in &node.values {
+    visitor.visit_template_value(value);
+  }
+}
+
+pub fn walk_template_value<V: Visit + ?Sized>(visitor: &mut V, node: &TemplateValueDeclaration) {
+  visitor.visit_member_name(&node.name);
+}
+
+pub fn walk_field<V: Visit + ?Sized>(visitor: &mut V, node: &FieldDeclaration) {
+  visitor.visit_member_name(&node.name);
+  visitor.visit_type(&node.kind);
+}
+
+pub fn walk_param<V: Visit + ?Sized>(visitor: &mut V, node: &ParamDeclaration) {
+  visitor.visit_member_name(&node.name);
+  visitor.visit_type(&node.kind);
+}
+
+pub fn walk_type<V: Visit + ?Sized>(visitor: &mut V, node: &Type) {
+  match node {
+    Type::Ident { ty, .. } => visitor.visit_identifier(ty),
+    Type::Generic { ty, params, .. } => {
+      visitor.visit_identifier(ty);
+      for param in params {
+        visitor.visit_type(param);
+      }
+    }
+    Type::Nested { ty, inner } => {
+      visitor.visit_type(ty);
+      visitor.visit_type(inner);
+    }
+  }
+}
+
+pub trait VisitMut {
+  fn visit_program_mut(&mut self, node: &mut Program) {
+    walk_program_mut(self, node);
+  }
+
+  fn visit_program_item_mut(&mut self, node: &mut ProgramItem) {
+    walk_program_item_mut(self, node);
+  }
+
+  fn visit_meta_mut(&mut self, node: &mut MetaDeclaration) {
+    walk_meta_mut(self, node);
+  }
+
+  fn visit_model_mut(&mut self, node: &mut ModelDeclaration) {
+    walk_model_mut(self, node);
+  }
+
+  fn visit_model_item_mut(&mut self, node: &mut ModelItem) {
+    walk_model_item_mut(self, node);
+  }
+
+  fn visit_entity_mut(&mut self, node: &mut EntityDeclaration) {
+    walk_entity_mut(self, node);
+  }
+
+  fn visit_constructor_mut(&mut self, node: &mut ConstructorDeclaration) {
+    walk_constructor_mut(self, node);
+  }
+
+  fn visit_server_method_mut(&mut self, node: &mut ServerMethodDeclaration) {
+    walk_server_method_mut(self, node);
+  }
+
+  fn visit_client_method_mut(&mut self, node: &mut ClientMethodDeclaration) {
+    walk_client_method_mut(self, node);
+  }
+
+  fn visit_include_mut(&mut self, node: &mut IncludeDeclaration) {
+    walk_include_mut(self, node);
+  }
+
+  fn visit_include_override_mut(&mut self, node: &mut IncludeOverrideDeclaration) {
+    walk_include_override_mut(self, node);
+  }
+
+  fn visit_interface_mut(&mut self, node: &mut InterfaceDeclaration) {
+    walk_interface_mut(self, node);
+  }
+
+  fn visit_interface_item_mut(&mut self, node: &mut InterfaceItem) {
+    walk_interface_item_mut(self, node);
+  }
+
+  fn visit_type_declaration_mut(&mut self, node: &mut TypeDeclaration) {
+    walk_type_declaration_mut(self, node);
+  }
+
+  fn visit_enum_mut(&mut self, node: &mut EnumDeclaration) {
+    walk_enum_mut(self, node);
+  }
+
+  fn visit_variant_mut(&mut self, node: &mut VariantDeclaration) {
+    walk_variant_mut(self, node);
+  }
+
+  fn visit_template_mut(&mut self, node: &mut TemplateDeclaration) {
+    walk_template_mut(self, node);
+  }
+
+  fn visit_template_model_mut(&mut self, node: &mut TemplateModelDeclaration) {
+    walk_template_model_mut(self, node);
+  }
+
+  fn visit_template_value_mut(&mut self, node: &mut TemplateValueDeclaration) {
+    walk_template_value_mut(self, node);
+  }
+
+  fn visit_field_mut(&mut self, node: &mut FieldDeclaration) {
+    walk_field_mut(self, node);
+  }
+
+  fn visit_param_mut(&mut self, node: &mut ParamDeclaration) {
+    walk_param_mut(self, node);
+  }
+
+  fn visit_type_mut(&mut self, node: &mut Type) {
+    walk_type_mut(self, node);
+  }
+
+  /// Called for the names of top-level declarations and the names that refer to them: types,
+  /// enum representations, included interfaces and template models. Not for meta keys.
+  fn visit_identifier_mut(&mut self, _node: &mut Positioned<Identifier>) {}
+
+  /// Called for the names of members: entities, methods, fields, parameters, variants, template
+  /// values and include overrides
+  fn visit_member_name_mut(&mut self, _node: &mut Positioned<Identifier>) {}
+}
+
+pub fn walk_program_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut Program) {
+  for item in &mut node.body {
+    visitor.visit_program_item_mut(item);
+  }
+}
+
+pub fn walk_program_item_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut ProgramItem) {
+  match node {
+    ProgramItem::Meta(item) => visitor.visit_meta_mut(item),
+    ProgramItem::Model(item) => visitor.visit_model_mut(item),
+    ProgramItem::Type(item) => visitor.visit_type_declaration_mut(item),
+    ProgramItem::Enum(item) => visitor.visit_enum_mut(item),
+    ProgramItem::Interface(item) => visitor.visit_interface_mut(item),
+    ProgramItem::Template(item) => visitor.visit_template_mut(item),
+  }
+}
+
+pub fn walk_meta_mut<V: VisitMut + ?Sized>(_visitor: &mut V, _node: &mut MetaDeclaration) {}
+
+pub fn walk_model_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut ModelDeclaration) {
+  visitor.visit_identifier_mut(&mut node.name);
+  for item in &mut node.meta {
+    visitor.visit_meta_mut(item);
+  }
+  for item in &mut node.body {
+    visitor.visit_model_item_mut(item);
+  }
+}
+
+pub fn walk_model_item_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut ModelItem) {
+  match node {
+    ModelItem::Entity(item) => visitor.visit_entity_mut(item),
+    ModelItem::Constructor(item) => visitor.visit_constructor_mut(item),
+    ModelItem::ServerMethod(item) => visitor.visit_server_method_mut(item),
+    ModelItem::ClientMethod(item) => visitor.visit_client_method_mut(item),
+    ModelItem::Include(item) => visitor.visit_include_mut(item),
+  }
+}
+
+pub fn walk_entity_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut EntityDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+}
+
+pub fn walk_constructor_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut ConstructorDeclaration) {
+  for item in &mut node.meta {
+    visitor.visit_meta_mut(item);
+  }
+  for field in &mut node.fields {
+    visitor.visit_field_mut(field);
+  }
+}
+
+pub fn walk_server_method_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut ServerMethodDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+  for param in &mut node.params {
+    visitor.visit_param_mut(param);
+  }
+}
+
+pub fn walk_client_method_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut ClientMethodDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+  for param in &mut node.params {
+    visitor.visit_param_mut(param);
+  }
+}
+
+pub fn walk_include_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut IncludeDeclaration) {
+  visitor.visit_identifier_mut(&mut node.name);
+  for item in &mut node.overrides {
+    visitor.visit_include_override_mut(item);
+  }
+}
+
+pub fn walk_include_override_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut IncludeOverrideDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+}
+
+pub fn walk_interface_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut InterfaceDeclaration) {
+  visitor.visit_identifier_mut(&mut node.name);
+  for item in &mut node.meta {
+    visitor.visit_meta_mut(item);
+  }
+  for item in &mut node.body {
+    visitor.visit_interface_item_mut(item);
+  }
+}
+
+pub fn walk_interface_item_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut InterfaceItem) {
+  match node {
+    InterfaceItem::ServerMethod(item) => visitor.visit_server_method_mut(item),
+    InterfaceItem::ClientMethod(item) => visitor.visit_client_method_mut(item),
+  }
+}
+
+pub fn walk_type_declaration_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut TypeDeclaration) {
+  visitor.visit_identifier_mut(&mut node.name);
+  for item in &mut node.meta {
+    visitor.visit_meta_mut(item);
+  }
+  for field in &mut node.fields {
+    visitor.visit_field_mut(field);
+  }
+}
+
+pub fn walk_enum_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut EnumDeclaration) {
+  visitor.visit_identifier_mut(&mut node.name);
+  visitor.visit_identifier_mut(&mut node.repr);
+  for item in &mut node.meta {
+    visitor.visit_meta_mut(item);
+  }
+  for variant in &mut node.variants {
+    visitor.visit_variant_mut(variant);
+  }
+}
+
+pub fn walk_variant_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut VariantDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+}
+
+pub fn walk_template_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut TemplateDeclaration) {
+  visitor.visit_identifier_mut(&mut node.name);
+  for item in &mut node.meta {
+    visitor.visit_meta_mut(item);
+  }
+  for model in &mut node.models {
+    visitor.visit_template_model_mut(model);
+  }
+}
+
+pub fn walk_template_model_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut TemplateModelDeclaration) {
+  visitor.visit_identifier_mut(&mut node.name);
+  for value in &mut node.values {
+    visitor.visit_template_value_mut(value);
+  }
+}
+
+pub fn walk_template_value_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut TemplateValueDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+}
+
+pub fn walk_field_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut FieldDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+  visitor.visit_type_mut(&mut node.kind);
+}
+
+pub fn walk_param_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut ParamDeclaration) {
+  visitor.visit_member_name_mut(&mut node.name);
+  visitor.visit_type_mut(&mut node.kind);
+}
+
+pub fn walk_type_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut Type) {
+  match node {
+    Type::Ident { ty, .. } => visitor.visit_identifier_mut(ty),
+    Type::Generic { ty, params, .. } => {
+      visitor.visit_identifier_mut(ty);
+      for param in params {
+        visitor.visit_type_mut(param);
+      }
+    }
+    Type::Nested { ty, inner } => {
+      visitor.visit_type_mut(ty);
+      visitor.visit_type_mut(inner);
+    }
+  }
+}
+
+/// Rebuilds the tree by value. Unlike [VisitMut], a fold may replace a node with a node of a
+/// different variant, e.g. turn [Type::Ident] into [Type::Generic].
+pub trait Fold {
+  fn fold_program(&mut self, node: Program) -> Program {
+    fold_program(self, node)
+  }
+
+  fn fold_program_item(&mut self, node: ProgramItem) -> ProgramItem {
+    fold_program_item(self, node)
+  }
+
+  fn fold_meta(&mut self, node: MetaDeclaration) -> MetaDeclaration {
+    node
+  }
+
+  fn fold_model(&mut self, node: ModelDeclaration) -> ModelDeclaration {
+    fold_model(self, node)
+  }
+
+  fn fold_model_item(&mut self, node: ModelItem) -> ModelItem {
+    fold_model_item(self, node)
+  }
+
+  fn fold_entity(&mut self, node: EntityDeclaration) -> EntityDeclaration {
+    fold_entity(self, node)
+  }
+
+  fn fold_constructor(&mut self, node: ConstructorDeclaration) -> ConstructorDeclaration {
+    fold_constructor(self, node)
+  }
+
+  fn fold_server_method(&mut self, node: ServerMethodDeclaration) -> ServerMethodDeclaration {
+    fold_server_method(self, node)
+  }
+
+  fn fold_client_method(&mut self, node: ClientMethodDeclaration) -> ClientMethodDeclaration {
+    fold_client_method(self, node)
+  }
+
+  fn fold_include(&mut self, node: IncludeDeclaration) -> IncludeDeclaration {
+    fold_include(self, node)
+  }
+
+  fn fold_include_override(&mut self, node: IncludeOverrideDeclaration) -> IncludeOverrideDeclaration {
+    fold_include_override(self, node)
+  }
+
+  fn fold_interface(&mut self, node: InterfaceDeclaration) -> InterfaceDeclaration {
+    fold_interface(self, node)
+  }
+
+  fn fold_interface_item(&mut self, node: InterfaceItem) -> InterfaceItem {
+    fold_interface_item(self, node)
+  }
+
+  fn fold_type_declaration(&mut self, node: TypeDeclaration) -> TypeDeclaration {
+    fold_type_declaration(self, node)
+  }
+
+  fn fold_enum(&mut self, node: EnumDeclaration) -> EnumDeclaration {
+    fold_enum(self, node)
+  }
+
+  fn fold_variant(&mut self, node: VariantDeclaration) -> VariantDeclaration {
+    fold_variant(self, node)
+  }
+
+  fn fold_template(&mut self, node: TemplateDeclaration) -> TemplateDeclaration {
+    fold_template(self, node)
+  }
+
+  fn fold_template_model(&mut self, node: TemplateModelDeclaration) -> TemplateModelDeclaration {
+    fold_template_model(self, node)
+  }
+
+  fn fold_template_value(&mut self, node: TemplateValueDeclaration) -> TemplateValueDeclaration {
+    fold_template_value(self, node)
+  }
+
+  fn fold_field(&mut self, node: FieldDeclaration) -> FieldDeclaration {
+    fold_field(self, node)
+  }
+
+  fn fold_param(&mut self, node: ParamDeclaration) -> ParamDeclaration {
+    fold_param(self, node)
+  }
+
+  fn fold_type(&mut self, node: Type) -> Type {
+    fold_type(self, node)
+  }
+
+  /// Called for the names of top-level declarations and the names that refer to them: types,
+  /// enum representations, included interfaces and template models. Not for meta keys.
+  fn fold_identifier(&mut self, node: Positioned<Identifier>) -> Positioned<Identifier> {
+    node
+  }
+
+  /// Called for the names of members: entities, methods, fields, parameters, variants, template
+  /// values and include overrides
+  fn fold_member_name(&mut self, node: Positioned<Identifier>) -> Positioned<Identifier> {
+    node
+  }
+}
+
+pub fn fold_program<F: Fold + ?Sized>(folder: &mut F, node: Program) -> Program {
+  Program {
+    body: node.body.into_iter().map(|it| folder.fold_program_item(it)).collect(),
+  }
+}
+
+pub fn fold_program_item<F: Fold + ?Sized>(folder: &mut F, node: ProgramItem) -> ProgramItem {
+  match node {
+    ProgramItem::Meta(item) => ProgramItem::Meta(folder.fold_meta(item)),
+    ProgramItem::Model(item) => ProgramItem::Model(folder.fold_model(item)),
+    ProgramItem::Type(item) => ProgramItem::Type(folder.fold_type_declaration(item)),
+    ProgramItem::Enum(item) => ProgramItem::Enum(folder.fold_enum(item)),
+    ProgramItem::Interface(item) => ProgramItem::Interface(folder.fold_interface(item)),
+    ProgramItem::Template(item) => ProgramItem::Template(folder.fold_template(item)),
+  }
+}
+
+pub fn fold_model<F: Fold + ?Sized>(folder: &mut F, node: ModelDeclaration) -> ModelDeclaration {
+  ModelDeclaration {
+    name: folder.fold_identifier(node.name),
+    id: node.id,
+    meta: node.meta.into_iter().map(|it| folder.fold_meta(it)).collect(),
+    body: node.body.into_iter().map(|it| folder.fold_model_item(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_model_item<F: Fold + ?Sized>(folder: &mut F, node: ModelItem) -> ModelItem {
+  match node {
+    ModelItem::Entity(item) => ModelItem::Entity(folder.fold_entity(item)),
+    ModelItem::Constructor(item) => ModelItem::Constructor(folder.fold_constructor(item)),
+    ModelItem::ServerMethod(item) => ModelItem::ServerMethod(folder.fold_server_method(item)),
+    ModelItem::ClientMethod(item) => ModelItem::ClientMethod(folder.fold_client_method(item)),
+    ModelItem::Include(item) => ModelItem::Include(folder.fold_include(item)),
+  }
+}
+
+pub fn fold_entity<F: Fold + ?Sized>(folder: &mut F, node: EntityDeclaration) -> EntityDeclaration {
+  EntityDeclaration {
+    name: folder.fold_member_name(node.name),
+    required: node.required,
+    comments: node.comments,
+  }
+}
+
+pub fn fold_constructor<F: Fold + ?Sized>(folder: &mut F, node: ConstructorDeclaration) -> ConstructorDeclaration {
+  ConstructorDeclaration {
+    meta: node.meta.into_iter().map(|it| folder.fold_meta(it)).collect(),
+    fields: node.fields.into_iter().map(|it| folder.fold_field(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_server_method<F: Fold + ?Sized>(folder: &mut F, node: ServerMethodDeclaration) -> ServerMethodDeclaration {
+  ServerMethodDeclaration {
+    name: folder.fold_member_name(node.name),
+    params: node.params.into_iter().map(|it| folder.fold_param(it)).collect(),
+    id: node.id,
+    comments: node.comments,
+  }
+}
+
+pub fn fold_client_method<F: Fold + ?Sized>(folder: &mut F, node: ClientMethodDeclaration) -> ClientMethodDeclaration {
+  ClientMethodDeclaration {
+    name: folder.fold_member_name(node.name),
+    params: node.params.into_iter().map(|it| folder.fold_param(it)).collect(),
+    id: node.id,
+    comments: node.comments,
+  }
+}
+
+pub fn fold_include<F: Fold + ?Sized>(folder: &mut F, node: IncludeDeclaration) -> IncludeDeclaration {
+  IncludeDeclaration {
+    name: folder.fold_identifier(node.name),
+    offset: node.offset,
+    overrides: node.overrides.into_iter().map(|it| folder.fold_include_override(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_include_override<F: Fold + ?Sized>(folder: &mut F, node: IncludeOverrideDeclaration) -> IncludeOverrideDeclaration {
+  IncludeOverrideDeclaration {
+    name: folder.fold_member_name(node.name),
+    id: node.id,
+  }
+}
+
+pub fn fold_interface<F: Fold + ?Sized>(folder: &mut F, node: InterfaceDeclaration) -> InterfaceDeclaration {
+  InterfaceDeclaration {
+    name: folder.fold_identifier(node.name),
+    meta: node.meta.into_iter().map(|it| folder.fold_meta(it)).collect(),
+    body: node.body.into_iter().map(|it| folder.fold_interface_item(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_interface_item<F: Fold + ?Sized>(folder: &mut F, node: InterfaceItem) -> InterfaceItem {
+  match node {
+    InterfaceItem::ServerMethod(item) => InterfaceItem::ServerMethod(folder.fold_server_method(item)),
+    InterfaceItem::ClientMethod(item) => InterfaceItem::ClientMethod(folder.fold_client_method(item)),
+  }
+}
+
+pub fn fold_type_declaration<F: Fold + ?Sized>(folder: &mut F, node: TypeDeclaration) -> TypeDeclaration {
+  TypeDeclaration {
+    name: folder.fold_identifier(node.name),
+    meta: node.meta.into_iter().map(|it| folder.fold_meta(it)).collect(),
+    fields: node.fields.into_iter().map(|it| folder.fold_field(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_enum<F: Fold + ?Sized>(folder: &mut F, node: EnumDeclaration) -> EnumDeclaration {
+  EnumDeclaration {
+    name: folder.fold_identifier(node.name),
+    repr: folder.fold_identifier(node.repr),
+    meta: node.meta.into_iter().map(|it| folder.fold_meta(it)).collect(),
+    variants: node.variants.into_iter().map(|it| folder.fold_variant(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_variant<F: Fold + ?Sized>(folder: &mut F, node: VariantDeclaration) -> VariantDeclaration {
+  VariantDeclaration {
+    name: folder.fold_member_name(node.name),
+    value: node.value,
+    comments: node.comments,
+  }
+}
+
+pub fn fold_template<F: Fold + ?Sized>(folder: &mut F, node: TemplateDeclaration) -> TemplateDeclaration {
+  TemplateDeclaration {
+    name: folder.fold_identifier(node.name),
+    meta: node.meta.into_iter().map(|it| folder.fold_meta(it)).collect(),
+    models: node.models.into_iter().map(|it| folder.fold_template_model(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_template_model<F: Fold + ?Sized>(folder: &mut F, node: TemplateModelDeclaration) -> TemplateModelDeclaration {
+  TemplateModelDeclaration {
+    name: folder.fold_identifier(node.name),
+    values: node.values.into_iter().map(|it| folder.fold_template_value(it)).collect(),
+    comments: node.comments,
+  }
+}
+
+pub fn fold_template_value<F: Fold + ?Sized>(folder: &mut F, node: TemplateValueDeclaration) -> TemplateValueDeclaration {
+  TemplateValueDeclaration {
+    name: folder.fold_member_name(node.name),
+    value: node.value,
+  }
+}
+
+pub fn fold_field<F: Fold + ?Sized>(folder: &mut F, node: FieldDeclaration) -> FieldDeclaration {
+  FieldDeclaration {
+    name: folder.fold_member_name(node.name),
+    kind: folder.fold_type(node.kind),
+    position: node.position,
+    comments: node.comments,
+  }
+}
+
+pub fn fold_param<F: Fold + ?Sized>(folder: &mut F, node: ParamDeclaration) -> ParamDeclaration {
+  ParamDeclaration {
+    name: folder.fold_member_name(node.name),
+    kind: folder.fold_type(node.kind),
+  }
+}
+
+pub fn fold_type<F: Fold + ?Sized>(folder: &mut F, node: Type) -> Type {
+  match node {
+    Type::Ident { ty, nullable } => Type::Ident {
+      ty: folder.fold_identifier(ty),
+      nullable,
+    },
+    Type::Generic { ty, nullable, params } => Type::Generic {
+      ty: folder.fold_identifier(ty),
+      nullable,
+      params: params.into_iter().map(|it| folder.fold_type(it)).collect(),
+    },
+    Type::Nested { ty, inner } => Type::Nested {
+      ty: Box::new(folder.fold_type(*ty)),
+      inner: Box::new(folder.fold_type(*inner)),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{model_to_definition, parse_program, tokenizer, Declarations};
+
+  const SOURCE: &str = r#"
+    interface VisitTestNotifications {
+      client notify(team: VisitTestTeam) = 100;
+    }
+
+    enum VisitTestTeam : i32 {
+      RED = 0;
+    }
+
+    model VisitTestModel = 1 {
+      required entity VisitTestTeam;
+      server select(team: VisitTestTeam) = 1;
+      include VisitTestNotifications {
+        notify = 2;
+      }
+    }
+
+    type VisitTestItem {
+      team: List<VisitTestTeam?> = 1;
+    }
+
+    template VisitTestObject {
+      model VisitTestModel {
+        team = RED;
+      }
+    }
+  "#;
+
+  fn parse(source: &str) -> Program {
+    let tokens = tokenizer(source).unwrap();
+    parse_program(&mut itertools::multipeek(&tokens)).unwrap()
+  }
+
+  #[derive(Default)]
+  struct Names {
+    identifiers: Vec<String>,
+    members: Vec<String>,
+  }
+
+  impl Visit for Names {
+    fn visit_identifier(&mut self, node: &Positioned<Identifier>) {
+      self.identifiers.push(node.value.0.clone());
+    }
+
+    fn visit_member_name(&mut self, node: &Positioned<Identifier>) {
+      self.members.push(node.value.0.clone());
+    }
+  }
+
+  fn names(program: &Program) -> Names {
+    let mut names = Names::default();
+    names.visit_program(program);
+    names
+  }
+
+  #[test]
+  fn identifiers_and_member_names() {
+    let names = names(&parse(SOURCE));
+    assert_eq!(names.identifiers, vec![
+      "VisitTestNotifications", "VisitTestTeam", "VisitTestTeam", "i32", "VisitTestModel", "VisitTestTeam",
+      "VisitTestNotifications", "VisitTestItem", "List", "VisitTestTeam", "VisitTestObject", "VisitTestModel",
+    ]);
+    assert_eq!(names.members, vec!["notify", "team", "RED", "VisitTestTeam", "select", "team", "notify", "team", "team"]);
+  }
+
+  #[test]
+  fn rename_type_mut() {
+    struct Rename;
+
+    impl VisitMut for Rename {
+      fn visit_identifier_mut(&mut self, node: &mut Positioned<Identifier>) {
+        if node.value.0 == "VisitTestTeam" {
+          node.value.0 = "RenamedTeam".to_owned();
+        }
+      }
+    }
+
+    // The entity shares the name of the enum, but is a member
+    let mut program = parse(SOURCE);
+    Rename.visit_program_mut(&mut program);
+    let names = names(&program);
+    assert_eq!(names.identifiers.iter().filter(|it| *it == "RenamedTeam").count(), 4);
+    assert!(!names.identifiers.contains(&"VisitTestTeam".to_owned()));
+    assert!(names.members.contains(&"VisitTestTeam".to_owned()));
+  }
+
+  #[test]
+  fn fold_member_names() {
+    struct Prefix;
+
+    impl Fold for Prefix {
+      fn fold_member_name(&mut self, node: Positioned<Identifier>) -> Positioned<Identifier> {
+        node.span.wrap(Identifier(format!("my_{}", node.value.0)))
+      }
+    }
+
+    let names = names(&Prefix.fold_program(parse(SOURCE)));
+    assert!(names.members.iter().all(|it| it.starts_with("my_")), "{:?}", names.members);
+    assert!(names.identifiers.iter().all(|it| !it.starts_with("my_")), "{:?}", names.identifiers);
+  }
+
+  #[test]
+  fn visitors() {
+    let ast = parse(r#"
+      model VisitTestModel = 1 {
+        constructor {
+          items: List<VisitTestItem?> = 1;
+        }
+
+        client show(item: VisitTestItem) = 1;
+        server hide() = 2;
+      }
+
+      type VisitTestItem {
+        id: i32 = 1;
+      }
+    "#);
+
+    struct Methods(Vec<String>);
+
+    impl Visit for Methods {
+      fn visit_client_method(&mut self, node: &ClientMethodDeclaration) {
+        self.0.push(format!("client {}", node.name.value.0));
+      }
+
+      fn visit_server_method(&mut self, node: &ServerMethodDeclaration) {
+        self.0.push(format!("server {}", node.name.value.0));
+      }
+    }
+
+    let mut methods = Methods(Vec::new());
+    methods.visit_program(&ast);
+    assert_eq!(methods.0, vec!["client show", "server hide"]);
+
+    struct Rename;
+
+    impl VisitMut for Rename {
+      fn visit_identifier_mut(&mut self, node: &mut Positioned<Identifier>) {
+        if node.value.0 == "VisitTestItem" {
+          node.value.0 = "RenamedItem".to_owned();
+        }
+      }
+    }
+
+    let mut ast = ast;
+    Rename.visit_program_mut(&mut ast);
+
+    struct NonNullable;
+
+    impl Fold for NonNullable {
+      fn fold_type(&mut self, node: Type) -> Type {
+        let node = match node {
+          Type::Ident { ty, .. } => Type::Ident { ty, nullable: None },
+          Type::Generic { ty, params, .. } => Type::Generic { ty, nullable: None, params },
+          node => node,
+        };
+        fold_type(self, node)
+      }
+    }
+
+    let ast = NonNullable.fold_program(ast);
+    let types = match &ast.body[0] {
+      ProgramItem::Model(model) => model_to_definition(model, &Declarations::default()).unwrap(),
+      _ => unreachable!()
+    };
+    assert_eq!(types.constructor.unwrap().fields[0].kind, "List<RenamedItem>");
+    assert_eq!(types.client_methods[0].params[0].kind, "RenamedItem");
+    match &ast.body[1] {
+      ProgramItem::Type(type_def) => assert_eq!(type_def.name.value.0, "RenamedItem"),
+      _ => unreachable!()
+    }
+  }
+}
@@ -0,0 +1,587 @@
+//! Whole `.proto` tree loaded in one pass: every file is read, parsed and lowered exactly once,
+//! and the results are shared by all generator stages. [Workspace::reload] loads it again after
+//! changes on disk, and only parses the files whose content changed.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+
+use rayon::prelude::*;
+use tracing::{debug, info};
+
+use crate::discovery::{Discovery, DiscoveryError};
+use crate::hl::Definition;
+use crate::module::{referenced_names, DescriptorError, Module, ModuleDescriptor, ModuleError};
+use crate::{
+  convert_meta, enum_to_definition, hl, interface_to_definition, model_to_definition, parse_program, template_to_definition,
+  tokenizer, type_to_definition, Declarations, Program, ProgramItem, SyntaxError,
+};
+
+pub const MODULE_DESCRIPTOR: &str = "module.yaml";
+
+#[derive(Debug)]
+pub enum WorkspaceError {
+  Io {
+    path: PathBuf,
+    error: io::Error,
+  },
+  Discovery(DiscoveryError),
+  Syntax {
+    path: PathBuf,
+    error: SyntaxError,
+  },
+  /// Invalid `module.yaml`
+  Descriptor {
+    path: PathBuf,
+    error: DescriptorError,
+  },
+  /// Definition or module name declared twice
+  Duplicate {
+    name: String,
+    first: PathBuf,
+    second: PathBuf,
+  },
+}
+
+impl WorkspaceError {
+  /// File the error is about, if any
+  pub fn path(&self) -> Option<&Path> {
+    match self {
+      WorkspaceError::Io { path, .. } | WorkspaceError::Syntax { path, .. } | WorkspaceError::Descriptor { path, .. } => Some(path),
+      WorkspaceError::Duplicate { second, .. } => Some(second),
+      WorkspaceError::Discovery(_) => None,
+    }
+  }
+
+  /// Description without the path
+  pub fn message(&self) -> String {
+    match self {
+      WorkspaceError::Io { error, .. } => error.to_string(),
+      WorkspaceError::Discovery(error) => error.to_string(),
+      WorkspaceError::Syntax { error, .. } => error.to_string(),
+      WorkspaceError::Descriptor { error, .. } => error.to_string(),
+      WorkspaceError::Duplicate { name, first, .. } => format!("{} is already declared in {:?}", name, first),
+    }
+  }
+}
+
+impl Display for WorkspaceError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self.path() {
+      Some(path) => write!(f, "{:?}: {}", path, self.message()),
+      None => write!(f, "{}", self.message()),
+    }
+  }
+}
+
+impl From<DiscoveryError> for WorkspaceError {
+  fn from(error: DiscoveryError) -> Self {
+    WorkspaceError::Discovery(error)
+  }
+}
+
+impl Error for WorkspaceError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      WorkspaceError::Io { error, .. } => Some(error),
+      WorkspaceError::Discovery(error) => Some(error),
+      WorkspaceError::Syntax { error, .. } => Some(error),
+      WorkspaceError::Descriptor { error, .. } => Some(error),
+      WorkspaceError::Duplicate { .. } => None,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+  /// Index into [Workspace::roots]
+  pub root: usize,
+  /// Relative to its root
+  pub path: PathBuf,
+  /// Name of the module of the closest `module.yaml`, see [Workspace::modules]
+  pub module: Option<String>,
+  pub content: String,
+  pub ast: Program,
+  /// Lowered [Program::body], in the same order
+  pub definitions: Vec<Definition>,
+}
+
+impl SourceFile {
+  /// Path relative to the root with `.` separators and without the extension, e.g. `battle.TankModel`
+  pub fn dotted_path(&self) -> String {
+    self.path.to_string_lossy().replace(".proto", "").replace(MAIN_SEPARATOR_STR, ".")
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+  Model,
+  Type,
+  Enum,
+  Interface,
+  Template,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+  pub name: String,
+  pub kind: SymbolKind,
+  /// Index into [Workspace::files]
+  pub file: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+  symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+  pub fn get(&self, name: &str) -> Option<&Symbol> {
+    self.symbols.get(name)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+    self.symbols.values()
+  }
+
+  pub fn len(&self) -> usize {
+    self.symbols.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.symbols.is_empty()
+  }
+
+  /// Returns the existing symbol if the name is already taken
+  fn insert(&mut self, symbol: Symbol) -> Result<(), &Symbol> {
+    if self.symbols.contains_key(&symbol.name) {
+      return Err(&self.symbols[&symbol.name]);
+    }
+    self.symbols.insert(symbol.name.clone(), symbol);
+    Ok(())
+  }
+}
+
+/// Directory of `.proto` files, or a single file
+#[derive(Debug, Clone)]
+pub struct WorkspaceRoot {
+  pub path: PathBuf,
+  /// Gitignore-style globs relative to [WorkspaceRoot::path], see [crate::discovery]
+  pub include: Vec<String>,
+  pub exclude: Vec<String>,
+  /// Definitions are resolvable, but nothing is generated for them
+  pub read_only: bool,
+}
+
+impl WorkspaceRoot {
+  pub fn new(path: impl AsRef<Path>) -> WorkspaceRoot {
+    WorkspaceRoot { path: path.as_ref().to_path_buf(), include: Vec::new(), exclude: Vec::new(), read_only: false }
+  }
+
+  pub fn include(mut self, include: Vec<String>) -> WorkspaceRoot {
+    self.include = include;
+    self
+  }
+
+  pub fn exclude(mut self, exclude: Vec<String>) -> WorkspaceRoot {
+    self.exclude = exclude;
+    self
+  }
+
+  pub fn read_only(mut self, read_only: bool) -> WorkspaceRoot {
+    self.read_only = read_only;
+    self
+  }
+}
+
+#[derive(Debug)]
+pub struct Workspace {
+  /// Directories of single file roots are stored instead of the file
+  pub roots: Vec<WorkspaceRoot>,
+  /// Sorted by root, then by path
+  pub files: Vec<SourceFile>,
+  /// Module name -> module
+  pub modules: BTreeMap<String, Module>,
+  pub symbols: SymbolTable,
+  /// Roots as passed to the loader, for [Workspace::reload]
+  loaded_roots: Vec<WorkspaceRoot>,
+}
+
+impl Workspace {
+  /// Loads all `.proto` files under `root`. If `root` is a file, only that file is loaded and
+  /// its directory is used as the root.
+  pub fn load(root: impl AsRef<Path>) -> Result<Workspace, WorkspaceError> {
+    Workspace::load_roots(vec![WorkspaceRoot::new(root)])
+  }
+
+  /// Loads several roots into one namespace, see [Workspace::load]. Definitions and modules must
+  /// have unique names across all roots.
+  pub fn load_roots(roots: Vec<WorkspaceRoot>) -> Result<Workspace, WorkspaceError> {
+    let (workspace, mut errors) = Workspace::load_partial(roots);
+    if errors.is_empty() {
+      Ok(workspace)
+    } else {
+      Err(errors.remove(0))
+    }
+  }
+
+  /// Same as [Workspace::load_roots], but does not stop at the first error. Files that cannot be
+  /// read or parsed, definitions that cannot be lowered and duplicate declarations are left out
+  /// of the returned workspace.
+  pub fn load_partial(roots: Vec<WorkspaceRoot>) -> (Workspace, Vec<WorkspaceError>) {
+    Workspace::load_reusing(roots, HashMap::new())
+  }
+
+  /// Loads the roots of this workspace again, see [Workspace::load_partial]. Discovery, module
+  /// descriptors and lowering run again, but files whose content did not change keep their
+  /// syntax tree instead of being parsed again.
+  pub fn reload(self) -> (Workspace, Vec<WorkspaceError>) {
+    let mut parsed = HashMap::new();
+    for file in self.files {
+      parsed.insert(self.roots[file.root].path.join(&file.path), (file.content, file.ast));
+    }
+    Workspace::load_reusing(self.loaded_roots, parsed)
+  }
+
+  /// `parsed`: full path -> content and syntax tree of a previous load
+  fn load_reusing(mut roots: Vec<WorkspaceRoot>, mut parsed: HashMap<PathBuf, (String, Program)>) -> (Workspace, Vec<WorkspaceError>) {
+    let loaded_roots = roots.clone();
+    let mut errors = Vec::new();
+    let mut sources = Vec::new();
+    for root in &mut roots {
+      sources.push(if root.path.is_file() {
+        let parent = root.path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let path = PathBuf::from(root.path.file_name().unwrap());
+        let module_dirs = if parent.join(MODULE_DESCRIPTOR).exists() { vec![PathBuf::new()] } else { Vec::new() };
+        root.path = parent;
+        (vec![path], module_dirs)
+      } else {
+        discover(root).unwrap_or_else(|error| {
+          errors.push(error);
+          (Vec::new(), Vec::new())
+        })
+      });
+    }
+
+    let mut modules: BTreeMap<String, Module> = BTreeMap::new();
+    let mut module_names = HashMap::new();
+    let mut pending = Vec::new();
+    for (index, (root, (paths, module_dirs))) in roots.iter().zip(sources).enumerate() {
+      for dir in module_dirs {
+        let path = root.path.join(&dir).join(MODULE_DESCRIPTOR);
+        let descriptor = match read_descriptor(&path) {
+          Ok(descriptor) => descriptor,
+          Err(error) => {
+            errors.push(error);
+            continue;
+          }
+        };
+
+        let name = descriptor.name.clone().unwrap_or_else(|| match dir.to_string_lossy().replace(MAIN_SEPARATOR_STR, "/") {
+          name if name.is_empty() => "root".to_owned(),
+          name => name,
+        });
+        info!("Found module '{}' descriptor at {:?}", name, path);
+        if let Some(existing) = modules.get(&name) {
+          let first = roots[existing.root].path.join(&existing.dir).join(MODULE_DESCRIPTOR);
+          errors.push(WorkspaceError::Duplicate { name: format!("module '{}'", name), first, second: path });
+          continue;
+        }
+        module_names.insert((index, dir.clone()), name.clone());
+        modules.insert(name.clone(), Module { name, root: index, dir, descriptor });
+      }
+
+      for path in paths {
+        let previous = parsed.remove(&root.path.join(&path));
+        pending.push((index, path_module(&module_names, index, &path), path, previous));
+      }
+    }
+
+    // Files are read, parsed and lowered in parallel, but collected and declared in workspace order
+    let sources = pending.into_par_iter()
+      .map(|(index, module, path, previous)| {
+        let full_path = roots[index].path.join(&path);
+        let content = std::fs::read_to_string(&full_path).map_err(|error| WorkspaceError::Io { path: full_path.clone(), error })?;
+        let ast = match previous.filter(|(previous, _)| *previous == content) {
+          Some((_, ast)) => ast,
+          None => parse_source(&full_path, &content)?,
+        };
+        Ok(SourceFile { root: index, module, path, content, ast, definitions: Vec::new() })
+      })
+      .collect::<Vec<Result<SourceFile, WorkspaceError>>>();
+    let mut files = Vec::new();
+    for source in sources {
+      match source {
+        Ok(file) => files.push(file),
+        Err(error) => errors.push(error),
+      }
+    }
+
+    let full_paths = files.iter().map(|it: &SourceFile| roots[it.root].path.join(&it.path)).collect::<Vec<_>>();
+    let declarations = Declarations::collect(files.iter().map(|it| &it.ast));
+    let lowered = files.par_iter()
+      .map(|file| file.ast.body.iter().map(|item| lower(item, &declarations).map_err(|error| error.locate(&file.content))).collect::<Vec<_>>())
+      .collect::<Vec<_>>();
+
+    let mut symbols = SymbolTable::default();
+    for (index, (file, lowered)) in files.iter_mut().zip(lowered).enumerate() {
+      let mut definitions = Vec::new();
+      for definition in lowered {
+        let definition = match definition {
+          Ok(definition) => definition,
+          Err(error) => {
+            errors.push(WorkspaceError::Syntax { path: full_paths[index].clone(), error });
+            continue;
+          }
+        };
+
+        let symbol = match &definition {
+          Definition::Meta(_) => None,
+          Definition::Model(model) => Some((model.name.clone(), SymbolKind::Model)),
+          Definition::Type(type_def) => Some((type_def.name.clone(), SymbolKind::Type)),
+          Definition::Enum(enum_def) => Some((enum_def.name.clone(), SymbolKind::Enum)),
+          Definition::Interface(interface) => Some((interface.name.clone(), SymbolKind::Interface)),
+          Definition::Template(template) => Some((template.name.clone(), SymbolKind::Template)),
+        };
+        if let Some((name, kind)) = symbol {
+          if let Err(existing) = symbols.insert(Symbol { name: name.clone(), kind, file: index }) {
+            errors.push(WorkspaceError::Duplicate { name, first: full_paths[existing.file].clone(), second: full_paths[index].clone() });
+            continue;
+          }
+        }
+
+        definitions.push(definition);
+      }
+      file.definitions = definitions;
+    }
+
+    info!("Loaded {} files, {} symbols", files.len(), symbols.len());
+    (Workspace { roots, files, modules, symbols, loaded_roots }, errors)
+  }
+
+  /// Full path of a workspace file
+  pub fn path(&self, file: &SourceFile) -> PathBuf {
+    self.roots[file.root].path.join(&file.path)
+  }
+
+  pub fn definitions(&self) -> impl Iterator<Item = (&SourceFile, &Definition)> {
+    self.files.iter().flat_map(|file| file.definitions.iter().map(move |definition| (file, definition)))
+  }
+
+  pub fn models(&self) -> impl Iterator<Item = &hl::Model> {
+    self.definitions().filter_map(|(_, definition)| match definition {
+      Definition::Model(model) => Some(model),
+      _ => None
+    })
+  }
+
+  /// File declaring the symbol with the given name
+  pub fn symbol_file(&self, name: &str) -> Option<&SourceFile> {
+    self.symbols.get(name).map(|symbol| &self.files[symbol.file])
+  }
+
+  pub fn module(&self, file: &SourceFile) -> Option<&Module> {
+    file.module.as_ref().and_then(|it| self.modules.get(it))
+  }
+
+  /// Given modules and every module they depend on, directly or not, sorted
+  pub fn with_dependencies(&self, modules: &[String]) -> Result<Vec<String>, ModuleError> {
+    let mut result = BTreeSet::new();
+    let mut queue = modules.iter().map(|it| (it.clone(), None)).collect::<Vec<_>>();
+    while let Some((name, referenced_by)) = queue.pop() {
+      let module = self.modules.get(&name).ok_or(ModuleError::UnknownModule { module: name.clone(), referenced_by })?;
+      if !result.insert(name.clone()) {
+        continue;
+      }
+      for dependency in module.descriptor.depends_on.iter().flatten() {
+        queue.push((dependency.clone(), Some(name.clone())));
+      }
+    }
+    Ok(result.into_iter().collect())
+  }
+
+  /// Checks module dependencies, orphan files and cross-module references against `depends_on`
+  /// and `exports`
+  pub fn validate(&self) -> Vec<ModuleError> {
+    let mut errors = Vec::new();
+    for module in self.modules.values() {
+      for dependency in module.descriptor.depends_on.iter().flatten() {
+        if !self.modules.contains_key(dependency) {
+          errors.push(ModuleError::UnknownModule { module: dependency.clone(), referenced_by: Some(module.name.clone()) });
+        }
+      }
+    }
+
+    for file in &self.files {
+      let Some(module) = self.module(file) else {
+        errors.push(ModuleError::Orphan(self.path(file)));
+        continue;
+      };
+
+      let names = file.definitions.iter().flat_map(referenced_names).collect::<BTreeSet<_>>();
+      for name in names {
+        let Some(other) = self.symbol_file(name).and_then(|it| self.module(it)) else { continue };
+        if other.name == module.name {
+          continue;
+        }
+
+        if !module.may_depend_on(&other.name) {
+          errors.push(ModuleError::UndeclaredDependency { path: self.path(file), name: name.to_owned(), module: other.name.clone() });
+        } else if !other.exports(name) {
+          errors.push(ModuleError::NotExported { path: self.path(file), name: name.to_owned(), module: other.name.clone() });
+        }
+      }
+    }
+    errors
+  }
+}
+
+/// Paths of `.proto` files under `root`, relative to it and sorted, see [crate::discovery]
+pub fn discover_sources(root: &Path, discovery: Discovery) -> Result<Vec<PathBuf>, WorkspaceError> {
+  Ok(discovery.walk(root)?)
+}
+
+/// Returns source files and directories with a module descriptor, both relative to the root
+fn discover(root: &WorkspaceRoot) -> Result<(Vec<PathBuf>, Vec<PathBuf>), WorkspaceError> {
+  let discovery = Discovery::new("proto")
+    .include(root.include.iter().cloned())
+    .exclude(root.exclude.iter().cloned())
+    .file_name(MODULE_DESCRIPTOR);
+  let (module_descriptors, paths) = discovery.walk(&root.path)?
+    .into_iter()
+    .partition::<Vec<_>, _>(|it| it.file_name().is_some_and(|it| it == MODULE_DESCRIPTOR));
+  let module_dirs = module_descriptors.into_iter().map(|it| it.parent().unwrap().to_path_buf()).collect();
+  Ok((paths, module_dirs))
+}
+
+fn read_descriptor(path: &Path) -> Result<ModuleDescriptor, WorkspaceError> {
+  let content = std::fs::read_to_string(path).map_err(|error| WorkspaceError::Io { path: path.to_path_buf(), error })?;
+  ModuleDescriptor::parse(&content).map_err(|error| WorkspaceError::Descriptor { path: path.to_path_buf(), error })
+}
+
+fn parse_source(path: &Path, content: &str) -> Result<Program, WorkspaceError> {
+  debug!("Parsing {:?}...", path);
+  let syntax_error = |error: SyntaxError| WorkspaceError::Syntax { path: path.to_path_buf(), error: error.locate(content) };
+  let tokens = tokenizer(content).map_err(syntax_error)?;
+  let mut iter = itertools::multipeek(&tokens);
+  parse_program(&mut iter).map_err(syntax_error)
+}
+
+fn lower(item: &ProgramItem, declarations: &Declarations) -> Result<Definition, SyntaxError> {
+  match item {
+    ProgramItem::Meta(meta) => Ok(Definition::Meta(convert_meta(std::slice::from_ref(meta)).remove(0))),
+    ProgramItem::Model(model) => model_to_definition(model, declarations).map(Definition::Model),
+    ProgramItem::Type(type_def) => type_to_definition(type_def, declarations).map(Definition::Type),
+    ProgramItem::Enum(enum_def) => enum_to_definition(enum_def).map(Definition::Enum),
+    ProgramItem::Interface(interface) => interface_to_definition(interface, declarations).map(Definition::Interface),
+    ProgramItem::Template(template) => template_to_definition(template).map(Definition::Template),
+  }
+}
+
+/// Name of the module of the closest `module.yaml` up the tree in the same root
+fn path_module(module_names: &HashMap<(usize, PathBuf), String>, root: usize, path: &Path) -> Option<String> {
+  path.ancestors().skip(1).find_map(|dir| module_names.get(&(root, dir.to_path_buf()))).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use itertools::Itertools;
+
+  use super::*;
+  use crate::write_tree;
+
+  #[test]
+  fn workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("battle/module.yaml", ""),
+      ("battle/WorkspaceTestModel.proto", "model WorkspaceTestModel = 1 {\n  include WorkspaceTestInterface = 10;\n}\n"),
+      ("battle/WorkspaceTestInterface.proto", "interface WorkspaceTestInterface {\n  client ping() = 1;\n}\n"),
+      ("Common.proto", "enum WorkspaceTestEnum : i32 {\n  A = 0;\n}\n"),
+      (".hidden/Broken.proto", "model {"),
+      ("excluded/Broken.proto", "model {"),
+    ]);
+
+    let workspace = Workspace::load(root).unwrap();
+
+    let paths = workspace.files.iter().map(|it| it.path.to_string_lossy().replace('\\', "/")).collect_vec();
+    assert_eq!(paths, vec!["Common.proto", "battle/WorkspaceTestInterface.proto", "battle/WorkspaceTestModel.proto"]);
+    assert_eq!(workspace.files.iter().map(|it| it.module.as_deref()).collect_vec(), vec![Some("root"), Some("battle"), Some("battle")]);
+    assert_eq!(workspace.modules.get("battle").map(|it| it.dir.as_path()), Some(std::path::Path::new("battle")));
+
+    let symbol = workspace.symbols.get("WorkspaceTestInterface").unwrap();
+    assert_eq!(symbol.kind, SymbolKind::Interface);
+    assert_eq!(workspace.symbol_file("WorkspaceTestEnum").unwrap().dotted_path(), "Common");
+
+    // Interface from another file is applied
+    let model = workspace.models().next().unwrap();
+    assert_eq!(model.client_methods.iter().map(|it| (it.name.as_str(), it.id)).collect_vec(), vec![("ping", 11)]);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn workspace_roots() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("platform/module.yaml", "name: platform\n"),
+      ("platform/common/RootsTestTeam.proto", "enum RootsTestTeam : i32 {\n  RED = 0;\n}\n"),
+      ("game/module.yaml", ""),
+      ("game/battle/RootsTestType.proto", "type RootsTestType {\n  team: RootsTestTeam = 1;\n}\n"),
+    ]);
+
+    let roots = vec![WorkspaceRoot::new(root.join("game")), WorkspaceRoot::new(root.join("platform")).read_only(true)];
+    let workspace = Workspace::load_roots(roots).unwrap();
+    let files = workspace.files.iter().map(|it| (it.root, it.path.to_string_lossy().replace('\\', "/"), it.module.as_deref())).collect_vec();
+    assert_eq!(files, vec![
+      (0, "battle/RootsTestType.proto".to_owned(), Some("root")),
+      (1, "common/RootsTestTeam.proto".to_owned(), Some("platform")),
+    ]);
+    assert_eq!(workspace.symbol_file("RootsTestTeam").unwrap().root, 1);
+    assert!(workspace.validate().is_empty());
+
+    fs::write(root.join("platform/common/RootsTestType.proto"), "type RootsTestType {\n}\n").unwrap();
+    let result = Workspace::load_roots(vec![WorkspaceRoot::new(root.join("game")), WorkspaceRoot::new(root.join("platform"))]);
+    assert!(matches!(&result, Err(WorkspaceError::Duplicate { name, second, .. }) if name == "RootsTestType" && second.starts_with(root.join("platform"))));
+
+    // Both root modules are called `root` without a name
+    fs::remove_file(root.join("platform/common/RootsTestType.proto")).unwrap();
+    fs::write(root.join("platform/module.yaml"), "").unwrap();
+    let result = Workspace::load_roots(vec![WorkspaceRoot::new(root.join("game")), WorkspaceRoot::new(root.join("platform"))]);
+    assert!(matches!(&result, Err(WorkspaceError::Duplicate { name, .. }) if name == "module 'root'"));
+  }
+
+  #[test]
+  fn workspace_reload() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_tree(root, &[
+      ("module.yaml", ""),
+      ("ReloadTestTeam.proto", "enum ReloadTestTeam : i32 {\n  RED = 0;\n}\n"),
+      ("ReloadTestType.proto", "type ReloadTestType {\n}\n"),
+    ]);
+
+    let (workspace, errors) = Workspace::load_partial(vec![WorkspaceRoot::new(root)]);
+    assert!(errors.is_empty());
+
+    fs::write(root.join("ReloadTestType.proto"), "type ReloadTestType {\n  team: ReloadTestTeam = 1;\n}\n").unwrap();
+    fs::write(root.join("ReloadTestBroken.proto"), "type {").unwrap();
+    let (workspace, errors) = workspace.reload();
+    assert!(matches!(&errors[..], [WorkspaceError::Syntax { path, .. }] if path.ends_with("ReloadTestBroken.proto")));
+    let type_def = workspace.symbol_file("ReloadTestType").unwrap();
+    assert!(type_def.content.contains("team"));
+
+    fs::remove_file(root.join("ReloadTestBroken.proto")).unwrap();
+    fs::remove_file(root.join("ReloadTestTeam.proto")).unwrap();
+    let (workspace, errors) = workspace.reload();
+    assert!(errors.is_empty());
+    assert_eq!(workspace.files.iter().map(|it| it.path.to_string_lossy().into_owned()).collect_vec(), vec!["ReloadTestType.proto"]);
+    assert!(workspace.symbols.get("ReloadTestTeam").is_none());
+  }
+}